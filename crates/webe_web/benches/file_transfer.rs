@@ -0,0 +1,159 @@
+//! Compares serving a file with `sendfile` against the read/write copy loop.
+//!
+//! Both routes serve the same file over loopback keep-alive connections:
+//! `/sendfile/<path>` through `FileResponder` (zero-copy where supported) and
+//! `/copy` through a responder that hands the file to `Response::message_body`
+//! as an ordinary reader. Run with `cargo bench -p webe_web --bench file_transfer`.
+
+use std::io::Write;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use webe_web::request::Request;
+use webe_web::responders::Responder;
+use webe_web::responders::file::FileResponder;
+use webe_web::response::Response;
+use webe_web::server::{Route, RouteMap, Server};
+use webe_web::validation::Validation;
+
+const FILE_SIZES: [usize; 3] = [64 * 1024, 1024 * 1024, 16 * 1024 * 1024];
+const BYTES_PER_RUN: usize = 1024 * 1024 * 1024;
+const CONNECTIONS: usize = 4;
+
+/// Serves `path` through the generic reader path, never zero-copy.
+struct CopyResponder {
+    path: PathBuf,
+}
+
+#[async_trait]
+impl Responder for CopyResponder {
+    async fn build_response(
+        &self,
+        _request: &mut Request,
+        _params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        let file = tokio::fs::File::open(&self.path)
+            .await
+            .map_err(|_| 500u16)?;
+        let length = file.metadata().await.map_err(|_| 500u16)?.len();
+        let mut response = Response::new(200);
+        response
+            .headers
+            .insert("Content-Length".to_owned(), length.to_string());
+        response.message_body = Some(Box::pin(BufReader::new(file)));
+        Ok(response)
+    }
+}
+
+fn main() {
+    println!("webe_web file transfer benchmark");
+    println!("package_version: {}", env!("CARGO_PKG_VERSION"));
+    println!("os: {}", std::env::consts::OS);
+    println!("arch: {}", std::env::consts::ARCH);
+    println!();
+
+    let runtime = tokio::runtime::Runtime::new().expect("runtime");
+    let directory = std::env::temp_dir().join(format!("webe_bench_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).expect("bench directory");
+    for size in FILE_SIZES {
+        let path = directory.join(format!("{size}.bin"));
+        write_file(&path, size);
+        runtime.block_on(run_size(&directory, &path, size));
+    }
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+fn write_file(path: &Path, size: usize) {
+    let contents: Vec<u8> = (0..size).map(|index| (index % 251) as u8).collect();
+    let mut file = std::fs::File::create(path).expect("bench file");
+    file.write_all(&contents).expect("bench file contents");
+}
+
+async fn run_size(directory: &Path, path: &Path, size: usize) {
+    let mut routes = RouteMap::new();
+    let files = FileResponder::new(
+        directory.to_string_lossy().into_owned(),
+        "<path>".to_owned(),
+    )
+    .expect("mount point");
+    routes.add_route(Route::new("GET", "/sendfile/<path>"), files);
+    routes.add_route(
+        Route::new("GET", "/copy"),
+        CopyResponder {
+            path: path.to_owned(),
+        },
+    );
+    let addr = spawn(routes).await;
+
+    let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
+    let requests = (BYTES_PER_RUN / size / CONNECTIONS).max(1);
+    for (label, target) in [
+        ("copy", "/copy".to_owned()),
+        ("sendfile", format!("/sendfile/{file_name}")),
+    ] {
+        let elapsed = transfer(addr, &target, requests, size).await;
+        let bytes = requests * CONNECTIONS * size;
+        println!(
+            "{label}: file_size={size} requests={} elapsed={elapsed:?} throughput_mib_per_sec={:.1}",
+            requests * CONNECTIONS,
+            bytes as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64()
+        );
+    }
+    println!();
+}
+
+async fn spawn(routes: RouteMap<'static>) -> SocketAddr {
+    let server = Server::new(&Ipv4Addr::LOCALHOST, &0)
+        .await
+        .expect("server should bind on an ephemeral port");
+    let addr = server.local_addr().expect("local address");
+    tokio::spawn(async move {
+        let _ = server.start(routes).await;
+    });
+    addr
+}
+
+/// Downloads `target` `requests` times on each of [`CONNECTIONS`] keep-alive
+/// connections, returning the wall time.
+async fn transfer(addr: SocketAddr, target: &str, requests: usize, size: usize) -> Duration {
+    let started = Instant::now();
+    let clients = (0..CONNECTIONS).map(|_| {
+        let request = format!("GET {target} HTTP/1.1\r\nHost: bench\r\n\r\n");
+        tokio::spawn(async move {
+            let mut stream = BufReader::new(TcpStream::connect(addr).await.expect("connect"));
+            let mut body = vec![0u8; size];
+            for _ in 0..requests {
+                stream
+                    .get_mut()
+                    .write_all(request.as_bytes())
+                    .await
+                    .expect("send");
+                let mut length = None;
+                let mut line = String::new();
+                loop {
+                    line.clear();
+                    stream.read_line(&mut line).await.expect("head");
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        length = value.trim().parse::<usize>().ok();
+                    }
+                }
+                assert_eq!(length, Some(size), "unexpected response for {request:?}");
+                stream.read_exact(&mut body).await.expect("body");
+            }
+        })
+    });
+    for client in clients.collect::<Vec<_>>() {
+        client.await.expect("client task");
+    }
+    started.elapsed()
+}
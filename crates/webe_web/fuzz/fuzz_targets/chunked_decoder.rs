@@ -0,0 +1,28 @@
+//! Feeds arbitrary bytes, split into arbitrary reads, through `ChunkedDecoder`.
+//!
+//! The first input byte picks the read size; the decoder must never panic, and a
+//! successful decode must have consumed a complete, well-formed body.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tokio::io::{AsyncReadExt, BufReader};
+use webe_web::encoding::chunked::ChunkedDecoder;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&split, input)) = data.split_first() else {
+        return;
+    };
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let reader = BufReader::with_capacity(usize::from(split) + 1, input);
+        let mut decoder = ChunkedDecoder::new(reader);
+        let mut decoded = Vec::new();
+        if decoder.read_to_end(&mut decoded).await.is_ok() {
+            assert!(decoder.is_finished());
+            assert!(decoded.len() <= input.len());
+            assert!(decoder.trailers().get().is_some());
+        }
+    });
+});
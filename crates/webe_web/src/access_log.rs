@@ -0,0 +1,276 @@
+//! Per-request access logging through a [`webe_log::Sink`].
+//!
+//! An [`AccessLog`] attached with [`crate::config::ServerConfig::with_access_log`]
+//! receives one line per answered request, at [`LogLevel::INFO`], in the chosen
+//! [`AccessLogFormat`]: the request line, status, body bytes sent, time taken,
+//! peer address, and user agent. When request ids are enabled
+//! ([`crate::request_id`]), each line also carries the request's id: as a final
+//! quoted field in the Common and Combined formats, and as `request_id` in JSON.
+//! Connections that end in an error are reported to the same sink at
+//! [`LogLevel::ERROR`].
+//!
+//! The sink is shared by every connection task and written under a lock, so it
+//! should only queue the line (as [`webe_log::ConsoleLogger`] does) rather than
+//! block on I/O.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Local, SecondsFormat};
+use webe_log::{LogLevel, Sink};
+
+use crate::limits::ShedReason;
+
+/// How each access-log line is laid out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessLogFormat {
+    /// The Common Log Format:
+    /// `host - - [time] "request line" status bytes`.
+    Common,
+    /// The Combined Log Format: Common plus the quoted `Referer` and
+    /// `User-Agent`.
+    Combined,
+    /// One JSON object per request, including the duration in milliseconds.
+    Json,
+}
+
+/// A shared access-log destination and format.
+#[derive(Clone)]
+pub struct AccessLog {
+    sink: Arc<Mutex<Box<dyn Sink + Send>>>,
+    format: AccessLogFormat,
+}
+
+impl std::fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessLog")
+            .field("format", &self.format)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AccessLog {
+    /// Logs every request to `sink` in `format`.
+    pub fn new<S: Sink + Send + 'static>(sink: S, format: AccessLogFormat) -> AccessLog {
+        AccessLog {
+            sink: Arc::new(Mutex::new(Box::new(sink))),
+            format,
+        }
+    }
+
+    /// The line format in use.
+    pub fn format(&self) -> AccessLogFormat {
+        self.format
+    }
+
+    /// Writes one answered request.
+    pub(crate) fn record(&self, record: &AccessRecord<'_>) {
+        self.write(&LogLevel::INFO, &record.format(self.format));
+    }
+
+    /// Reports a connection that ended with `error`.
+    pub(crate) fn connection_error(&self, peer: Option<SocketAddr>, error: &dyn std::fmt::Display) {
+        let peer = peer.map_or_else(|| "-".to_owned(), |peer| peer.to_string());
+        self.write(&LogLevel::ERROR, &format!("connection {peer}: {error}"));
+    }
+
+    /// Reports a connection or request shed by a limit.
+    pub(crate) fn shed(&self, peer: Option<SocketAddr>, reason: ShedReason) {
+        let peer = peer.map_or_else(|| "-".to_owned(), |peer| peer.to_string());
+        self.write(
+            &LogLevel::WARN,
+            &format!("connection {peer}: shed: {reason}"),
+        );
+    }
+
+    /// Reports that accepting stopped at the connection limit.
+    pub(crate) fn accept_paused(&self) {
+        self.write(
+            &LogLevel::WARN,
+            "accept paused: the connection limit was reached",
+        );
+    }
+
+    fn write(&self, level: &LogLevel, message: &str) {
+        // a sink that panicked once still gets the lines that follow
+        let mut sink = self
+            .sink
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        sink.write(level, message);
+    }
+}
+
+/// What is logged about one answered request.
+pub(crate) struct AccessRecord<'a> {
+    pub(crate) time: DateTime<Local>,
+    pub(crate) peer: Option<SocketAddr>,
+    /// `None` when the request line did not parse.
+    pub(crate) method: Option<&'a str>,
+    pub(crate) uri: Option<&'a str>,
+    pub(crate) version: &'a str,
+    pub(crate) status: u16,
+    pub(crate) body_bytes: u64,
+    pub(crate) duration: Duration,
+    pub(crate) referer: Option<&'a str>,
+    pub(crate) user_agent: Option<&'a str>,
+    pub(crate) request_id: Option<&'a str>,
+}
+
+impl AccessRecord<'_> {
+    fn format(&self, format: AccessLogFormat) -> String {
+        let line = match format {
+            AccessLogFormat::Common => self.common(),
+            AccessLogFormat::Combined => format!(
+                "{} \"{}\" \"{}\"",
+                self.common(),
+                escape(self.referer.unwrap_or("-")),
+                escape(self.user_agent.unwrap_or("-")),
+            ),
+            AccessLogFormat::Json => serde_json::json!({
+                "time": self.time.to_rfc3339_opts(SecondsFormat::Millis, false),
+                "peer": self.peer.map(|peer| peer.to_string()),
+                "method": self.method,
+                "path": self.uri,
+                "version": self.version,
+                "status": self.status,
+                "bytes": self.body_bytes,
+                "duration_ms": self.duration.as_secs_f64() * 1000.0,
+                "referer": self.referer,
+                "user_agent": self.user_agent,
+                "request_id": self.request_id,
+            })
+            .to_string(),
+        };
+        match (format, self.request_id) {
+            (AccessLogFormat::Common | AccessLogFormat::Combined, Some(id)) => {
+                format!("{line} \"{}\"", escape(id))
+            }
+            _ => line,
+        }
+    }
+
+    fn common(&self) -> String {
+        let host = self
+            .peer
+            .map_or_else(|| "-".to_owned(), |peer| peer.ip().to_string());
+        let request_line = match (self.method, self.uri) {
+            (Some(method), Some(uri)) => escape(&format!("{method} {uri} {}", self.version)),
+            _ => "-".to_owned(),
+        };
+        let bytes = match self.body_bytes {
+            0 => "-".to_owned(),
+            bytes => bytes.to_string(),
+        };
+        format!(
+            "{host} - - [{}] \"{request_line}\" {} {bytes}",
+            self.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            self.status
+        )
+    }
+}
+
+/// Escapes a client-supplied value for a quoted log field: quotes, backslashes,
+/// and control or non-ASCII bytes become `\"`, `\\`, and `\xHH`, so a field can
+/// neither end early nor forge a line.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\x{byte:02x}")),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn record() -> AccessRecord<'static> {
+        AccessRecord {
+            time: Local.with_ymd_and_hms(2000, 10, 10, 13, 55, 36).unwrap(),
+            peer: Some("127.0.0.1:50000".parse().unwrap()),
+            method: Some("GET"),
+            uri: Some("/apache_pb.gif"),
+            version: "HTTP/1.0",
+            status: 200,
+            body_bytes: 2326,
+            duration: Duration::from_millis(5),
+            referer: Some("http://www.example.com/start.html"),
+            user_agent: Some("Mozilla/4.08 \"quoted\""),
+            request_id: None,
+        }
+    }
+
+    #[test]
+    fn common_and_combined_lines() {
+        let record = record();
+        let offset = record.time.format("%z").to_string();
+        let common = format!(
+            "127.0.0.1 - - [10/Oct/2000:13:55:36 {offset}] \"GET /apache_pb.gif HTTP/1.0\" 200 2326"
+        );
+        assert_eq!(record.format(AccessLogFormat::Common), common);
+        assert_eq!(
+            record.format(AccessLogFormat::Combined),
+            format!(
+                "{common} \"http://www.example.com/start.html\" \"Mozilla/4.08 \\\"quoted\\\"\""
+            )
+        );
+    }
+
+    #[test]
+    fn unparsed_requests_and_empty_bodies_use_placeholders() {
+        let mut record = record();
+        record.peer = None;
+        record.method = None;
+        record.body_bytes = 0;
+        record.status = 400;
+        let line = record.format(AccessLogFormat::Common);
+        assert!(line.starts_with("- - - ["), "{line}");
+        assert!(line.ends_with("] \"-\" 400 -"), "{line}");
+    }
+
+    #[test]
+    fn json_lines_hold_every_field() {
+        let line = record().format(AccessLogFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["peer"], "127.0.0.1:50000");
+        assert_eq!(value["method"], "GET");
+        assert_eq!(value["path"], "/apache_pb.gif");
+        assert_eq!(value["status"], 200);
+        assert_eq!(value["bytes"], 2326);
+        assert_eq!(value["duration_ms"], 5.0);
+        assert_eq!(value["user_agent"], "Mozilla/4.08 \"quoted\"");
+        assert!(value["request_id"].is_null());
+    }
+
+    #[test]
+    fn request_ids_end_every_format() {
+        let mut record = record();
+        record.request_id = Some("0187a2c4d5e6f701");
+        assert!(
+            record
+                .format(AccessLogFormat::Common)
+                .ends_with(" 200 2326 \"0187a2c4d5e6f701\"")
+        );
+        assert!(
+            record
+                .format(AccessLogFormat::Combined)
+                .ends_with("\\\"quoted\\\"\" \"0187a2c4d5e6f701\"")
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&record.format(AccessLogFormat::Json)).unwrap();
+        assert_eq!(json["request_id"], "0187a2c4d5e6f701");
+    }
+
+    #[test]
+    fn escaping_keeps_fields_on_one_line() {
+        assert_eq!(escape("a\"b\\c\r\nd"), "a\\\"b\\\\c\\x0d\\x0ad");
+    }
+}
@@ -9,8 +9,18 @@
 //! - **Response**: a known length sends `Content-Length`; a streamed body of
 //!   unknown length sends `Transfer-Encoding: chunked`; a bodyless response sends
 //!   neither.
+//! - **Decoding** (opt-in): compression codings layered under `chunked` or named
+//!   by `Content-Encoding` are resolved by [`decide_request_codings`].
 
 use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, ReadBuf, Take};
+
+use crate::constants::WEBE_BUFFER_SIZE;
+use crate::encoding::chunked::{ChunkedDecoder, Trailers};
+use crate::encoding::decompress::Coding;
 
 /// Why a body could not be framed within the supported subset. Maps to `400`.
 #[derive(Debug, PartialEq, Eq)]
@@ -19,8 +29,14 @@ pub enum BodyError {
     ConflictingFraming,
     /// A `Content-Length` value could not be parsed as a byte count.
     UnparseableLength,
-    /// The final transfer coding was not `chunked`.
+    /// The final transfer coding was not `chunked`, or (with decoding enabled) a
+    /// transfer coding below it could not be decoded.
     UnsupportedCoding,
+    /// (With decoding enabled) a `Content-Encoding` could not be decoded. Maps to
+    /// `415` rather than `400`.
+    UnsupportedContentCoding,
+    /// (With decoding enabled) more codings were stacked than the configured limit.
+    TooManyCodings,
 }
 
 impl std::fmt::Display for BodyError {
@@ -37,6 +53,14 @@ impl std::fmt::Display for BodyError {
                 f,
                 "body: unsupported transfer coding; only a final 'chunked' coding is accepted (400)"
             ),
+            BodyError::UnsupportedContentCoding => write!(
+                f,
+                "body: unsupported content coding; only gzip, deflate, and br can be decoded (415)"
+            ),
+            BodyError::TooManyCodings => write!(
+                f,
+                "body: too many stacked codings; raise DecodingLimits::max_codings to accept more (400)"
+            ),
         }
     }
 }
@@ -87,6 +111,149 @@ pub fn decide_request_body(
     }
 }
 
+/// A request body read through its [`RequestBody`] framing, which knows when
+/// the framed body has been read completely so the next pipelined request starts
+/// at the right byte.
+pub(crate) enum FramedBody<R: AsyncBufRead + Unpin> {
+    None,
+    Length(Take<R>),
+    Chunked(ChunkedDecoder<R>),
+}
+
+impl<R: AsyncBufRead + Unpin> FramedBody<R> {
+    pub(crate) fn new(framing: &RequestBody, reader: R) -> FramedBody<R> {
+        match framing {
+            RequestBody::None => FramedBody::None,
+            RequestBody::Length(length) => FramedBody::Length(reader.take(*length)),
+            RequestBody::Chunked => FramedBody::Chunked(ChunkedDecoder::new(reader)),
+        }
+    }
+
+    /// Whether every byte of the framed body has been read from the connection.
+    pub(crate) fn is_complete(&self) -> bool {
+        match self {
+            FramedBody::None => true,
+            FramedBody::Length(take) => take.limit() == 0,
+            FramedBody::Chunked(decoder) => decoder.is_finished(),
+        }
+    }
+
+    /// The trailers of a chunked body; never filled for other framings.
+    pub(crate) fn trailers(&self) -> Trailers {
+        match self {
+            FramedBody::Chunked(decoder) => decoder.trailers(),
+            _ => Trailers::default(),
+        }
+    }
+
+    /// Discards whatever the responder left unread, up to `limit` bytes.
+    ///
+    /// Returns `true` when the body is now complete, so the connection can carry
+    /// another request; `false` when more than `limit` bytes remained or the body
+    /// could not be read, in which case the connection must close.
+    pub(crate) async fn drain(&mut self, limit: u64) -> bool {
+        let mut drained = 0u64;
+        let mut scratch = [0u8; WEBE_BUFFER_SIZE];
+        while !self.is_complete() {
+            match self.read(&mut scratch).await {
+                Ok(0) | Err(_) => return self.is_complete(),
+                Ok(read) => drained += read as u64,
+            }
+            if drained > limit {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncRead for FramedBody<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            FramedBody::None => Poll::Ready(Ok(())),
+            FramedBody::Length(take) => Pin::new(take).poll_read(cx, buf),
+            FramedBody::Chunked(decoder) => Pin::new(decoder).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncBufRead for FramedBody<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<&[u8]>> {
+        match self.get_mut() {
+            FramedBody::None => Poll::Ready(Ok(&[])),
+            FramedBody::Length(take) => Pin::new(take).poll_fill_buf(cx),
+            FramedBody::Chunked(decoder) => Pin::new(decoder).poll_fill_buf(cx),
+        }
+    }
+
+    fn consume(self: Pin<&mut Self>, amount: usize) {
+        match self.get_mut() {
+            FramedBody::None => {}
+            FramedBody::Length(take) => Pin::new(take).consume(amount),
+            FramedBody::Chunked(decoder) => Pin::new(decoder).consume(amount),
+        }
+    }
+}
+
+/// Decides which compression codings must be removed from the request body,
+/// returned in removal order (the last applied coding first).
+///
+/// Transfer codings listed before the final `chunked` are removed first, then the
+/// `Content-Encoding` codings; `identity` is ignored. Returns
+/// [`BodyError::UnsupportedCoding`] for an undecodable transfer coding,
+/// [`BodyError::UnsupportedContentCoding`] for an undecodable content coding, and
+/// [`BodyError::TooManyCodings`] when more than `max_codings` remain.
+pub fn decide_request_codings(
+    headers: Option<&HashMap<String, String>>,
+    max_codings: usize,
+) -> Result<Vec<Coding>, BodyError> {
+    let mut codings = Vec::new();
+
+    if let Some(encoding) = headers.and_then(|h| h.get("transfer-encoding")) {
+        let mut transfer: Vec<&str> = encoding.split(',').map(|c| c.trim()).collect();
+        // the final coding is the already-validated `chunked` framing
+        transfer.pop();
+        for token in transfer.iter().rev() {
+            match Coding::from_token(token) {
+                Some(coding) => codings.push(coding),
+                None if Coding::is_identity(token) => {}
+                None => return Err(BodyError::UnsupportedCoding),
+            }
+        }
+    }
+
+    if let Some(encoding) = headers.and_then(|h| h.get("content-encoding")) {
+        for token in encoding.split(',').rev() {
+            match Coding::from_token(token) {
+                Some(coding) => codings.push(coding),
+                None if Coding::is_identity(token) => {}
+                None => return Err(BodyError::UnsupportedContentCoding),
+            }
+        }
+    }
+
+    if codings.len() > max_codings {
+        return Err(BodyError::TooManyCodings);
+    }
+    Ok(codings)
+}
+
+/// Drops the request headers that described a body's codings once
+/// [`decide_request_codings`]' codings are being removed: the decoded body has
+/// no `Content-Encoding` or known `Content-Length`, and only the `chunked`
+/// framing is left of a `Transfer-Encoding`.
+pub(crate) fn remove_decoded_codings(headers: &mut HashMap<String, String>) {
+    headers.remove("content-encoding");
+    headers.remove("content-length");
+    if let Some(encoding) = headers.get_mut("transfer-encoding") {
+        "chunked".clone_into(encoding);
+    }
+}
+
 /// How an outgoing response body is framed.
 #[derive(Debug, PartialEq, Eq)]
 pub enum ResponseFraming {
@@ -96,6 +263,9 @@ pub enum ResponseFraming {
     Length,
     /// Unknown length; stream with `Transfer-Encoding: chunked`.
     Chunked,
+    /// Unknown length for an `HTTP/1.0` client, which cannot decode `chunked`;
+    /// the body ends when the connection closes.
+    CloseDelimited,
 }
 
 /// Decides response framing: a bodyless response is [`ResponseFraming::None`]; a
@@ -181,6 +351,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn codings_are_resolved_in_removal_order() {
+        let h = headers(&[
+            ("transfer-encoding", "gzip, chunked"),
+            ("content-encoding", "deflate, identity, br"),
+        ]);
+        assert_eq!(
+            decide_request_codings(Some(&h), 3),
+            Ok(vec![Coding::Gzip, Coding::Brotli, Coding::Deflate])
+        );
+        assert_eq!(decide_request_codings(None, 3), Ok(vec![]));
+    }
+
+    #[test]
+    fn undecodable_or_excess_codings_are_rejected() {
+        let transfer = headers(&[("transfer-encoding", "compress, chunked")]);
+        assert_eq!(
+            decide_request_codings(Some(&transfer), 2),
+            Err(BodyError::UnsupportedCoding)
+        );
+        let content = headers(&[("content-encoding", "zstd")]);
+        assert_eq!(
+            decide_request_codings(Some(&content), 2),
+            Err(BodyError::UnsupportedContentCoding)
+        );
+        let stacked = headers(&[("content-encoding", "gzip, gzip, gzip")]);
+        assert_eq!(
+            decide_request_codings(Some(&stacked), 2),
+            Err(BodyError::TooManyCodings)
+        );
+    }
+
     #[test]
     fn response_framing_selection() {
         assert_eq!(
@@ -196,4 +398,25 @@ mod tests {
             ResponseFraming::Chunked
         );
     }
+
+    #[test]
+    fn decoded_codings_leave_only_chunked_framing() {
+        let mut h = headers(&[
+            ("transfer-encoding", "gzip, chunked"),
+            ("content-encoding", "br"),
+            ("content-type", "text/plain"),
+        ]);
+        remove_decoded_codings(&mut h);
+        assert_eq!(
+            h,
+            headers(&[
+                ("transfer-encoding", "chunked"),
+                ("content-type", "text/plain")
+            ])
+        );
+
+        let mut h = headers(&[("content-encoding", "gzip"), ("content-length", "12")]);
+        remove_decoded_codings(&mut h);
+        assert!(h.is_empty());
+    }
 }
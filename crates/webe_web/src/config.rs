@@ -0,0 +1,127 @@
+//! Server-wide options applied by the connection processor.
+//!
+//! A [`ServerConfig`] is attached to a [`crate::server::Server`] with
+//! [`crate::server::Server::with_config`] and shared read-only by every
+//! connection task. The default configuration keeps the crate's documented
+//! baseline behavior, so every option here is opt-in.
+
+use std::sync::Arc;
+
+use crate::access_log::AccessLog;
+use crate::constants::DEFAULT_DRAIN_LIMIT;
+use crate::encoding::decompress::DecodingLimits;
+use crate::error_pages::{ErrorContext, ErrorPages};
+use crate::forwarded::TrustedProxies;
+use crate::limits::ConnectionLimits;
+use crate::metrics::Metrics;
+use crate::request_id::RequestIds;
+use crate::response::Response;
+
+/// Options shared by every connection a [`crate::server::Server`] accepts.
+///
+/// Built with chained `with_*` calls starting from [`ServerConfig::new`].
+#[derive(Clone, Debug, Default)]
+pub struct ServerConfig {
+    pub(crate) request_decoding: Option<DecodingLimits>,
+    pub(crate) drain_limit: Option<u64>,
+    pub(crate) error_pages: ErrorPages,
+    pub(crate) access_log: Option<AccessLog>,
+    pub(crate) metrics: Option<Metrics>,
+    pub(crate) request_ids: Option<RequestIds>,
+    pub(crate) trusted_proxies: Option<TrustedProxies>,
+    pub(crate) connection_limits: Option<ConnectionLimits>,
+}
+
+impl ServerConfig {
+    /// Creates a configuration with every option at its default.
+    pub fn new() -> ServerConfig {
+        ServerConfig::default()
+    }
+
+    /// Transparently decodes compressed request bodies (`Content-Encoding` and
+    /// `Transfer-Encoding: gzip, chunked`) within `limits`. Without this,
+    /// responders receive the body exactly as it was sent.
+    ///
+    /// When a body is decoded, its `content-encoding` and `content-length`
+    /// request headers are removed, since they describe the encoded bytes, and
+    /// a `transfer-encoding` header is rewritten to `chunked`, the only framing
+    /// left once its codings are removed.
+    pub fn with_request_decoding(mut self, limits: DecodingLimits) -> ServerConfig {
+        self.request_decoding = Some(limits);
+        self
+    }
+
+    /// Sets how many unread request body bytes are discarded after a response so
+    /// the connection can serve the next request (default
+    /// [`DEFAULT_DRAIN_LIMIT`]). When a responder leaves more than this unread,
+    /// the connection is closed after the response instead.
+    pub fn with_drain_limit(mut self, max_bytes: u64) -> ServerConfig {
+        self.drain_limit = Some(max_bytes);
+        self
+    }
+
+    /// Renders failures with status `status` (such as `404`) through `handler`
+    /// instead of the default reason-phrase page; see [`crate::error_pages`].
+    /// The handler returns `None` to leave a failure to the catch-all handler or
+    /// the default page. Registering a status again replaces its handler.
+    pub fn with_error_handler<F>(mut self, status: u16, handler: F) -> ServerConfig
+    where
+        F: Fn(&ErrorContext) -> Option<Response> + Send + Sync + 'static,
+    {
+        self.error_pages.insert(status, Arc::new(handler));
+        self
+    }
+
+    /// Renders failures that no status handler answered through `handler`;
+    /// returning `None` falls back to the default reason-phrase page.
+    pub fn with_default_error_handler<F>(mut self, handler: F) -> ServerConfig
+    where
+        F: Fn(&ErrorContext) -> Option<Response> + Send + Sync + 'static,
+    {
+        self.error_pages.set_fallback(Arc::new(handler));
+        self
+    }
+
+    /// Writes a line to `access_log` for every answered request, and reports
+    /// connections that end in an error; see [`crate::access_log`].
+    pub fn with_access_log(mut self, access_log: AccessLog) -> ServerConfig {
+        self.access_log = Some(access_log);
+        self
+    }
+
+    /// Records request, connection, and error metrics in `metrics`, which a
+    /// [`crate::responders::metrics::MetricsResponder`] sharing the registry
+    /// exposes; see [`crate::metrics`].
+    pub fn with_metrics(mut self, metrics: Metrics) -> ServerConfig {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Gives every request an id, accepted from the client or generated, stored
+    /// in [`crate::request::Request::id`], echoed on the response, and written to
+    /// the access log; see [`crate::request_id`].
+    pub fn with_request_ids(mut self, request_ids: RequestIds) -> ServerConfig {
+        self.request_ids = Some(request_ids);
+        self
+    }
+
+    /// Believes the forwarding headers (`Forwarded`, `X-Forwarded-For`,
+    /// `X-Forwarded-Proto`, `X-Forwarded-Host`) of peers inside
+    /// `trusted_proxies` when resolving [`crate::request::Request::client`];
+    /// see [`crate::forwarded`]. Without this, the client is always the peer.
+    pub fn with_trusted_proxies(mut self, trusted_proxies: TrustedProxies) -> ServerConfig {
+        self.trusted_proxies = Some(trusted_proxies);
+        self
+    }
+
+    /// Bounds open connections (overall and per client IP) and requests in
+    /// flight, shedding the excess; see [`crate::limits`].
+    pub fn with_connection_limits(mut self, limits: ConnectionLimits) -> ServerConfig {
+        self.connection_limits = Some(limits);
+        self
+    }
+
+    pub(crate) fn drain_limit(&self) -> u64 {
+        self.drain_limit.unwrap_or(DEFAULT_DRAIN_LIMIT)
+    }
+}
@@ -14,9 +14,14 @@ pub const MAX_REQUEST_LINE_SIZE: usize = 10480000; // 1MB
 pub const MAX_HEADERS_SIZE: usize = 2048000; // 2MB - Maximum size of all headers combined
 /// Maximum overall request size, in bytes.
 pub const MAX_REQUEST_SIZE: usize = 51200000; // 50MB
+/// Maximum length, in bytes, of a chunk-size line (size plus extensions).
+pub const MAX_CHUNK_SIZE_LINE: usize = 4096; // 4KB
+/// Default number of unread request body bytes discarded to keep a connection
+/// alive; a larger remainder closes the connection instead.
+pub const DEFAULT_DRAIN_LIMIT: u64 = 65536; // 64KB
 
 // ---MIME TYPES---
-// TODO: Currently assuming everything is utf-8 encoded
+// Text types assume utf-8 encoding
 /// MIME mapping for `.js` files.
 pub const MIME_JS: (&str, &str) = ("js", "application/javascript; charset=utf-8");
 /// MIME mapping for `.json` files.
@@ -44,7 +49,100 @@ pub const MIME_ICO: (&str, &str) = ("ico", "image/x-icon");
 pub const MIME_OCTET_STREAM: &str = "application/octet-stream";
 
 /// The default extension-to-MIME table used by `FileResponder`.
-pub const DEFAULT_MIME_TYPES: [(&str, &str); 11] = [
-    MIME_JS, MIME_JSON, MIME_HTM, MIME_HTML, MIME_CSS, MIME_GIF, MIME_JPG, MIME_JPEG, MIME_PNG,
-    MIME_SVG, MIME_ICO,
+///
+/// Extensions are stored lowercase and matched case-insensitively (see
+/// [`crate::mime::lookup_extension`]).
+pub const DEFAULT_MIME_TYPES: [(&str, &str); 78] = [
+    // text and documents
+    MIME_HTM,
+    MIME_HTML,
+    MIME_CSS,
+    MIME_JS,
+    ("mjs", "application/javascript; charset=utf-8"),
+    MIME_JSON,
+    ("map", "application/json; charset=utf-8"),
+    ("jsonld", "application/ld+json; charset=utf-8"),
+    ("webmanifest", "application/manifest+json; charset=utf-8"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("text", "text/plain; charset=utf-8"),
+    ("log", "text/plain; charset=utf-8"),
+    ("md", "text/markdown; charset=utf-8"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("tsv", "text/tab-separated-values; charset=utf-8"),
+    ("xml", "application/xml; charset=utf-8"),
+    ("xhtml", "application/xhtml+xml; charset=utf-8"),
+    ("rss", "application/rss+xml; charset=utf-8"),
+    ("atom", "application/atom+xml; charset=utf-8"),
+    ("ics", "text/calendar; charset=utf-8"),
+    ("vtt", "text/vtt; charset=utf-8"),
+    ("yaml", "application/yaml; charset=utf-8"),
+    ("yml", "application/yaml; charset=utf-8"),
+    ("toml", "application/toml; charset=utf-8"),
+    ("pdf", "application/pdf"),
+    ("rtf", "application/rtf"),
+    ("doc", "application/msword"),
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    ("xls", "application/vnd.ms-excel"),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    ("ppt", "application/vnd.ms-powerpoint"),
+    (
+        "pptx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    ),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("ods", "application/vnd.oasis.opendocument.spreadsheet"),
+    ("epub", "application/epub+zip"),
+    // images
+    MIME_GIF,
+    MIME_JPG,
+    MIME_JPEG,
+    MIME_PNG,
+    MIME_SVG,
+    MIME_ICO,
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("apng", "image/apng"),
+    ("bmp", "image/bmp"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("heic", "image/heic"),
+    // fonts
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("eot", "application/vnd.ms-fontobject"),
+    // audio and video
+    ("mp3", "audio/mpeg"),
+    ("wav", "audio/wav"),
+    ("oga", "audio/ogg"),
+    ("ogg", "audio/ogg"),
+    ("opus", "audio/opus"),
+    ("flac", "audio/flac"),
+    ("aac", "audio/aac"),
+    ("m4a", "audio/mp4"),
+    ("weba", "audio/webm"),
+    ("mp4", "video/mp4"),
+    ("m4v", "video/mp4"),
+    ("webm", "video/webm"),
+    ("ogv", "video/ogg"),
+    ("mov", "video/quicktime"),
+    ("avi", "video/x-msvideo"),
+    ("mpeg", "video/mpeg"),
+    // applications and archives
+    ("wasm", "application/wasm"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tar", "application/x-tar"),
+    ("7z", "application/x-7z-compressed"),
+    ("bz2", "application/x-bzip2"),
+    ("xz", "application/x-xz"),
+    ("zst", "application/zstd"),
+    ("bin", "application/octet-stream"),
 ];
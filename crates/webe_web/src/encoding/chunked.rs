@@ -1,116 +1,343 @@
 //! Streaming chunked transfer-coding decoder and (legacy) encoder primitives.
 
-const NEW_LINE: [u8; 2] = [b'\r', b'\n'];
-use std::io::{BufRead, Cursor, Error, Read, Write};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Write};
 use std::marker::Unpin;
 use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll, ready};
 
-use pin_project_lite::pin_project;
-
-use std::task::{Context, Poll};
 use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
 
-// Must use 'where' style trait bounds because of this bug: https://github.com/taiki-e/pin-project-lite/issues/2
-pin_project! {
-  /// An [`AsyncBufRead`] adapter that decodes a chunked transfer-coded body.
-  pub struct ChunkedDecoder<B>
-  where
-    B: AsyncBufRead,
-    B: Unpin,
-  {
-    finished: bool,
-    cur_chunk_size: usize,
-    cur_chunk_pos: usize,
-    #[pin]
+use crate::constants::{MAX_CHUNK_SIZE_LINE, MAX_HEADERS_SIZE};
+
+/// The trailer fields of a chunked body, available once the body has been read
+/// to its end.
+///
+/// A cheap shared handle: the decoder fills it when it reaches the end of the
+/// trailer section, and clones (such as [`crate::request::Request::trailers`])
+/// observe the result. Names are lowercased and duplicates comma-combined, like
+/// request headers.
+#[derive(Clone, Debug, Default)]
+pub struct Trailers(Arc<OnceLock<HashMap<String, String>>>);
+
+impl Trailers {
+    /// Trailers whose fields are already known, e.g. for a response body whose
+    /// checksum was computed up front. Names are lowercased.
+    pub fn from_fields(fields: HashMap<String, String>) -> Trailers {
+        let trailers = Trailers::default();
+        trailers.fill(fields);
+        trailers
+    }
+
+    /// The trailer fields, or `None` until the whole body has been read (and
+    /// always `None` for a body that was not chunked).
+    pub fn get(&self) -> Option<&HashMap<String, String>> {
+        self.0.get()
+    }
+
+    /// Sets the fields, lowercasing names; only the first call has any effect.
+    pub(crate) fn fill(&self, fields: HashMap<String, String>) {
+        let fields = fields
+            .into_iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value))
+            .collect();
+        let _ = self.0.set(fields);
+    }
+}
+
+/// Where the decoder is within the chunked body.
+#[derive(Debug, PartialEq, Eq)]
+enum State {
+    /// Reading a `chunk-size [chunk-ext] CRLF` line.
+    Size,
+    /// Passing through chunk data; this many bytes of the chunk remain.
+    Data(u64),
+    /// Expecting the CRLF after chunk data; this many of its bytes were seen.
+    DataEnd(usize),
+    /// Reading trailer field lines up to the empty line.
+    Trailers,
+    /// The body has ended.
+    Done,
+}
+
+/// An [`AsyncBufRead`] adapter that decodes a chunked transfer-coded body.
+///
+/// A state machine over `inner`'s buffer: size lines, chunk data, CRLFs, and
+/// trailers may be split across reads at any byte. Chunk data is passed through
+/// without copying. Malformed framing (a bad size or extension, a missing CRLF, a
+/// size line longer than [`MAX_CHUNK_SIZE_LINE`], trailers larger than
+/// [`MAX_HEADERS_SIZE`], or a premature end) fails the read with
+/// [`ErrorKind::InvalidData`] or [`ErrorKind::UnexpectedEof`].
+pub struct ChunkedDecoder<B> {
+    state: State,
+    /// The partial size or trailer line being accumulated.
+    line: Vec<u8>,
+    extensions: Vec<(String, Option<String>)>,
+    trailer_fields: HashMap<String, String>,
+    trailer_size: usize,
+    trailers: Trailers,
     inner: B,
-  }
 }
 
 impl<B: AsyncBufRead + Unpin> ChunkedDecoder<B> {
     /// Wraps `inner`, decoding its chunked body as it is read.
     pub fn new(inner: B) -> ChunkedDecoder<B> {
         ChunkedDecoder {
-            finished: false,
-            cur_chunk_size: 0,
-            cur_chunk_pos: 0,
+            state: State::Size,
+            line: Vec::new(),
+            extensions: Vec::new(),
+            trailer_fields: HashMap::new(),
+            trailer_size: 0,
+            trailers: Trailers::default(),
             inner,
         }
     }
-}
 
-impl<B: AsyncBufRead + Unpin> AsyncRead for ChunkedDecoder<B> {
-    fn poll_read(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &mut ReadBuf<'_>,
-    ) -> Poll<tokio::io::Result<()>> {
-        let mut this = self.project();
-        if *this.finished {
-            return Poll::Ready(Ok(())); // already finished with all chunks
-        }
-
-        let mut inner_buf: Cursor<Vec<u8>>;
-
-        match this.inner.as_mut().poll_fill_buf(cx) {
-            Poll::Ready(Ok(data)) => inner_buf = Cursor::new(Vec::from(data)), // TODO: I hate this, but it's the only way I can find to drop the 'this.inner' borrow.
-            Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
-            Poll::Pending => return Poll::Pending,
-        }
-
-        // if start of chunk - read in the new chunk size
-        if *this.cur_chunk_size == 0 && *this.cur_chunk_pos == 0 {
-            let mut chunk_size_line = String::new();
-            match inner_buf.read_line(&mut chunk_size_line) {
-                Ok(size) => {
-                    this.inner.as_mut().consume(size);
-                    // parse chunk size from hex string
-                    match usize::from_str_radix(&chunk_size_line[..(size - 2)], 16) {
-                        Ok(chunk_size) => {
-                            if chunk_size == 0 {
-                                // completely finished with all chunks
-                                *this.finished = true;
-                                return Poll::Ready(Ok(()));
-                            } else {
-                                *this.cur_chunk_size = chunk_size;
-                            }
-                        }
-                        Err(_error) => {
-                            return Poll::Ready(Err(std::io::Error::from(
-                                std::io::ErrorKind::InvalidData,
-                            )));
-                        }
+    /// Whether the whole chunked body, through the last chunk and the trailer
+    /// section, has been read from `inner`.
+    pub fn is_finished(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// The extensions (`;name[=value]`) of the most recent chunk-size line, with
+    /// quoted values unescaped.
+    pub fn extensions(&self) -> &[(String, Option<String>)] {
+        &self.extensions
+    }
+
+    /// A handle to the trailer fields, filled once the body has been read.
+    pub fn trailers(&self) -> Trailers {
+        self.trailers.clone()
+    }
+
+    /// Runs the framing states until chunk data is available or the body ends.
+    fn poll_advance(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        loop {
+            match self.state {
+                State::Data(0) => self.state = State::DataEnd(0),
+                State::Data(_) | State::Done => return Poll::Ready(Ok(())),
+                State::DataEnd(seen) => {
+                    let data = ready!(Pin::new(&mut self.inner).poll_fill_buf(cx))?;
+                    let Some(&byte) = data.first() else {
+                        return Poll::Ready(Err(ErrorKind::UnexpectedEof.into()));
+                    };
+                    if byte != b"\r\n"[seen] {
+                        return Poll::Ready(Err(invalid("chunk data not followed by CRLF")));
                     }
+                    Pin::new(&mut self.inner).consume(1);
+                    self.state = match seen {
+                        0 => State::DataEnd(1),
+                        _ => State::Size,
+                    };
                 }
-                Err(error) => return Poll::Ready(Err(error)),
+                State::Size | State::Trailers => {
+                    let limit = match self.state {
+                        State::Size => MAX_CHUNK_SIZE_LINE,
+                        _ => MAX_HEADERS_SIZE - self.trailer_size,
+                    };
+                    ready!(self.poll_line(cx, limit))?;
+                    let line = std::mem::take(&mut self.line);
+                    match self.state {
+                        State::Size => self.size_line(&line)?,
+                        _ => self.trailer_line(&line)?,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Accumulates bytes into `self.line` through the next LF, failing once the
+    /// line would exceed `limit` bytes.
+    fn poll_line(&mut self, cx: &mut Context<'_>, limit: usize) -> Poll<std::io::Result<()>> {
+        loop {
+            let data = ready!(Pin::new(&mut self.inner).poll_fill_buf(cx))?;
+            if data.is_empty() {
+                return Poll::Ready(Err(ErrorKind::UnexpectedEof.into()));
+            }
+            let (take, complete) = match data.iter().position(|byte| *byte == b'\n') {
+                Some(end) => (end + 1, true),
+                None => (data.len(), false),
+            };
+            if self.line.len() + take > limit {
+                return Poll::Ready(Err(invalid("chunk size or trailer line too long")));
+            }
+            self.line.extend_from_slice(&data[..take]);
+            Pin::new(&mut self.inner).consume(take);
+            if complete {
+                return Poll::Ready(Ok(()));
             }
         }
-        // read from remaining chunk data
-
-        let limit = std::cmp::min(*this.cur_chunk_size - *this.cur_chunk_pos, buf.remaining());
-
-        let mut take = inner_buf.take(limit as u64);
-        let mut temp = Vec::from(buf.initialize_unfilled()); // TODO: not sure why I can't read directly into buf
-        match take.read(&mut temp) {
-            Ok(size) => {
-                temp.truncate(size);
-                this.inner.as_mut().consume(size);
-                *this.cur_chunk_pos += size;
-                if *this.cur_chunk_pos == *this.cur_chunk_size {
-                    // at the end of the current chunk
-                    // trash the ending new line chars
-                    this.inner.as_mut().consume(NEW_LINE.len());
-                    // reset self
-                    *this.cur_chunk_pos = 0;
-                    *this.cur_chunk_size = 0;
-                    buf.put_slice(&temp);
+    }
+
+    fn size_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        let line = strip_crlf(line)?;
+        let line = std::str::from_utf8(line).map_err(|_| invalid("chunk size line is not text"))?;
+        let (size, extensions) = match line.split_once(';') {
+            Some((size, extensions)) => (size.trim_end_matches([' ', '\t']), Some(extensions)),
+            None => (line, None),
+        };
+        if size.is_empty() || size.len() > 16 || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid("invalid chunk size"));
+        }
+        let size = u64::from_str_radix(size, 16).map_err(|_| invalid("invalid chunk size"))?;
+        self.extensions = match extensions {
+            Some(extensions) => {
+                parse_extensions(extensions).ok_or_else(|| invalid("invalid chunk extension"))?
+            }
+            None => Vec::new(),
+        };
+        self.state = match size {
+            0 => State::Trailers,
+            size => State::Data(size),
+        };
+        Ok(())
+    }
+
+    fn trailer_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        self.trailer_size += line.len();
+        let line = strip_crlf(line)?;
+        if line.is_empty() {
+            // end of the trailer section, and of the body
+            self.trailers.fill(std::mem::take(&mut self.trailer_fields));
+            self.state = State::Done;
+            return Ok(());
+        }
+        let line = std::str::from_utf8(line).map_err(|_| invalid("trailer is not text"))?;
+        let (name, value) = line
+            .split_once(':')
+            .filter(|(name, _)| !name.is_empty() && name.bytes().all(is_tchar))
+            .ok_or_else(|| invalid("malformed trailer field"))?;
+        let value = value.trim_matches([' ', '\t']);
+        self.trailer_fields
+            .entry(name.to_ascii_lowercase())
+            .and_modify(|combined| {
+                combined.push_str(", ");
+                combined.push_str(value);
+            })
+            .or_insert_with(|| value.to_owned());
+        Ok(())
+    }
+}
+
+fn invalid(reason: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("chunked: {reason}"))
+}
+
+/// Strips the CRLF ending a line; a bare LF is rejected.
+fn strip_crlf(line: &[u8]) -> std::io::Result<&[u8]> {
+    line.strip_suffix(b"\r\n")
+        .ok_or_else(|| invalid("line not terminated by CRLF"))
+}
+
+/// RFC 9110 `tchar`: the characters allowed in a token.
+fn is_tchar(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+/// Parses `ext-name [ "=" ext-val ] *( ";" ext-name [ "=" ext-val ] )` (the text
+/// after a chunk size's first `;`), allowing whitespace around the separators.
+/// Values are tokens or quoted strings.
+fn parse_extensions(text: &str) -> Option<Vec<(String, Option<String>)>> {
+    let bytes = text.as_bytes();
+    let mut position = 0;
+    let skip_whitespace = |position: &mut usize| {
+        while bytes
+            .get(*position)
+            .is_some_and(|b| *b == b' ' || *b == b'\t')
+        {
+            *position += 1;
+        }
+    };
+    let token = |position: &mut usize| {
+        let start = *position;
+        while bytes.get(*position).is_some_and(|b| is_tchar(*b)) {
+            *position += 1;
+        }
+        (*position > start).then(|| text[start..*position].to_owned())
+    };
+
+    let mut extensions = Vec::new();
+    loop {
+        skip_whitespace(&mut position);
+        let name = token(&mut position)?;
+        skip_whitespace(&mut position);
+        let mut value = None;
+        if bytes.get(position) == Some(&b'=') {
+            position += 1;
+            skip_whitespace(&mut position);
+            if bytes.get(position) == Some(&b'"') {
+                position += 1;
+                let mut quoted = Vec::new();
+                loop {
+                    match bytes.get(position)? {
+                        b'"' => break,
+                        b'\\' => {
+                            position += 1;
+                            quoted.push(*bytes.get(position)?);
+                        }
+                        b'\r' | b'\n' | 0 => return None,
+                        byte => quoted.push(*byte),
+                    }
+                    position += 1;
                 }
-                Poll::Ready(Ok(()))
+                position += 1; // closing quote
+                value = Some(String::from_utf8(quoted).ok()?);
+            } else {
+                value = Some(token(&mut position)?);
             }
-            Err(error) => Poll::Ready(Err(error)),
+            skip_whitespace(&mut position);
+        }
+        extensions.push((name, value));
+        match bytes.get(position) {
+            None => return Some(extensions),
+            Some(b';') => position += 1,
+            Some(_) => return None,
+        }
+    }
+}
+
+impl<B: AsyncBufRead + Unpin> AsyncBufRead for ChunkedDecoder<B> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<&[u8]>> {
+        let this = self.get_mut();
+        ready!(this.poll_advance(cx))?;
+        let State::Data(remaining) = this.state else {
+            return Poll::Ready(Ok(&[])); // done
+        };
+        let data = ready!(Pin::new(&mut this.inner).poll_fill_buf(cx))?;
+        if data.is_empty() {
+            return Poll::Ready(Err(ErrorKind::UnexpectedEof.into()));
+        }
+        let available = data
+            .len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        Poll::Ready(Ok(&data[..available]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amount: usize) {
+        let this = self.get_mut();
+        if let State::Data(remaining) = &mut this.state {
+            let amount = amount.min(usize::try_from(*remaining).unwrap_or(usize::MAX));
+            *remaining -= amount as u64;
+            Pin::new(&mut this.inner).consume(amount);
         }
     }
 }
 
+impl<B: AsyncBufRead + Unpin> AsyncRead for ChunkedDecoder<B> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<tokio::io::Result<()>> {
+        let data = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let amount = data.len().min(buf.remaining());
+        buf.put_slice(&data[..amount]);
+        self.consume(amount);
+        Poll::Ready(Ok(()))
+    }
+}
+
 /// A synchronous [`Write`] adapter that emits a body as chunked transfer-coding.
 pub struct ChunkedEncoder<W> {
     finished: bool,
@@ -205,3 +432,187 @@ async fn chunked_encoder() {
         &"4\r\nWiki\r\n5\r\npedia\r\nE\r\n in\r\n\r\nchunks.\r\n0\r\n\r\n".as_bytes()
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::io::Cursor;
+    use tokio::io::{AsyncReadExt, BufReader};
+
+    /// A reader that yields its input in the given pieces, one per read.
+    struct Pieces(VecDeque<Vec<u8>>);
+
+    impl AsyncRead for Pieces {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            if let Some(mut piece) = self.0.pop_front() {
+                let amount = piece.len().min(buf.remaining());
+                buf.put_slice(&piece[..amount]);
+                if amount < piece.len() {
+                    self.0.push_front(piece.split_off(amount));
+                }
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Splits `input` after each of the (sorted) offsets in `cuts`.
+    fn split_at(input: &[u8], cuts: &[usize]) -> BufReader<Pieces> {
+        let mut pieces = VecDeque::new();
+        let mut start = 0;
+        for &cut in cuts.iter().chain([&input.len()]) {
+            let cut = cut.clamp(start, input.len());
+            if cut > start {
+                pieces.push_back(input[start..cut].to_vec());
+                start = cut;
+            }
+        }
+        BufReader::new(Pieces(pieces))
+    }
+
+    async fn decode<B: AsyncBufRead + Unpin>(
+        decoder: &mut ChunkedDecoder<B>,
+    ) -> std::io::Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        decoder.read_to_end(&mut decoded).await?;
+        Ok(decoded)
+    }
+
+    /// A small xorshift generator, so the randomized tests are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+    }
+
+    const BODY: &[u8] =
+        b"4;name=value\r\nWiki\r\n5 ; q=\"a \\\"quoted\\\" value\"\r\npedia\r\n0\r\nExpires: never\r\nX-Sum: 1\r\nx-sum: 2\r\n\r\nNEXT";
+
+    #[tokio::test]
+    async fn decodes_one_byte_at_a_time() {
+        let mut decoder = ChunkedDecoder::new(BufReader::with_capacity(1, Cursor::new(BODY)));
+        assert_eq!(decode(&mut decoder).await.unwrap(), b"Wikipedia");
+        assert!(decoder.is_finished());
+        let trailers = decoder.trailers();
+        let trailers = trailers.get().unwrap();
+        assert_eq!(trailers.get("expires").unwrap(), "never");
+        assert_eq!(trailers.get("x-sum").unwrap(), "1, 2");
+        // nothing after the trailer section is consumed
+        let mut rest = String::new();
+        decoder.inner.read_to_string(&mut rest).await.unwrap();
+        assert_eq!(rest, "NEXT");
+    }
+
+    #[tokio::test]
+    async fn parses_chunk_extensions() {
+        let mut decoder = ChunkedDecoder::new(Cursor::new(BODY));
+        let mut first = [0u8; 4];
+        decoder.read_exact(&mut first).await.unwrap();
+        assert_eq!(
+            decoder.extensions(),
+            [("name".to_owned(), Some("value".to_owned()))]
+        );
+        let mut second = [0u8; 5];
+        decoder.read_exact(&mut second).await.unwrap();
+        assert_eq!(
+            decoder.extensions(),
+            [("q".to_owned(), Some("a \"quoted\" value".to_owned()))]
+        );
+        assert!(decoder.trailers().get().is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_framing() {
+        let long_size = format!("1;{}\r\nx\r\n0\r\n\r\n", "a".repeat(MAX_CHUNK_SIZE_LINE));
+        let cases: [(&[u8], ErrorKind); 12] = [
+            (b"\r\n", ErrorKind::InvalidData),
+            (b"\n", ErrorKind::InvalidData),
+            (b"", ErrorKind::UnexpectedEof),
+            (b"x\r\n", ErrorKind::InvalidData),
+            (b"4\nWiki\r\n0\r\n\r\n", ErrorKind::InvalidData),
+            (b"4\r\nWikiX\r\n0\r\n\r\n", ErrorKind::InvalidData),
+            (b"4\r\nWi", ErrorKind::UnexpectedEof),
+            (b"11111111111111111\r\n", ErrorKind::InvalidData),
+            (b"4;=x\r\nWiki\r\n0\r\n\r\n", ErrorKind::InvalidData),
+            (b"4;a=\"open\r\nWiki\r\n0\r\n\r\n", ErrorKind::InvalidData),
+            (b"0\r\n folded\r\n\r\n", ErrorKind::InvalidData),
+            (long_size.as_bytes(), ErrorKind::InvalidData),
+        ];
+        for (input, kind) in cases {
+            let mut decoder = ChunkedDecoder::new(Cursor::new(input));
+            let error = decode(&mut decoder).await.unwrap_err();
+            assert_eq!(error.kind(), kind, "{:?}", String::from_utf8_lossy(input));
+        }
+    }
+
+    #[tokio::test]
+    async fn oversized_trailers_are_rejected() {
+        let mut input = b"0\r\n".to_vec();
+        for index in 0..MAX_HEADERS_SIZE / 16 {
+            input.extend_from_slice(format!("x-{index:08}: value\r\n").as_bytes());
+        }
+        input.extend_from_slice(b"\r\n");
+        let mut decoder = ChunkedDecoder::new(Cursor::new(input));
+        let error = decode(&mut decoder).await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    /// Random bodies, encoded with random chunk sizes and split into random
+    /// reads, always decode to the original.
+    #[tokio::test]
+    async fn random_splits_round_trip() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..500 {
+            let body: Vec<u8> = (0..rng.below(300)).map(|_| rng.next() as u8).collect();
+            let mut encoded = Vec::new();
+            let mut rest = body.as_slice();
+            while !rest.is_empty() {
+                let (chunk, later) = rest.split_at(1 + rng.below(rest.len()));
+                encoded
+                    .extend_from_slice(format!("{:x} ;i=\"\\;\"; e\r\n", chunk.len()).as_bytes());
+                encoded.extend_from_slice(chunk);
+                encoded.extend_from_slice(b"\r\n");
+                rest = later;
+            }
+            encoded.extend_from_slice(b"0\r\nT: v\r\n\r\n");
+            let mut cuts: Vec<usize> = (0..rng.below(20))
+                .map(|_| rng.below(encoded.len()))
+                .collect();
+            cuts.sort_unstable();
+
+            let mut decoder = ChunkedDecoder::new(split_at(&encoded, &cuts));
+            assert_eq!(decode(&mut decoder).await.unwrap(), body);
+            assert_eq!(decoder.trailers().get().unwrap().get("t").unwrap(), "v");
+        }
+    }
+
+    /// Random mutations of a valid body never panic or read past the input.
+    #[tokio::test]
+    async fn random_garbage_never_panics() {
+        let mut rng = Rng(0x0123_4567_89AB_CDEF);
+        let alphabet = b"0123456789abcdefxX;=\" \t\r\n:\\";
+        for _ in 0..2000 {
+            let mut input = BODY.to_vec();
+            for _ in 0..1 + rng.below(8) {
+                let index = rng.below(input.len());
+                input[index] = alphabet[rng.below(alphabet.len())];
+            }
+            let cuts: Vec<usize> = (0..input.len()).step_by(1 + rng.below(7)).collect();
+            let mut decoder = ChunkedDecoder::new(split_at(&input, &cuts));
+            let _ = decode(&mut decoder).await;
+        }
+    }
+}
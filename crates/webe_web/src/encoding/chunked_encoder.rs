@@ -2,11 +2,32 @@
 //!
 //! [`encode_chunked`] reads a body to end and writes it as HTTP/1.1 `chunked`
 //! transfer-coding without buffering the whole body in memory, so unknown-length
-//! responses stay streamed.
+//! responses stay streamed. [`encode_chunked_with_trailers`] also writes a
+//! trailer section after the final chunk.
 
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use crate::constants::WEBE_BUFFER_SIZE;
+use crate::encoding::chunked::Trailers;
+
+/// Fields that control framing, routing, or how the content is interpreted,
+/// which a trailer section must not carry (RFC 9110 §6.5.1).
+const FORBIDDEN_TRAILERS: [&str; 14] = [
+    "authorization",
+    "cache-control",
+    "connection",
+    "content-encoding",
+    "content-length",
+    "content-range",
+    "content-type",
+    "host",
+    "keep-alive",
+    "set-cookie",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
 
 /// Streams `reader` to `writer` as `chunked` transfer-coding.
 ///
@@ -14,6 +35,41 @@ use crate::constants::WEBE_BUFFER_SIZE;
 /// is terminated with the final zero-length chunk (`0\r\n\r\n`). The body is read
 /// incrementally, never fully buffered.
 pub async fn encode_chunked<R, W>(reader: &mut R, writer: &mut W) -> std::io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    encode_chunked_with_trailers(reader, writer, None).await
+}
+
+/// Like [`encode_chunked`], then writes the fields of `trailers` between the
+/// final zero-length chunk and the closing empty line.
+///
+/// `trailers` is read only once `reader` reaches its end, so a producer may fill
+/// it while the body streams. Fields that may not appear in a trailer (framing,
+/// routing, and content metadata such as `Content-Length`), and values that
+/// would break the message, are dropped.
+pub async fn encode_chunked_with_trailers<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    trailers: Option<&Trailers>,
+) -> std::io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    write_chunked(reader, writer, trailers, false).await
+}
+
+/// [`encode_chunked_with_trailers`], also flushing `writer` after every chunk
+/// when `flush_chunks` is set, so a slow producer's chunks (e.g. server-sent
+/// events) reach the client as they happen rather than when a buffer fills.
+pub(crate) async fn write_chunked<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    trailers: Option<&Trailers>,
+    flush_chunks: bool,
+) -> std::io::Result<()>
 where
     R: AsyncRead + Unpin,
     W: AsyncWrite + Unpin,
@@ -22,21 +78,48 @@ where
     loop {
         let read = reader.read(&mut buf).await?;
         if read == 0 {
-            writer.write_all(b"0\r\n\r\n").await?;
+            let mut last = b"0\r\n".to_vec();
+            for (name, value) in trailers.into_iter().flat_map(trailer_fields) {
+                last.extend_from_slice(format!("{name}: {value}\r\n").as_bytes());
+            }
+            last.extend_from_slice(b"\r\n");
+            writer.write_all(&last).await?;
             break;
         }
         let size_line = format!("{read:X}\r\n");
         writer.write_all(size_line.as_bytes()).await?;
         writer.write_all(&buf[..read]).await?;
         writer.write_all(b"\r\n").await?;
+        if flush_chunks {
+            writer.flush().await?;
+        }
     }
     Ok(())
 }
 
+/// The fields of `trailers` that may be sent, skipping forbidden names and
+/// names or values that are not valid field syntax.
+pub(crate) fn trailer_fields(trailers: &Trailers) -> impl Iterator<Item = (&str, &str)> {
+    trailers
+        .get()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, value)| {
+            let valid_name = !name.is_empty()
+                && name
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+            let valid_value = !value.bytes().any(|b| b == b'\r' || b == b'\n' || b == 0);
+            (valid_name && valid_value && !FORBIDDEN_TRAILERS.contains(&name.as_str()))
+                .then_some((name.as_str(), value.as_str()))
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::encoding::chunked::ChunkedDecoder;
+    use std::collections::HashMap;
     use std::io::Cursor;
 
     #[tokio::test]
@@ -55,6 +138,48 @@ mod tests {
         assert_eq!(decoded, data);
     }
 
+    #[tokio::test]
+    async fn trailers_follow_the_final_chunk() {
+        let trailers = Trailers::from_fields(HashMap::from([
+            ("Digest".to_owned(), "sha-256=abc".to_owned()),
+            ("Content-Length".to_owned(), "3".to_owned()),
+            ("X-Bad".to_owned(), "a\r\nInjected: yes".to_owned()),
+        ]));
+        let mut reader = Cursor::new(b"abc".to_vec());
+        let mut encoded: Vec<u8> = Vec::new();
+        encode_chunked_with_trailers(&mut reader, &mut encoded, Some(&trailers))
+            .await
+            .unwrap();
+        assert_eq!(encoded, b"3\r\nabc\r\n0\r\ndigest: sha-256=abc\r\n\r\n");
+
+        let mut decoder = ChunkedDecoder::new(Cursor::new(encoded));
+        decoder.read_to_end(&mut Vec::new()).await.unwrap();
+        assert_eq!(
+            decoder.trailers().get().unwrap().get("digest").unwrap(),
+            "sha-256=abc"
+        );
+    }
+
+    #[tokio::test]
+    async fn chunks_are_flushed_only_when_asked() {
+        let mut buffered = tokio::io::BufWriter::new(Vec::new());
+        encode_chunked(&mut Cursor::new(b"event".to_vec()), &mut buffered)
+            .await
+            .unwrap();
+        assert!(buffered.get_ref().is_empty());
+
+        let mut flushed = tokio::io::BufWriter::new(Vec::new());
+        write_chunked(
+            &mut Cursor::new(b"event".to_vec()),
+            &mut flushed,
+            None,
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(flushed.get_ref().as_slice(), b"5\r\nevent\r\n");
+    }
+
     #[tokio::test]
     async fn empty_body_emits_only_terminator() {
         let mut reader = Cursor::new(Vec::new());
@@ -0,0 +1,199 @@
+//! Request body decompression for the `gzip`, `deflate`, and `br` codings.
+//!
+//! [`decode_body`] layers streaming decoders over an already-framed body reader,
+//! so a compressed upload is never fully buffered. Every decoded layer is capped
+//! by [`DecodingLimits::max_decoded_size`], which turns a decompression bomb into
+//! an ordinary read error instead of unbounded CPU and memory use.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use async_compression::tokio::bufread::{BrotliDecoder, GzipDecoder, ZlibDecoder};
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncBufRead, AsyncRead, BufReader, ReadBuf};
+
+use crate::constants::MAX_REQUEST_SIZE;
+
+/// A compression coding that can be removed from a request body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coding {
+    /// `gzip` (or its `x-gzip` alias).
+    Gzip,
+    /// `deflate`, the zlib-wrapped deflate format.
+    Deflate,
+    /// `br`, Brotli.
+    Brotli,
+}
+
+impl Coding {
+    /// Parses a coding token case-insensitively.
+    ///
+    /// Returns `None` for `identity` and for codings this crate cannot decode;
+    /// callers distinguish the two with [`Coding::is_identity`].
+    pub fn from_token(token: &str) -> Option<Coding> {
+        let token = token.trim();
+        if token.eq_ignore_ascii_case("gzip") || token.eq_ignore_ascii_case("x-gzip") {
+            Some(Coding::Gzip)
+        } else if token.eq_ignore_ascii_case("deflate") {
+            Some(Coding::Deflate)
+        } else if token.eq_ignore_ascii_case("br") {
+            Some(Coding::Brotli)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` for the no-op `identity` coding (or an empty token).
+    pub fn is_identity(token: &str) -> bool {
+        let token = token.trim();
+        token.is_empty() || token.eq_ignore_ascii_case("identity")
+    }
+}
+
+/// Limits applied while transparently decompressing request bodies.
+#[derive(Debug, Clone)]
+pub struct DecodingLimits {
+    /// Maximum number of bytes any decoded layer may produce. Reading past it
+    /// fails the body read with [`std::io::ErrorKind::InvalidData`].
+    pub max_decoded_size: u64,
+    /// Maximum number of stacked codings accepted on a single body; more are
+    /// rejected with `400` before the body is read.
+    pub max_codings: usize,
+}
+
+impl Default for DecodingLimits {
+    /// Caps decoded output at [`MAX_REQUEST_SIZE`] and allows two stacked codings
+    /// (e.g. `Transfer-Encoding: gzip, chunked` plus a `Content-Encoding`).
+    fn default() -> Self {
+        DecodingLimits {
+            max_decoded_size: MAX_REQUEST_SIZE as u64,
+            max_codings: 2,
+        }
+    }
+}
+
+/// Wraps `body` with a decoder for each of `codings`, in the given order.
+///
+/// `codings` must already be in removal order (the last applied coding first),
+/// as produced by [`crate::body::decide_request_codings`]. Each layer is capped
+/// at `limits.max_decoded_size` bytes of output.
+pub fn decode_body<'r>(
+    body: Pin<Box<dyn AsyncBufRead + Send + Sync + 'r>>,
+    codings: &[Coding],
+    limits: &DecodingLimits,
+) -> Pin<Box<dyn AsyncBufRead + Send + Sync + 'r>> {
+    let mut reader = body;
+    for coding in codings {
+        reader = match coding {
+            Coding::Gzip => {
+                let mut decoder = GzipDecoder::new(reader);
+                // concatenated gzip members are a valid single gzip body
+                decoder.multiple_members(true);
+                limited(decoder, limits.max_decoded_size)
+            }
+            Coding::Deflate => limited(ZlibDecoder::new(reader), limits.max_decoded_size),
+            Coding::Brotli => limited(BrotliDecoder::new(reader), limits.max_decoded_size),
+        };
+    }
+    reader
+}
+
+/// Caps `decoder`'s output at `max` bytes and re-buffers it for the next layer.
+fn limited<'r, D>(decoder: D, max: u64) -> Pin<Box<dyn AsyncBufRead + Send + Sync + 'r>>
+where
+    D: AsyncRead + Send + Sync + 'r,
+{
+    Box::pin(BufReader::new(SizeLimit {
+        inner: decoder,
+        remaining: max,
+    }))
+}
+
+pin_project! {
+    /// An [`AsyncRead`] adapter that fails once more than a fixed number of bytes
+    /// have been produced, rather than silently truncating like `take`.
+    struct SizeLimit<R> {
+        #[pin]
+        inner: R,
+        remaining: u64,
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for SizeLimit<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.project();
+        let before = buf.filled().len();
+        match this.inner.poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => {
+                let read = (buf.filled().len() - before) as u64;
+                if read > *this.remaining {
+                    return Poll::Ready(Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "decoded request body exceeds the configured size limit",
+                    )));
+                }
+                *this.remaining -= read;
+                Poll::Ready(Ok(()))
+            }
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_compression::tokio::bufread::GzipEncoder;
+    use tokio::io::AsyncReadExt;
+
+    async fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        GzipEncoder::new(data)
+            .read_to_end(&mut encoded)
+            .await
+            .unwrap();
+        encoded
+    }
+
+    #[test]
+    fn coding_tokens_are_case_insensitive() {
+        assert_eq!(Coding::from_token("GZIP"), Some(Coding::Gzip));
+        assert_eq!(Coding::from_token(" x-gzip "), Some(Coding::Gzip));
+        assert_eq!(Coding::from_token("Deflate"), Some(Coding::Deflate));
+        assert_eq!(Coding::from_token("br"), Some(Coding::Brotli));
+        assert_eq!(Coding::from_token("compress"), None);
+        assert!(Coding::is_identity("Identity"));
+    }
+
+    #[tokio::test]
+    async fn stacked_gzip_layers_are_removed_in_order() {
+        let twice = gzip(&gzip(b"hello, decoded world").await).await;
+        let body: Pin<Box<dyn AsyncBufRead + Send + Sync>> = Box::pin(std::io::Cursor::new(twice));
+        let mut reader = decode_body(
+            body,
+            &[Coding::Gzip, Coding::Gzip],
+            &DecodingLimits::default(),
+        );
+        let mut decoded = String::new();
+        reader.read_to_string(&mut decoded).await.unwrap();
+        assert_eq!(decoded, "hello, decoded world");
+    }
+
+    #[tokio::test]
+    async fn output_beyond_the_limit_is_an_error() {
+        let bomb = gzip(&vec![0u8; 64 * 1024]).await;
+        let body: Pin<Box<dyn AsyncBufRead + Send + Sync>> = Box::pin(std::io::Cursor::new(bomb));
+        let limits = DecodingLimits {
+            max_decoded_size: 1024,
+            max_codings: 1,
+        };
+        let mut reader = decode_body(body, &[Coding::Gzip], &limits);
+        let mut decoded = Vec::new();
+        let error = reader.read_to_end(&mut decoded).await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
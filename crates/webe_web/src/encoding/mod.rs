@@ -1,6 +1,9 @@
-//! Transfer-coding helpers: chunked decoding and chunked response encoding.
+//! Transfer- and content-coding helpers: chunked decoding, chunked response
+//! encoding, and request body decompression.
 
 /// Streaming chunked transfer-coding decoder/encoder primitives.
 pub mod chunked;
 /// Async chunked response-body encoder.
 pub mod chunked_encoder;
+/// Streaming `gzip`/`deflate`/`br` request body decompression.
+pub mod decompress;
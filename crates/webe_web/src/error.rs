@@ -6,6 +6,7 @@
 //! maps client-visible failures to the documented HTTP status code.
 
 use crate::body::BodyError;
+use crate::limits::ShedReason;
 use crate::request::RequestError;
 use crate::response::ResponseError;
 use crate::route::RoutingError;
@@ -22,7 +23,8 @@ pub enum WebError {
     Request(RequestError),
     /// The request used an unsupported HTTP version (`505`). Holds the version.
     Version(String),
-    /// The request or response body could not be framed (`400`).
+    /// The request or response body could not be framed or decoded (`400`, or
+    /// `415` for an undecodable `Content-Encoding`).
     Body(BodyError),
     /// The request could not be routed (`404` / `405`).
     Routing(RoutingError),
@@ -30,6 +32,8 @@ pub enum WebError {
     Response(ResponseError),
     /// A responder rejected the request; holds the responder-provided status.
     Responder(u16),
+    /// A connection limit shed the connection or request (`503`).
+    Overloaded(ShedReason),
 }
 
 impl WebError {
@@ -40,11 +44,13 @@ impl WebError {
             WebError::Bind(_) | WebError::Accept(_) => None,
             WebError::Request(_) => Some(400),
             WebError::Version(_) => Some(505),
+            WebError::Body(BodyError::UnsupportedContentCoding) => Some(415),
             WebError::Body(_) => Some(400),
             WebError::Routing(RoutingError::NotFound) => Some(404),
             WebError::Routing(RoutingError::MethodNotAllowed) => Some(405),
             WebError::Response(_) => Some(500),
             WebError::Responder(code) => Some(*code),
+            WebError::Overloaded(_) => Some(503),
         }
     }
 }
@@ -61,7 +67,7 @@ impl std::fmt::Display for WebError {
             WebError::Request(e) => write!(f, "request: {e:?} (400)"),
             WebError::Version(v) => write!(
                 f,
-                "version: unsupported HTTP version '{v}'; only HTTP/1.1 is supported (505)"
+                "version: unsupported HTTP version '{v}'; only HTTP/1.1 and HTTP/1.0 are supported (505)"
             ),
             WebError::Body(e) => write!(f, "{e}"),
             WebError::Routing(e) => write!(f, "{e}"),
@@ -69,6 +75,7 @@ impl std::fmt::Display for WebError {
             WebError::Responder(code) => {
                 write!(f, "responder: rejected the request with status {code}")
             }
+            WebError::Overloaded(reason) => write!(f, "overload: {reason} (503)"),
         }
     }
 }
@@ -0,0 +1,149 @@
+//! Application-defined error responses.
+//!
+//! When the connection processor cannot produce a responder's response (a
+//! malformed request, no matching route, a body that cannot be framed, or a
+//! responder returning an error status), it renders the failure as a response.
+//! By default that is [`StaticResponder::from_standard_code`]'s reason-phrase
+//! page. Handlers registered with [`crate::config::ServerConfig::with_error_handler`]
+//! (per status) and [`crate::config::ServerConfig::with_default_error_handler`]
+//! (catch-all) replace it, receiving an [`ErrorContext`] describing the failure
+//! and the request as far as it was parsed.
+//!
+//! A handler returns `None` to decline, passing the failure on to the catch-all
+//! handler and then the default page, so it can answer only some requests (for
+//! example JSON problem details under `/api` and the usual page elsewhere).
+//! Connections are always closed after an error response.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::WebError;
+use crate::responders::static_message::StaticResponder;
+use crate::response::Response;
+
+/// A failure being rendered, with what is known of the request that caused it.
+#[derive(Debug)]
+pub struct ErrorContext {
+    /// The status code the failure maps to; see [`WebError::client_status`].
+    pub status: u16,
+    /// What went wrong.
+    pub error: WebError,
+    /// The request method, once the request line was parsed.
+    pub method: Option<String>,
+    /// The request target, once the request line was parsed.
+    pub uri: Option<String>,
+    /// The request's HTTP version (`HTTP/1.1` until the request line parsed).
+    pub version: String,
+    /// The request headers (lowercased names), once the header block was parsed.
+    pub headers: Option<HashMap<String, String>>,
+    /// The request id, when request ids are enabled; it is also echoed on the
+    /// rendered response.
+    pub request_id: Option<String>,
+}
+
+impl ErrorContext {
+    /// Starts a context for a request whose request line has not been parsed.
+    pub(crate) fn new(error: WebError) -> ErrorContext {
+        ErrorContext {
+            status: error.client_status().unwrap_or(500),
+            error,
+            method: None,
+            uri: None,
+            version: "HTTP/1.1".to_owned(),
+            headers: None,
+            request_id: None,
+        }
+    }
+
+    /// The value of request header `name` (lowercase), if the headers were parsed.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .as_ref()
+            .and_then(|headers| headers.get(name))
+            .map(|value| value.as_str())
+    }
+}
+
+/// An error handler: renders a failure, or returns `None` to decline it.
+type ErrorHandler = Arc<dyn Fn(&ErrorContext) -> Option<Response> + Send + Sync>;
+
+/// The registered error handlers, consulted by status code and then catch-all.
+#[derive(Clone, Default)]
+pub(crate) struct ErrorPages {
+    by_status: HashMap<u16, ErrorHandler>,
+    fallback: Option<ErrorHandler>,
+}
+
+impl std::fmt::Debug for ErrorPages {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut statuses: Vec<&u16> = self.by_status.keys().collect();
+        statuses.sort_unstable();
+        f.debug_struct("ErrorPages")
+            .field("statuses", &statuses)
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
+
+impl ErrorPages {
+    pub(crate) fn insert(&mut self, status: u16, handler: ErrorHandler) {
+        self.by_status.insert(status, handler);
+    }
+
+    pub(crate) fn set_fallback(&mut self, handler: ErrorHandler) {
+        self.fallback = Some(handler);
+    }
+
+    /// Whether any handler is registered, i.e. whether request details are
+    /// worth keeping for a possible error.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.by_status.is_empty() && self.fallback.is_none()
+    }
+
+    /// Renders `context` with the first handler that accepts it, or the
+    /// default reason-phrase page.
+    pub(crate) fn render(&self, context: &ErrorContext) -> Response {
+        let handled = self
+            .by_status
+            .get(&context.status)
+            .and_then(|handler| handler(context))
+            .or_else(|| self.fallback.as_ref().and_then(|handler| handler(context)));
+        handled
+            .unwrap_or_else(|| StaticResponder::from_standard_code(context.status).quick_response())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::route::RoutingError;
+
+    fn context(path: &str) -> ErrorContext {
+        let mut context = ErrorContext::new(WebError::Routing(RoutingError::NotFound));
+        context.uri = Some(path.to_owned());
+        context
+    }
+
+    #[test]
+    fn status_handlers_decline_to_the_fallback_then_the_default() {
+        let mut pages = ErrorPages::default();
+        pages.insert(
+            404,
+            Arc::new(|context: &ErrorContext| {
+                context
+                    .uri
+                    .as_deref()
+                    .is_some_and(|uri| uri.starts_with("/api"))
+                    .then(|| Response::new(context.status))
+            }),
+        );
+        assert_eq!(pages.render(&context("/api/x")).status.code, 404);
+        // declined, and nothing else is registered: the default page
+        let default = pages.render(&context("/page"));
+        assert_eq!(default.headers.get("Content-Type").unwrap(), "text/html");
+
+        pages.set_fallback(Arc::new(|_: &ErrorContext| Some(Response::new(418))));
+        assert_eq!(pages.render(&context("/page")).status.code, 418);
+        assert_eq!(pages.render(&context("/api/x")).status.code, 404);
+    }
+}
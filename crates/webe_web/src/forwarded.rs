@@ -0,0 +1,454 @@
+//! Client address resolution behind trusted reverse proxies.
+//!
+//! Every request records the connection's peer and local addresses
+//! ([`crate::request::Request::peer_addr`] and
+//! [`crate::request::Request::local_addr`]) and a resolved [`ClientInfo`]. By
+//! default the client is simply the peer. With [`TrustedProxies`] attached through
+//! [`crate::config::ServerConfig::with_trusted_proxies`], a request whose peer is
+//! inside a trusted CIDR range is instead attributed to the address its proxies
+//! report: the `Forwarded` header (RFC 7239) when present, otherwise
+//! `X-Forwarded-For` with `X-Forwarded-Proto` and `X-Forwarded-Host`.
+//!
+//! The forwarding chain is read from the right, skipping trusted proxies, so the
+//! client is the first hop no trusted proxy vouches for; entries a client added
+//! itself further left are ignored. Headers from an untrusted peer are never
+//! believed.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+
+/// An IP network in CIDR notation, such as `10.0.0.0/8` or `fd00::/8`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+/// A CIDR range that could not be parsed.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CidrError(String);
+
+impl std::fmt::Display for CidrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "forwarded: '{}' is not an IP address or CIDR range (such as 10.0.0.0/8)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for CidrError {}
+
+impl Cidr {
+    /// The network of `address` with a `prefix_len`-bit mask; host bits are
+    /// cleared. Fails when the prefix is longer than the address.
+    pub fn new(address: IpAddr, prefix_len: u8) -> Result<Cidr, CidrError> {
+        let network = match address {
+            IpAddr::V4(v4) if prefix_len <= 32 => {
+                IpAddr::V4(Ipv4Addr::from(u32::from(v4) & mask_v4(prefix_len)))
+            }
+            IpAddr::V6(v6) if prefix_len <= 128 => {
+                IpAddr::V6(Ipv6Addr::from(u128::from(v6) & mask_v6(prefix_len)))
+            }
+            _ => return Err(CidrError(format!("{address}/{prefix_len}"))),
+        };
+        Ok(Cidr {
+            network,
+            prefix_len,
+        })
+    }
+
+    /// Whether `ip` is inside this network. IPv4-mapped IPv6 addresses match
+    /// IPv4 networks.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                u32::from(ip) & mask_v4(self.prefix_len) == u32::from(network)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                u128::from(ip) & mask_v6(self.prefix_len) == u128::from(network)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = CidrError;
+
+    /// Parses `address/prefix`, or a bare address as a single-host network.
+    fn from_str(value: &str) -> Result<Cidr, CidrError> {
+        let invalid = || CidrError(value.to_owned());
+        let (address, prefix_len) = match value.trim().split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (value.trim(), None),
+        };
+        let address: IpAddr = address.parse().map_err(|_| invalid())?;
+        let prefix_len = match (prefix_len, address) {
+            (Some(prefix), _) => prefix.parse::<u8>().map_err(|_| invalid())?,
+            (None, IpAddr::V4(_)) => 32,
+            (None, IpAddr::V6(_)) => 128,
+        };
+        Cidr::new(address, prefix_len).map_err(|_| invalid())
+    }
+}
+
+fn mask_v4(prefix_len: u8) -> u32 {
+    u32::MAX
+        .checked_shl(32 - u32::from(prefix_len))
+        .unwrap_or(0)
+}
+
+fn mask_v6(prefix_len: u8) -> u128 {
+    u128::MAX
+        .checked_shl(128 - u32::from(prefix_len))
+        .unwrap_or(0)
+}
+
+/// The proxies whose forwarding headers are believed.
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies {
+    ranges: Vec<Cidr>,
+}
+
+impl TrustedProxies {
+    /// Trusts peers inside any of `ranges`.
+    pub fn new(ranges: impl IntoIterator<Item = Cidr>) -> TrustedProxies {
+        TrustedProxies {
+            ranges: ranges.into_iter().collect(),
+        }
+    }
+
+    /// Trusts peers inside any of `ranges`, given in CIDR notation (such as
+    /// `["10.0.0.0/8", "127.0.0.1"]`).
+    pub fn parse<S: AsRef<str>>(ranges: &[S]) -> Result<TrustedProxies, CidrError> {
+        let ranges = ranges
+            .iter()
+            .map(|range| range.as_ref().parse())
+            .collect::<Result<Vec<Cidr>, CidrError>>()?;
+        Ok(TrustedProxies { ranges })
+    }
+
+    /// Whether `ip` is a trusted proxy.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.ranges.iter().any(|range| range.contains(ip))
+    }
+}
+
+/// Who a request came from, after any trusted proxies are accounted for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientInfo {
+    /// The client's address: the peer's, or the one trusted proxies reported.
+    /// `None` when unknown, such as an obfuscated `Forwarded` identifier.
+    pub ip: Option<IpAddr>,
+    /// The scheme the client used, `http` or `https`.
+    pub scheme: String,
+    /// The host the client requested, from `Host` or a trusted proxy.
+    pub host: Option<String>,
+}
+
+impl Default for ClientInfo {
+    fn default() -> ClientInfo {
+        ClientInfo {
+            ip: None,
+            scheme: "http".to_owned(),
+            host: None,
+        }
+    }
+}
+
+/// One hop of a forwarding chain, as a proxy described it.
+#[derive(Debug, Default, PartialEq)]
+struct Hop {
+    ip: Option<IpAddr>,
+    proto: Option<String>,
+    host: Option<String>,
+}
+
+/// Resolves the client of a request from `peer` with `headers` (lowercased
+/// names), believing forwarding headers only from a `trusted` peer.
+pub(crate) fn resolve_client(
+    trusted: Option<&TrustedProxies>,
+    peer: Option<SocketAddr>,
+    headers: Option<&HashMap<String, String>>,
+) -> ClientInfo {
+    let header = |name: &str| headers.and_then(|headers| headers.get(name));
+    let direct = ClientInfo {
+        ip: peer.map(|peer| peer.ip().to_canonical()),
+        scheme: "http".to_owned(),
+        host: header("host").cloned(),
+    };
+    let (Some(trusted), Some(peer)) = (trusted, peer) else {
+        return direct;
+    };
+    if !trusted.contains(peer.ip()) {
+        return direct;
+    }
+
+    let hops = match (header("forwarded"), header("x-forwarded-for")) {
+        (Some(forwarded), _) => forwarded_hops(forwarded),
+        (None, Some(forwarded_for)) => x_forwarded_hops(
+            forwarded_for,
+            header("x-forwarded-proto").map(String::as_str),
+            header("x-forwarded-host").map(String::as_str),
+        ),
+        (None, None) => return direct,
+    };
+    // the rightmost hop no trusted proxy vouches for is the client
+    let mut client = None;
+    for hop in hops.into_iter().rev() {
+        let trusted_hop = hop.ip.is_some_and(|ip| trusted.contains(ip));
+        client = Some(hop);
+        if !trusted_hop {
+            break;
+        }
+    }
+    match client {
+        Some(hop) => ClientInfo {
+            ip: hop.ip,
+            scheme: hop.proto.unwrap_or(direct.scheme),
+            host: hop.host.or(direct.host),
+        },
+        None => direct,
+    }
+}
+
+/// The hops of an RFC 7239 `Forwarded` header, client first.
+fn forwarded_hops(value: &str) -> Vec<Hop> {
+    split_unquoted(value, ',')
+        .into_iter()
+        .map(|element| {
+            let mut hop = Hop::default();
+            for pair in split_unquoted(element, ';') {
+                let Some((name, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                let value = unquote(value.trim());
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "for" => hop.ip = parse_node(&value),
+                    "proto" => hop.proto = parse_proto(&value),
+                    "host" => hop.host = parse_host(&value),
+                    _ => {}
+                }
+            }
+            hop
+        })
+        .collect()
+}
+
+/// The hops of `X-Forwarded-For`, client first. Each proxy's proto and host
+/// are matched up by position when every hop has one; otherwise the last
+/// (nearest proxy's) value applies to the whole chain.
+fn x_forwarded_hops(forwarded_for: &str, proto: Option<&str>, host: Option<&str>) -> Vec<Hop> {
+    let list = |value: Option<&str>| -> Vec<String> {
+        value
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|item| item.trim().to_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let (protos, hosts) = (list(proto), list(host));
+    let addresses: Vec<&str> = forwarded_for.split(',').map(str::trim).collect();
+    let pick = |values: &[String], index: usize| match values.len() == addresses.len() {
+        true => values.get(index).cloned(),
+        false => values.last().cloned(),
+    };
+    addresses
+        .iter()
+        .enumerate()
+        .map(|(index, address)| Hop {
+            ip: parse_node(address),
+            proto: pick(&protos, index).and_then(|proto| parse_proto(&proto)),
+            host: pick(&hosts, index).and_then(|host| parse_host(&host)),
+        })
+        .collect()
+}
+
+/// An address in a forwarding header: `192.0.2.1`, `192.0.2.1:8080`,
+/// `2001:db8::1`, or `[2001:db8::1]:8080`. Obfuscated identifiers (`unknown`,
+/// `_hidden`) and anything else are `None`.
+fn parse_node(value: &str) -> Option<IpAddr> {
+    let value = value.trim();
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        return Some(ip.to_canonical());
+    }
+    if let Ok(socket) = value.parse::<SocketAddr>() {
+        return Some(socket.ip().to_canonical());
+    }
+    let bracketed = value.strip_prefix('[')?.split(']').next()?;
+    bracketed
+        .parse::<Ipv6Addr>()
+        .ok()
+        .map(|ip| IpAddr::V6(ip).to_canonical())
+}
+
+fn parse_proto(value: &str) -> Option<String> {
+    let proto = value.trim().to_ascii_lowercase();
+    matches!(proto.as_str(), "http" | "https").then_some(proto)
+}
+
+fn parse_host(value: &str) -> Option<String> {
+    let host = value.trim();
+    let valid = !host.is_empty()
+        && host
+            .bytes()
+            .all(|byte| byte.is_ascii_graphic() && byte != b'"' && byte != b'\\');
+    valid.then(|| host.to_owned())
+}
+
+/// Splits on `separator` outside double-quoted strings.
+fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (index, character) in value.char_indices() {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            _ if character == separator && !quoted => {
+                parts.push(value[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(value[start..].trim());
+    parts
+}
+
+/// Removes the quotes and backslash escapes of a quoted string.
+fn unquote(value: &str) -> String {
+    match value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        Some(inner) => {
+            let mut unquoted = String::with_capacity(inner.len());
+            let mut characters = inner.chars();
+            while let Some(character) = characters.next() {
+                match character {
+                    '\\' => unquoted.extend(characters.next()),
+                    _ => unquoted.push(character),
+                }
+            }
+            unquoted
+        }
+        None => value.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn ip(value: &str) -> Option<IpAddr> {
+        Some(value.parse().unwrap())
+    }
+
+    fn proxies() -> TrustedProxies {
+        TrustedProxies::parse(&["10.0.0.0/8", "fd00::/8"]).unwrap()
+    }
+
+    #[test]
+    fn cidr_ranges_match_their_networks() {
+        let range: Cidr = "192.168.4.7/22".parse().unwrap();
+        assert!(range.contains("192.168.5.200".parse().unwrap()));
+        assert!(!range.contains("192.168.8.1".parse().unwrap()));
+        assert!(range.contains("::ffff:192.168.4.1".parse().unwrap()));
+        let host: Cidr = "2001:db8::1".parse().unwrap();
+        assert!(host.contains("2001:db8::1".parse().unwrap()));
+        assert!(!host.contains("2001:db8::2".parse().unwrap()));
+        let everything: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(everything.contains("203.0.113.9".parse().unwrap()));
+        for invalid in ["10.0.0.0/33", "fd00::/129", "example.com", "10.0.0.0/x"] {
+            assert!(invalid.parse::<Cidr>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn untrusted_peers_are_the_client_whatever_they_claim() {
+        let peer = "203.0.113.5:4000".parse().ok();
+        let claimed = headers(&[
+            ("host", "app.example"),
+            ("x-forwarded-for", "198.51.100.1"),
+            ("x-forwarded-proto", "https"),
+        ]);
+        let client = resolve_client(Some(&proxies()), peer, Some(&claimed));
+        assert_eq!(client.ip, ip("203.0.113.5"));
+        assert_eq!(client.scheme, "http");
+        assert_eq!(client.host.as_deref(), Some("app.example"));
+        // without a trusted-proxy configuration, likewise
+        assert_eq!(
+            resolve_client(None, peer, Some(&claimed)).ip,
+            ip("203.0.113.5")
+        );
+    }
+
+    #[test]
+    fn x_forwarded_for_is_read_from_the_right_past_trusted_proxies() {
+        let peer = "10.0.0.2:4000".parse().ok();
+        let forwarded = headers(&[
+            ("host", "internal:8080"),
+            ("x-forwarded-for", "1.1.1.1, 198.51.100.7, 10.0.0.9"),
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "app.example"),
+        ]);
+        let client = resolve_client(Some(&proxies()), peer, Some(&forwarded));
+        // 1.1.1.1 was supplied by the client itself and is ignored
+        assert_eq!(client.ip, ip("198.51.100.7"));
+        assert_eq!(client.scheme, "https");
+        assert_eq!(client.host.as_deref(), Some("app.example"));
+    }
+
+    #[test]
+    fn forwarded_elements_supply_the_clients_proto_and_host() {
+        let peer = "[fd00::1]:4000".parse().ok();
+        let forwarded = headers(&[(
+            "forwarded",
+            "for=\"[2001:db8:cafe::17]:4711\";proto=https;host=\"app.example\", for=10.1.2.3;proto=http",
+        )]);
+        let client = resolve_client(Some(&proxies()), peer, Some(&forwarded));
+        assert_eq!(client.ip, ip("2001:db8:cafe::17"));
+        assert_eq!(client.scheme, "https");
+        assert_eq!(client.host.as_deref(), Some("app.example"));
+
+        let hidden = headers(&[("forwarded", "for=_hidden, for=10.1.2.3")]);
+        let client = resolve_client(Some(&proxies()), peer, Some(&hidden));
+        assert_eq!(client.ip, None);
+    }
+
+    #[test]
+    fn a_chain_of_only_trusted_proxies_ends_at_its_first_hop() {
+        let peer = "10.0.0.2:4000".parse().ok();
+        let forwarded = headers(&[("x-forwarded-for", "10.9.9.9, 10.0.0.3")]);
+        let client = resolve_client(Some(&proxies()), peer, Some(&forwarded));
+        assert_eq!(client.ip, ip("10.9.9.9"));
+        // and a trusted peer with no forwarding headers is the client
+        let client = resolve_client(Some(&proxies()), peer, None);
+        assert_eq!(client.ip, ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn quoted_forwarded_values_may_contain_separators() {
+        assert_eq!(
+            split_unquoted("for=a;host=\"x,y;z\", for=b", ','),
+            vec!["for=a;host=\"x,y;z\"", "for=b"]
+        );
+        assert_eq!(unquote("\"a\\\"b\""), "a\"b");
+        assert_eq!(parse_node("192.0.2.1:80"), ip("192.0.2.1"));
+        assert_eq!(parse_node("[2001:db8::1]"), ip("2001:db8::1"));
+        assert_eq!(parse_node("unknown"), None);
+    }
+}
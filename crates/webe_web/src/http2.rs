@@ -0,0 +1,478 @@
+//! HTTP/2 connections (the `http2` cargo feature).
+//!
+//! [`serve_connection`] runs an HTTP/2 connection over any transport, mapping
+//! each stream onto the same [`Request`] / [`Response`] / responder flow the
+//! HTTP/1.1 processor uses, so existing responders work unchanged. Streams on one
+//! connection are handled concurrently.
+//!
+//! Plaintext connections that open with the HTTP/2 connection preface
+//! (prior-knowledge `h2c`) are detected by [`crate::processor::process_connection`]
+//! and handed here automatically. The crate has no TLS listener; an application
+//! terminating TLS itself advertises [`ALPN_H2`] and calls [`serve_connection`]
+//! directly when the client selects it.
+//!
+//! Differences from HTTP/1.1: connection-specific headers (`Connection`,
+//! `Keep-Alive`, `Transfer-Encoding`, `Upgrade`) are dropped from responses,
+//! `:authority` is exposed as the `host` header, [`Request::version`] is
+//! `HTTP/2.0`, and `101` upgrades are not available. [`Response::trailers`] are
+//! sent as a trailing HEADERS frame.
+
+use std::collections::HashMap;
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures_util::StreamExt;
+use futures_util::stream::FuturesUnordered;
+use h2::server::SendResponse;
+use h2::{RecvStream, SendStream};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+use crate::access_log::AccessRecord;
+use crate::body::{decide_request_codings, remove_decoded_codings};
+use crate::config::ServerConfig;
+use crate::constants::WEBE_BUFFER_SIZE;
+use crate::encoding::chunked::Trailers;
+use crate::encoding::chunked_encoder::trailer_fields;
+use crate::encoding::decompress::decode_body;
+use crate::error::WebError;
+use crate::error_pages::ErrorContext;
+use crate::forwarded::resolve_client;
+use crate::limits::report_shed;
+use crate::metrics::{Metered, Metrics};
+use crate::processor::{ConnectionAddrs, echo_request_id};
+use crate::request::Request;
+use crate::response::Response;
+use crate::route::{RoutingError, parse_route_params};
+use crate::server::RouteMap;
+use crate::validation::Refusal;
+
+/// The ALPN protocol identifier a TLS listener advertises for HTTP/2.
+pub const ALPN_H2: &[u8] = b"h2";
+
+/// The client connection preface that starts every HTTP/2 connection.
+pub(crate) const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// Headers that are meaningful only for a single HTTP/1.1 connection and are
+/// forbidden in HTTP/2 messages.
+const CONNECTION_HEADERS: [&str; 5] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Serves one HTTP/2 connection until the client closes it.
+///
+/// `io` is the connection after any TLS handshake, positioned before the
+/// client's connection preface. Protocol errors end the connection (the client
+/// is sent `GOAWAY`), so this only returns once all of its streams are done.
+pub async fn serve_connection<IO>(
+    io: IO,
+    routes: Arc<RouteMap<'_>>,
+    config: Arc<ServerConfig>,
+) -> Result<(), WebError>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    serve(io, routes, config, ConnectionAddrs::default()).await
+}
+
+/// [`serve_connection`] for a connection between `addrs`, as recorded on its
+/// requests and in the access log.
+pub(crate) async fn serve<IO>(
+    io: IO,
+    routes: Arc<RouteMap<'_>>,
+    config: Arc<ServerConfig>,
+    addrs: ConnectionAddrs,
+) -> Result<(), WebError>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    let io = Metered::new(io, config.metrics.clone());
+    let mut connection = match h2::server::handshake(io).await {
+        Ok(connection) => connection,
+        Err(_error) => return Ok(()), // not a valid HTTP/2 client
+    };
+
+    let mut streams = FuturesUnordered::new();
+    let mut accepting = true;
+    while accepting || !streams.is_empty() {
+        tokio::select! {
+            accepted = connection.accept(), if accepting => match accepted {
+                Some(Ok((request, respond))) => {
+                    streams.push(serve_stream(request, respond, &routes, &config, addrs));
+                }
+                Some(Err(_)) | None => accepting = false,
+            },
+            Some(()) = streams.next(), if !streams.is_empty() => {}
+        }
+    }
+    Ok(())
+}
+
+/// Routes, validates, and answers a single stream.
+async fn serve_stream(
+    request: http::Request<RecvStream>,
+    mut respond: SendResponse<Bytes>,
+    routes: &RouteMap<'_>,
+    config: &ServerConfig,
+    addrs: ConnectionAddrs,
+) {
+    let started = Instant::now();
+    let time = config.access_log.as_ref().map(|_| chrono::Local::now());
+    let _in_flight = config.metrics.as_ref().map(Metrics::request_started);
+    let (parts, body) = request.into_parts();
+    let request_id = config
+        .request_ids
+        .as_ref()
+        .and_then(|request_ids| request_ids.assign(Some(&request_headers(&parts))));
+    let mut seen = SeenStream::default();
+    let admitted = match &config.connection_limits {
+        Some(limits) => limits.start_request(),
+        None => Ok(None),
+    };
+    let built = match &admitted {
+        Ok(_slot) => {
+            let request_id = request_id.clone();
+            build_response(&parts, body, request_id, addrs, &mut seen, routes, config).await
+        }
+        Err(reason) => {
+            report_shed(config, addrs.peer, *reason);
+            Err(WebError::Overloaded(*reason))
+        }
+    };
+    let mut response = match built {
+        Ok(response) => response,
+        Err(error) => {
+            if let Some(metrics) = &config.metrics {
+                metrics.observe_error(&error);
+            }
+            let overloaded = matches!(error, WebError::Overloaded(_));
+            let mut context = ErrorContext::new(error);
+            context.method = Some(request_method(&parts));
+            context.uri = Some(request_target(&parts));
+            context.version = "HTTP/2.0".to_owned();
+            context.request_id.clone_from(&request_id);
+            if !config.error_pages.is_empty() {
+                context.headers = Some(request_headers(&parts));
+            }
+            let mut response = config.error_pages.render(&context);
+            if let (true, Some(limits)) = (overloaded, &config.connection_limits) {
+                limits.add_retry_after(&mut response);
+            }
+            for (name, value) in seen.refusal_headers.drain(..) {
+                response.headers.insert(name, value);
+            }
+            response
+        }
+    };
+    if let (Some(request_ids), Some(id)) = (&config.request_ids, &request_id) {
+        echo_request_id(&mut response, request_ids.header(), id);
+    }
+    // a stream error means the client reset the stream or left
+    let _ = send_response(&mut respond, &mut response).await;
+    let duration = started.elapsed();
+
+    let method = request_method(&parts);
+    if let Some(metrics) = &config.metrics {
+        metrics.observe_request(
+            seen.route.as_deref(),
+            Some(&method),
+            response.status.code,
+            duration,
+            response.body_bytes,
+        );
+    }
+    if let (Some(access_log), Some(time)) = (&config.access_log, time) {
+        let header = |name| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value: &http::HeaderValue| value.to_str().ok())
+        };
+        let uri = request_target(&parts);
+        access_log.record(&AccessRecord {
+            time,
+            peer: addrs.peer,
+            method: Some(&method),
+            uri: Some(&uri),
+            version: "HTTP/2.0",
+            status: response.status.code,
+            body_bytes: response.body_bytes,
+            duration,
+            referer: header(http::header::REFERER),
+            user_agent: header(http::header::USER_AGENT),
+            request_id: request_id.as_deref(),
+        });
+    }
+}
+
+/// What [`build_response`] learns that the error path needs.
+#[derive(Default)]
+struct SeenStream {
+    /// The matched route's pattern, kept only for metrics.
+    route: Option<String>,
+    /// Headers a [`Refusal`] adds to its error response.
+    refusal_headers: Vec<(String, String)>,
+}
+
+/// Maps the stream onto a [`Request`] and runs the responder, returning the
+/// failure to render on error like the HTTP/1.1 processor does. `seen` is
+/// given the matched route's pattern (for metrics) and any refusal headers.
+async fn build_response(
+    parts: &http::request::Parts,
+    body: RecvStream,
+    request_id: Option<String>,
+    addrs: ConnectionAddrs,
+    seen: &mut SeenStream,
+    routes: &RouteMap<'_>,
+    config: &ServerConfig,
+) -> Result<Response, WebError> {
+    let headers = request_headers(parts);
+    let client = resolve_client(config.trusted_proxies.as_ref(), addrs.peer, Some(&headers));
+    let mut request = Request {
+        total_size: 0,
+        method: request_method(parts),
+        uri: request_target(parts),
+        version: "HTTP/2.0".to_owned(),
+        headers: Some(headers),
+        message_body: None,
+        trailers: Default::default(),
+        id: request_id,
+        peer_addr: addrs.peer,
+        local_addr: addrs.local,
+        client,
+    };
+
+    let route = routes.find_best_route(&request)?;
+    if config.metrics.is_some() {
+        seen.route = Some(route.uri.clone());
+    }
+    // unreachable: route came from this map
+    let responder = routes
+        .responder_for(route)
+        .ok_or(WebError::Routing(RoutingError::NotFound))?;
+    let params = parse_route_params(&request, route);
+
+    // HTTP/2 frames the body itself; only the optional decoding applies
+    let mut body_reader: Pin<Box<dyn AsyncBufRead + Send + Sync>> = Box::pin(H2Body::new(body));
+    if let Some(limits) = &config.request_decoding {
+        let codings = decide_request_codings(request.headers.as_ref(), limits.max_codings)?;
+        if !codings.is_empty() {
+            body_reader = decode_body(body_reader, &codings, limits);
+            if let Some(headers) = request.headers.as_mut() {
+                remove_decoded_codings(headers);
+            }
+        }
+    }
+    request.set_message_body(Some(body_reader));
+
+    let validation = responder
+        .validate(&request, &params, None)
+        .await
+        .map_err(|status| WebError::Responder(status.code))?;
+    let validation = match Refusal::from_validation(validation) {
+        Ok(refusal) => {
+            seen.refusal_headers = refusal.headers;
+            return Err(WebError::Responder(refusal.status));
+        }
+        Err(validation) => validation,
+    };
+    let response = responder
+        .build_response(&mut request, &params, validation)
+        .await
+        .map_err(WebError::Responder)?;
+    if response.status.code == 101 {
+        // protocol upgrades do not exist in HTTP/2
+        return Err(WebError::Responder(501));
+    }
+    Ok(response)
+}
+
+/// The request method, uppercased as HTTP/1.1 request lines are matched.
+fn request_method(parts: &http::request::Parts) -> String {
+    parts.method.as_str().to_uppercase()
+}
+
+/// The request target (`:path`), or `/` when absent.
+fn request_target(parts: &http::request::Parts) -> String {
+    parts
+        .uri
+        .path_and_query()
+        .map(|target| target.as_str().to_owned())
+        .unwrap_or_else(|| "/".to_owned())
+}
+
+/// Lowercased request headers, duplicates comma-combined as in HTTP/1.1 (with
+/// `cookie` crumbs rejoined by `; `), plus `host` from `:authority`.
+fn request_headers(parts: &http::request::Parts) -> HashMap<String, String> {
+    let mut headers = HashMap::<String, String>::new();
+    for (name, value) in parts.headers.iter() {
+        let Ok(value) = value.to_str() else {
+            continue; // not representable as a header string
+        };
+        let separator = if name == http::header::COOKIE {
+            "; "
+        } else {
+            ", "
+        };
+        headers
+            .entry(name.as_str().to_owned())
+            .and_modify(|combined| {
+                combined.push_str(separator);
+                combined.push_str(value);
+            })
+            .or_insert_with(|| value.to_owned());
+    }
+    if let Some(authority) = parts.uri.authority() {
+        headers
+            .entry("host".to_owned())
+            .or_insert_with(|| authority.as_str().to_owned());
+    }
+    headers
+}
+
+/// Sends the response head, then streams its body within the peer's flow
+/// control window.
+async fn send_response(
+    respond: &mut SendResponse<Bytes>,
+    response: &mut Response,
+) -> Result<(), h2::Error> {
+    let mut head = http::Response::builder().status(response.status.code);
+    let appended = response
+        .appended_headers
+        .iter()
+        .map(|(name, value)| (name, value));
+    for (name, value) in response.headers.iter().chain(appended) {
+        let lowercase = name.to_ascii_lowercase();
+        if CONNECTION_HEADERS.contains(&lowercase.as_str()) {
+            continue;
+        }
+        head = head.header(lowercase, value.as_str());
+    }
+    let head = match head.body(()) {
+        Ok(head) => head,
+        // a header the responder produced is not valid in HTTP/2
+        Err(_error) => http::Response::builder()
+            .status(500)
+            .body(())
+            .expect("a bare status is a valid response"),
+    };
+
+    let Some(mut body) = response.take_body_reader() else {
+        respond.send_response(head, true)?;
+        return Ok(());
+    };
+    let mut stream = respond.send_response(head, false)?;
+    let mut buf = vec![0u8; WEBE_BUFFER_SIZE];
+    loop {
+        let read = match body.read(&mut buf).await {
+            Ok(read) => read,
+            Err(_error) => {
+                stream.send_reset(h2::Reason::INTERNAL_ERROR);
+                return Ok(());
+            }
+        };
+        if read == 0 {
+            return match response.trailers.as_ref().map(trailer_map) {
+                Some(trailers) if !trailers.is_empty() => stream.send_trailers(trailers),
+                _ => stream.send_data(Bytes::new(), true),
+            };
+        }
+        send_data(&mut stream, &buf[..read]).await?;
+        response.body_bytes += read as u64;
+    }
+}
+
+/// The sendable trailer fields as an HTTP/2 header map.
+fn trailer_map(trailers: &Trailers) -> http::HeaderMap {
+    let mut map = http::HeaderMap::new();
+    for (name, value) in trailer_fields(trailers) {
+        if let (Ok(name), Ok(value)) = (
+            http::HeaderName::from_bytes(name.as_bytes()),
+            http::HeaderValue::from_str(value),
+        ) {
+            map.append(name, value);
+        }
+    }
+    map
+}
+
+/// Sends `data` as DATA frames, waiting for flow-control capacity as needed.
+async fn send_data(stream: &mut SendStream<Bytes>, mut data: &[u8]) -> Result<(), h2::Error> {
+    while !data.is_empty() {
+        stream.reserve_capacity(data.len());
+        let capacity = match poll_fn(|cx| stream.poll_capacity(cx)).await {
+            Some(capacity) => capacity?,
+            None => return Err(h2::Error::from(h2::Reason::CANCEL)), // stream closed
+        };
+        let (now, later) = data.split_at(capacity.min(data.len()));
+        stream.send_data(Bytes::copy_from_slice(now), false)?;
+        data = later;
+    }
+    Ok(())
+}
+
+/// A request body read from an HTTP/2 stream, releasing flow-control capacity
+/// as the responder consumes it.
+struct H2Body {
+    stream: RecvStream,
+    chunk: Bytes,
+}
+
+impl H2Body {
+    fn new(stream: RecvStream) -> H2Body {
+        H2Body {
+            stream,
+            chunk: Bytes::new(),
+        }
+    }
+}
+
+impl AsyncRead for H2Body {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let available = match self.as_mut().poll_fill_buf(cx) {
+            Poll::Ready(Ok(available)) => available,
+            Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+            Poll::Pending => return Poll::Pending,
+        };
+        let amount = available.len().min(buf.remaining());
+        buf.put_slice(&available[..amount]);
+        self.consume(amount);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncBufRead for H2Body {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        while this.chunk.is_empty() {
+            match this.stream.poll_data(cx) {
+                Poll::Ready(Some(Ok(chunk))) => this.chunk = chunk,
+                Poll::Ready(Some(Err(error))) => {
+                    return Poll::Ready(Err(io::Error::other(error)));
+                }
+                Poll::Ready(None) => break, // end of stream
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(&this.chunk))
+    }
+
+    fn consume(self: Pin<&mut Self>, amount: usize) {
+        let this = self.get_mut();
+        let _ = this.chunk.split_to(amount);
+        // only consumed bytes reopen the window, so a slow responder slows the client
+        let _ = this.stream.flow_control().release_capacity(amount);
+    }
+}
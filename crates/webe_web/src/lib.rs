@@ -5,22 +5,51 @@
 //! through the `webe::web` facade. The crate is organized into focused modules:
 //!
 //! - [`server`] — bind / accept / start lifecycle ([`server::Server`]).
+//! - [`config`] — opt-in, server-wide options ([`config::ServerConfig`]).
 //! - [`route`] — [`route::Route`], [`route::RouteMap`], and deterministic matching.
 //! - [`processor`] — the per-connection request lifecycle.
 //! - [`request`] / [`response`] — request parsing and framed response writing.
 //! - [`body`] — request and response body-framing decisions.
+//! - [`streaming`] — response bodies pushed from application code, with trailers.
 //! - [`error`] — the consolidated, categorized [`error::WebError`].
+//! - [`error_pages`] — application-defined responses for failed requests.
+//! - [`access_log`] — per-request access logging through a `webe_log` sink.
+//! - [`metrics`] — request, connection, and error metrics for Prometheus.
+//! - [`request_id`] — accepted or generated request ids for log correlation.
+//! - [`forwarded`] — client address resolution behind trusted proxies.
+//! - [`rate_limit`] — per-client or per-route request rate limiting.
+//! - [`limits`] — connection and in-flight request limits with load shedding.
 //! - [`responders`] — the [`responders::Responder`] trait and built-in responders.
+//! - [`upgrade`] — handing a connection to another protocol after `101`.
+//! - [`websocket`] — the RFC 6455 WebSocket protocol over an upgraded connection.
+//! - [`testing`] — in-memory clients for testing responders and route maps.
+//! - `http2` — HTTP/2 connections (h2c and ALPN `h2`), behind the `http2` feature.
 #![deny(missing_docs)]
+pub mod access_log;
 pub mod body;
+pub mod config;
 pub mod constants;
 pub mod encoding;
 pub mod error;
+pub mod error_pages;
+pub mod forwarded;
+#[cfg(feature = "http2")]
+pub mod http2;
+pub mod limits;
+pub mod metrics;
+pub mod mime;
 pub mod processor;
+pub mod rate_limit;
 pub mod request;
+pub mod request_id;
 pub mod responders;
 pub mod response;
 pub mod route;
+pub(crate) mod sendfile;
 pub mod server;
 pub mod status;
+pub mod streaming;
+pub mod testing;
+pub mod upgrade;
 pub mod validation;
+pub mod websocket;
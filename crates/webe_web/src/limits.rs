@@ -0,0 +1,336 @@
+//! Connection limits and load shedding.
+//!
+//! Without limits, [`crate::server::Server::start`] accepts every connection
+//! and serves every request, so a spike can exhaust file descriptors and
+//! memory. [`ConnectionLimits`], attached through
+//! [`crate::config::ServerConfig::with_connection_limits`], bounds:
+//!
+//! - open connections ([`ConnectionLimits::with_max_connections`]): at the limit
+//!   the server either stops accepting until a connection closes
+//!   ([`OverloadAction::Pause`], leaving new clients in the listen backlog) or
+//!   accepts and immediately answers `503 Service Unavailable` and closes
+//!   ([`OverloadAction::Reject`]);
+//! - open connections per client IP
+//!   ([`ConnectionLimits::with_max_connections_per_ip`]), refused with `503`;
+//! - requests in flight across all connections
+//!   ([`ConnectionLimits::with_max_in_flight`]), answered with `503` without
+//!   being routed; the connection is closed afterward, once the client has
+//!   stopped sending (or a second has passed).
+//!
+//! Every shed connection or request is counted in the
+//! `webe_http_shed_total{reason}` metric and reported as a warning in the
+//! access log when those are enabled. Requests shed in flight fail with
+//! [`crate::error::WebError::Overloaded`], so error handlers can render them;
+//! [`ConnectionLimits::with_retry_after`] adds `Retry-After` to every `503`.
+//! The per-IP cap counts the connection's peer address, before any
+//! trusted-proxy resolution.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufWriter};
+use tokio::net::TcpStream;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::ServerConfig;
+use crate::error::WebError;
+use crate::error_pages::ErrorContext;
+use crate::metrics::lock;
+use crate::response::Response;
+
+/// How long a refused connection may take to receive its `503` and close.
+const REFUSAL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Open connections per peer IP address, shared by every clone of the limits.
+type PerIpCounts = Arc<Mutex<HashMap<IpAddr, usize>>>;
+
+/// What the server does with a new connection while at its connection limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverloadAction {
+    /// Stops accepting until a connection closes.
+    Pause,
+    /// Accepts, answers `503 Service Unavailable`, and closes.
+    Reject,
+}
+
+/// Which limit caused a connection or request to be shed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShedReason {
+    /// The server was at its connection limit.
+    Connections,
+    /// The client IP was at its connection limit.
+    ConnectionsPerIp,
+    /// The server was at its in-flight request limit.
+    InFlight,
+}
+
+impl ShedReason {
+    /// The `reason` label of the shed metric.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ShedReason::Connections => "connections",
+            ShedReason::ConnectionsPerIp => "connections_per_ip",
+            ShedReason::InFlight => "in_flight",
+        }
+    }
+}
+
+impl std::fmt::Display for ShedReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let limit = match self {
+            ShedReason::Connections => "connection",
+            ShedReason::ConnectionsPerIp => "per-IP connection",
+            ShedReason::InFlight => "in-flight request",
+        };
+        write!(f, "the {limit} limit was reached")
+    }
+}
+
+/// Bounds on concurrent connections and requests; clones share their counts.
+#[derive(Clone, Debug)]
+pub struct ConnectionLimits {
+    connections: Option<(Arc<Semaphore>, OverloadAction)>,
+    max_per_ip: Option<usize>,
+    max_in_flight: Option<usize>,
+    retry_after: Option<Duration>,
+    per_ip: PerIpCounts,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> ConnectionLimits {
+        ConnectionLimits::new()
+    }
+}
+
+impl ConnectionLimits {
+    /// No limits; add them with the `with_*` methods.
+    pub fn new() -> ConnectionLimits {
+        ConnectionLimits {
+            connections: None,
+            max_per_ip: None,
+            max_in_flight: None,
+            retry_after: None,
+            per_ip: Arc::new(Mutex::new(HashMap::new())),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Allows at most `max` open connections, handling more as `action` says.
+    pub fn with_max_connections(mut self, max: usize, action: OverloadAction) -> ConnectionLimits {
+        self.connections = Some((Arc::new(Semaphore::new(max)), action));
+        self
+    }
+
+    /// Allows at most `max` open connections from one peer IP address.
+    pub fn with_max_connections_per_ip(mut self, max: usize) -> ConnectionLimits {
+        self.max_per_ip = Some(max);
+        self
+    }
+
+    /// Allows at most `max` requests in flight at once, across HTTP/1.x
+    /// connections and HTTP/2 streams.
+    pub fn with_max_in_flight(mut self, max: usize) -> ConnectionLimits {
+        self.max_in_flight = Some(max);
+        self
+    }
+
+    /// Sends `Retry-After` (in whole seconds) on every `503` a limit causes.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> ConnectionLimits {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    /// In [`OverloadAction::Pause`] mode, the semaphore whose permit must be
+    /// held before the next connection is accepted.
+    pub(crate) fn pause_semaphore(&self) -> Option<&Arc<Semaphore>> {
+        match &self.connections {
+            Some((semaphore, OverloadAction::Pause)) => Some(semaphore),
+            _ => None,
+        }
+    }
+
+    /// Admits a connection from `peer`, given the permit reserved before
+    /// accepting it in pause mode, or says why it must be refused.
+    pub(crate) fn admit_connection(
+        &self,
+        peer: IpAddr,
+        reserved: Option<OwnedSemaphorePermit>,
+    ) -> Result<ConnectionSlot, ShedReason> {
+        let permit = match (reserved, &self.connections) {
+            (Some(permit), _) => Some(permit),
+            (None, Some((semaphore, _))) => Some(
+                Arc::clone(semaphore)
+                    .try_acquire_owned()
+                    .map_err(|_| ShedReason::Connections)?,
+            ),
+            (None, None) => None,
+        };
+        let peer = match self.max_per_ip {
+            Some(max) => {
+                let mut per_ip = lock(&self.per_ip);
+                let open = per_ip.get(&peer).copied().unwrap_or(0);
+                if open >= max {
+                    return Err(ShedReason::ConnectionsPerIp);
+                }
+                per_ip.insert(peer, open + 1);
+                Some((Arc::clone(&self.per_ip), peer))
+            }
+            None => None,
+        };
+        Ok(ConnectionSlot {
+            _permit: permit,
+            peer,
+        })
+    }
+
+    /// Admits a request, holding its place until the slot is dropped, or
+    /// refuses it at the in-flight limit.
+    pub(crate) fn start_request(&self) -> Result<Option<RequestSlot>, ShedReason> {
+        let Some(max) = self.max_in_flight else {
+            return Ok(None);
+        };
+        if self.in_flight.fetch_add(1, Ordering::AcqRel) >= max {
+            self.in_flight.fetch_sub(1, Ordering::AcqRel);
+            return Err(ShedReason::InFlight);
+        }
+        Ok(Some(RequestSlot {
+            in_flight: Arc::clone(&self.in_flight),
+        }))
+    }
+
+    /// Adds the configured `Retry-After` to a `503` caused by a limit.
+    pub(crate) fn add_retry_after(&self, response: &mut Response) {
+        if let Some(retry_after) = self.retry_after {
+            let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            response
+                .headers
+                .insert("Retry-After".to_owned(), seconds.to_string());
+        }
+    }
+}
+
+/// An admitted connection's share of the limits, released when dropped.
+pub(crate) struct ConnectionSlot {
+    _permit: Option<OwnedSemaphorePermit>,
+    peer: Option<(PerIpCounts, IpAddr)>,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        if let Some((per_ip, peer)) = &self.peer {
+            let mut per_ip = lock(per_ip);
+            if let Some(open) = per_ip.get_mut(peer) {
+                *open -= 1;
+                if *open == 0 {
+                    per_ip.remove(peer);
+                }
+            }
+        }
+    }
+}
+
+/// An admitted request's place in the in-flight count, released when dropped.
+pub(crate) struct RequestSlot {
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for RequestSlot {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Counts and logs a shed connection or request.
+pub(crate) fn report_shed(config: &ServerConfig, peer: Option<SocketAddr>, reason: ShedReason) {
+    if let Some(metrics) = &config.metrics {
+        metrics.observe_shed(reason);
+    }
+    if let Some(access_log) = &config.access_log {
+        access_log.shed(peer, reason);
+    }
+}
+
+/// Answers a connection refused at accept with `503` and closes it, reading
+/// (and discarding) whatever the client sends meanwhile so its request does
+/// not reset the connection before the response arrives.
+pub(crate) async fn refuse_connection(
+    mut stream: TcpStream,
+    config: Arc<ServerConfig>,
+    reason: ShedReason,
+) {
+    let mut response = config
+        .error_pages
+        .render(&ErrorContext::new(WebError::Overloaded(reason)));
+    if let Some(limits) = &config.connection_limits {
+        limits.add_retry_after(&mut response);
+    }
+    response.keep_alive = false;
+    let (mut reader, writer) = stream.split();
+    let mut writer = BufWriter::new(writer);
+    let _ = tokio::time::timeout(REFUSAL_TIMEOUT, async {
+        if response.respond(&mut writer).await.is_ok() {
+            drain_refused(&mut reader, &mut writer).await;
+        }
+    })
+    .await;
+}
+
+/// Closes the write side of a connection whose refusal has been written, then
+/// reads (and discards) whatever the client still sends, for at most
+/// [`REFUSAL_TIMEOUT`], so an unread request does not reset the connection
+/// before the client has read the refusal.
+pub(crate) async fn drain_refused<R, W>(reader: &mut R, writer: &mut W)
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let _ = tokio::time::timeout(REFUSAL_TIMEOUT, async {
+        let _ = writer.shutdown().await;
+        let _ = tokio::io::copy(reader, &mut tokio::io::sink()).await;
+    })
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([192, 0, 2, last])
+    }
+
+    #[test]
+    fn connection_slots_are_released_when_dropped() {
+        let limits = ConnectionLimits::new()
+            .with_max_connections(2, OverloadAction::Reject)
+            .with_max_connections_per_ip(1);
+        let first = limits.admit_connection(ip(1), None).unwrap();
+        assert_eq!(
+            limits.admit_connection(ip(1), None).err(),
+            Some(ShedReason::ConnectionsPerIp)
+        );
+        let second = limits.admit_connection(ip(2), None).unwrap();
+        assert_eq!(
+            limits.admit_connection(ip(3), None).err(),
+            Some(ShedReason::Connections)
+        );
+        drop(first);
+        assert!(limits.admit_connection(ip(1), None).is_ok());
+        drop(second);
+        assert!(lock(&limits.per_ip).is_empty());
+    }
+
+    #[test]
+    fn in_flight_requests_are_bounded() {
+        let limits = ConnectionLimits::new().with_max_in_flight(1);
+        let slot = limits.start_request().unwrap();
+        assert_eq!(limits.start_request().err(), Some(ShedReason::InFlight));
+        drop(slot);
+        assert!(limits.start_request().unwrap().is_some());
+        assert!(ConnectionLimits::new().start_request().unwrap().is_none());
+    }
+}
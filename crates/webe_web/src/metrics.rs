@@ -0,0 +1,447 @@
+//! Server instrumentation, exposed in the Prometheus text format.
+//!
+//! A [`Metrics`] registry attached with [`crate::config::ServerConfig::with_metrics`]
+//! is updated by the connection processor as requests are served, over HTTP/1.x
+//! and HTTP/2 alike, and rendered by
+//! [`crate::responders::metrics::MetricsResponder`] (or [`Metrics::render`]):
+//!
+//! - `webe_http_requests_total{route, method, status}`: requests answered.
+//! - `webe_http_request_duration_seconds{route, method}`: latency histogram, from
+//!   the first request byte to the last response byte.
+//! - `webe_http_requests_in_flight`: requests being served.
+//! - `webe_http_open_connections` and `webe_http_connections_total`.
+//! - `webe_http_received_bytes_total`: bytes read from client connections.
+//! - `webe_http_response_body_bytes_total`: response body bytes sent.
+//! - `webe_http_errors_total{category}`: failures by [`WebError`] category.
+//! - `webe_http_shed_total{reason}`: connections and requests shed by
+//!   [`crate::limits`], by [`ShedReason`].
+//!
+//! `route` is the matched route's pattern (such as `/users/<id>`), or
+//! `unmatched`, and unknown methods are counted as `OTHER`, so client input
+//! cannot grow the number of series.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::error::WebError;
+use crate::limits::ShedReason;
+
+/// The `route` label of requests that matched no route.
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Upper bounds, in seconds, of the latency histogram buckets (the Prometheus
+/// client defaults).
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Methods counted under their own name; any other is `OTHER`.
+const KNOWN_METHODS: [&str; 9] = [
+    "GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH",
+];
+
+/// A shared registry of server metrics; clones update the same values.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Registry>,
+}
+
+#[derive(Default)]
+struct Registry {
+    requests: Mutex<BTreeMap<(String, &'static str, u16), u64>>,
+    latency: Mutex<BTreeMap<(String, &'static str), Histogram>>,
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+    shed: Mutex<BTreeMap<&'static str, u64>>,
+    in_flight: AtomicI64,
+    open_connections: AtomicI64,
+    connections: AtomicU64,
+    received_bytes: AtomicU64,
+    response_body_bytes: AtomicU64,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Per-bucket (not cumulative) observation counts; the last is `+Inf`.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl std::fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Metrics")
+            .field("in_flight", &self.inner.in_flight.load(Ordering::Relaxed))
+            .field(
+                "open_connections",
+                &self.inner.open_connections.load(Ordering::Relaxed),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl Metrics {
+    /// Creates an empty registry.
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    /// Counts an answered request and its latency. `route` is the matched
+    /// pattern, if any.
+    pub(crate) fn observe_request(
+        &self,
+        route: Option<&str>,
+        method: Option<&str>,
+        status: u16,
+        duration: Duration,
+        response_body_bytes: u64,
+    ) {
+        let route = route.unwrap_or(UNMATCHED_ROUTE);
+        let method = method_label(method);
+        *lock(&self.inner.requests)
+            .entry((route.to_owned(), method, status))
+            .or_default() += 1;
+
+        let seconds = duration.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        let mut latency = lock(&self.inner.latency);
+        let histogram = latency.entry((route.to_owned(), method)).or_default();
+        histogram.buckets[bucket] += 1;
+        histogram.sum += seconds;
+        histogram.count += 1;
+        drop(latency);
+
+        self.inner
+            .response_body_bytes
+            .fetch_add(response_body_bytes, Ordering::Relaxed);
+    }
+
+    /// Counts a failure by its [`WebError`] category.
+    pub(crate) fn observe_error(&self, error: &WebError) {
+        *lock(&self.inner.errors).entry(category(error)).or_default() += 1;
+    }
+
+    /// Counts a connection or request shed by a limit.
+    pub(crate) fn observe_shed(&self, reason: ShedReason) {
+        *lock(&self.inner.shed).entry(reason.label()).or_default() += 1;
+    }
+
+    /// Marks a request as in flight until the guard is dropped.
+    pub(crate) fn request_started(&self) -> InFlight {
+        self.inner.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight {
+            gauge: Arc::clone(&self.inner),
+            connection: false,
+        }
+    }
+
+    /// Counts a connection, open until the guard is dropped.
+    pub(crate) fn connection_opened(&self) -> InFlight {
+        self.inner.open_connections.fetch_add(1, Ordering::Relaxed);
+        self.inner.connections.fetch_add(1, Ordering::Relaxed);
+        InFlight {
+            gauge: Arc::clone(&self.inner),
+            connection: true,
+        }
+    }
+
+    /// Renders every metric in the Prometheus text exposition format (0.0.4).
+    pub fn render(&self) -> String {
+        let registry = &self.inner;
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "webe_http_requests_total",
+            "counter",
+            "Requests answered, by route pattern, method, and status.",
+        );
+        for ((route, method, status), count) in lock(&registry.requests).iter() {
+            let _ = writeln!(
+                out,
+                "webe_http_requests_total{{route=\"{}\",method=\"{method}\",status=\"{status}\"}} {count}",
+                escape_label(route)
+            );
+        }
+
+        header(
+            &mut out,
+            "webe_http_request_duration_seconds",
+            "histogram",
+            "Time from the first request byte to the last response byte.",
+        );
+        for ((route, method), histogram) in lock(&registry.latency).iter() {
+            let labels = format!("route=\"{}\",method=\"{method}\"", escape_label(route));
+            let mut cumulative = 0;
+            for (index, count) in histogram.buckets.iter().enumerate() {
+                cumulative += count;
+                let bound = LATENCY_BUCKETS
+                    .get(index)
+                    .map_or_else(|| "+Inf".to_owned(), |bound| bound.to_string());
+                let _ = writeln!(
+                    out,
+                    "webe_http_request_duration_seconds_bucket{{{labels},le=\"{bound}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "webe_http_request_duration_seconds_sum{{{labels}}} {}",
+                histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "webe_http_request_duration_seconds_count{{{labels}}} {}",
+                histogram.count
+            );
+        }
+
+        let scalars = [
+            (
+                "webe_http_requests_in_flight",
+                "gauge",
+                "Requests being served.",
+                registry.in_flight.load(Ordering::Relaxed).to_string(),
+            ),
+            (
+                "webe_http_open_connections",
+                "gauge",
+                "Client connections currently open.",
+                registry
+                    .open_connections
+                    .load(Ordering::Relaxed)
+                    .to_string(),
+            ),
+            (
+                "webe_http_connections_total",
+                "counter",
+                "Client connections accepted.",
+                registry.connections.load(Ordering::Relaxed).to_string(),
+            ),
+            (
+                "webe_http_received_bytes_total",
+                "counter",
+                "Bytes read from client connections.",
+                registry.received_bytes.load(Ordering::Relaxed).to_string(),
+            ),
+            (
+                "webe_http_response_body_bytes_total",
+                "counter",
+                "Response body bytes sent.",
+                registry
+                    .response_body_bytes
+                    .load(Ordering::Relaxed)
+                    .to_string(),
+            ),
+        ];
+        for (name, kind, help, value) in scalars {
+            header(&mut out, name, kind, help);
+            let _ = writeln!(out, "{name} {value}");
+        }
+
+        header(
+            &mut out,
+            "webe_http_errors_total",
+            "counter",
+            "Failed requests and connections, by error category.",
+        );
+        for (category, count) in lock(&registry.errors).iter() {
+            let _ = writeln!(
+                out,
+                "webe_http_errors_total{{category=\"{category}\"}} {count}"
+            );
+        }
+
+        header(
+            &mut out,
+            "webe_http_shed_total",
+            "counter",
+            "Connections and requests shed by a limit, by reason.",
+        );
+        for (reason, count) in lock(&registry.shed).iter() {
+            let _ = writeln!(out, "webe_http_shed_total{{reason=\"{reason}\"}} {count}");
+        }
+        out
+    }
+}
+
+/// Decrements a gauge when dropped, however the request or connection ends.
+pub(crate) struct InFlight {
+    gauge: Arc<Registry>,
+    connection: bool,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let gauge = match self.connection {
+            true => &self.gauge.open_connections,
+            false => &self.gauge.in_flight,
+        };
+        gauge.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A connection (or its read half) whose reads are counted as received bytes.
+/// Without metrics it is a plain pass-through.
+pub(crate) struct Metered<T> {
+    inner: T,
+    metrics: Option<Metrics>,
+}
+
+impl<T> Metered<T> {
+    pub(crate) fn new(inner: T, metrics: Option<Metrics>) -> Metered<T> {
+        Metered { inner, metrics }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Metered<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let polled = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Some(metrics) = &this.metrics {
+            let read = (buf.filled().len() - before) as u64;
+            metrics
+                .inner
+                .received_bytes
+                .fetch_add(read, Ordering::Relaxed);
+        }
+        polled
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Metered<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// The `category` label for `error`.
+fn category(error: &WebError) -> &'static str {
+    match error {
+        WebError::Bind(_) => "bind",
+        WebError::Accept(_) => "accept",
+        WebError::Request(_) => "request",
+        WebError::Version(_) => "version",
+        WebError::Body(_) => "body",
+        WebError::Routing(_) => "routing",
+        WebError::Response(_) => "response",
+        WebError::Responder(_) => "responder",
+        WebError::Overloaded(_) => "overload",
+    }
+}
+
+fn method_label(method: Option<&str>) -> &'static str {
+    method
+        .and_then(|method| KNOWN_METHODS.iter().find(|known| **known == method))
+        .copied()
+        .unwrap_or("OTHER")
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Escapes a label value: backslash, double quote, and line feed.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Locks `mutex`, still usable after a panic elsewhere left it poisoned.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::route::RoutingError;
+
+    #[test]
+    fn requests_render_as_counters_and_cumulative_histograms() {
+        let metrics = Metrics::new();
+        let route = Some("/users/<id>");
+        metrics.observe_request(route, Some("GET"), 200, Duration::from_millis(3), 10);
+        metrics.observe_request(route, Some("GET"), 200, Duration::from_millis(30), 5);
+        metrics.observe_request(None, Some("BREW"), 404, Duration::from_secs(20), 0);
+        metrics.observe_error(&WebError::Routing(RoutingError::NotFound));
+
+        let text = metrics.render();
+        assert!(text.contains(
+            "webe_http_requests_total{route=\"/users/<id>\",method=\"GET\",status=\"200\"} 2\n"
+        ));
+        assert!(text.contains(
+            "webe_http_requests_total{route=\"unmatched\",method=\"OTHER\",status=\"404\"} 1\n"
+        ));
+        let labels = "route=\"/users/<id>\",method=\"GET\"";
+        for (bound, count) in [("0.005", 1), ("0.025", 1), ("0.05", 2), ("+Inf", 2)] {
+            let line = format!(
+                "webe_http_request_duration_seconds_bucket{{{labels},le=\"{bound}\"}} {count}\n"
+            );
+            assert!(text.contains(&line), "missing {line}");
+        }
+        assert!(text.contains(&format!(
+            "webe_http_request_duration_seconds_count{{{labels}}} 2\n"
+        )));
+        assert!(text.contains(
+            "webe_http_request_duration_seconds_bucket{route=\"unmatched\",method=\"OTHER\",le=\"10\"} 0\n"
+        ));
+        assert!(text.contains("webe_http_response_body_bytes_total 15\n"));
+        assert!(text.contains("webe_http_errors_total{category=\"routing\"} 1\n"));
+        assert!(text.contains("# TYPE webe_http_request_duration_seconds histogram\n"));
+    }
+
+    #[test]
+    fn guards_restore_the_gauges() {
+        let metrics = Metrics::new();
+        let connection = metrics.connection_opened();
+        let request = metrics.request_started();
+        assert!(
+            metrics
+                .render()
+                .contains("webe_http_requests_in_flight 1\n")
+        );
+        drop(request);
+        drop(connection);
+        let text = metrics.render();
+        assert!(text.contains("webe_http_requests_in_flight 0\n"));
+        assert!(text.contains("webe_http_open_connections 0\n"));
+        assert!(text.contains("webe_http_connections_total 1\n"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
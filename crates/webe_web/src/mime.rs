@@ -0,0 +1,159 @@
+//! MIME type resolution: case-insensitive extension lookup and magic-byte
+//! content sniffing for files without a usable extension.
+
+use crate::constants::DEFAULT_MIME_TYPES;
+
+/// Number of leading bytes [`sniff`] needs to recognize every supported format.
+pub const SNIFF_LENGTH: usize = 512;
+
+/// Looks up `extension` (without the dot) in the built-in
+/// [`DEFAULT_MIME_TYPES`] table, ignoring ASCII case.
+pub fn lookup_extension(extension: &str) -> Option<&'static str> {
+    DEFAULT_MIME_TYPES
+        .iter()
+        .find(|(known, _mime)| known.eq_ignore_ascii_case(extension))
+        .map(|(_known, mime)| *mime)
+}
+
+/// Guesses a MIME type from the leading bytes of a file.
+///
+/// Recognizes common binary signatures (images, fonts, audio/video containers,
+/// archives, PDF, WebAssembly), HTML/XML/SVG markup, and falls back to
+/// `text/plain` for UTF-8 data without binary control bytes. Returns `None` when
+/// nothing matches; callers then use `application/octet-stream`. Pass at least
+/// [`SNIFF_LENGTH`] bytes when available.
+pub fn sniff(bytes: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: [(&[u8], &str); 16] = [
+        (b"%PDF-", "application/pdf"),
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"\xFF\xD8\xFF", "image/jpeg"),
+        (b"\x00\x00\x01\x00", "image/x-icon"),
+        (b"\x00asm", "application/wasm"),
+        (b"wOFF", "font/woff"),
+        (b"wOF2", "font/woff2"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1F\x8B\x08", "application/gzip"),
+        (b"7z\xBC\xAF\x27\x1C", "application/x-7z-compressed"),
+        (b"\x1A\x45\xDF\xA3", "video/webm"),
+        (b"OggS\x00", "application/ogg"),
+        (b"ID3", "audio/mpeg"),
+        (b"fLaC", "audio/flac"),
+    ];
+    if let Some((_signature, mime)) = SIGNATURES
+        .iter()
+        .find(|(signature, _mime)| bytes.starts_with(signature))
+    {
+        return Some(mime);
+    }
+
+    // RIFF containers carry their format at offset 8
+    if bytes.len() >= 12 && bytes.starts_with(b"RIFF") {
+        match &bytes[8..12] {
+            b"WEBP" => return Some("image/webp"),
+            b"WAVE" => return Some("audio/wav"),
+            b"AVI " => return Some("video/x-msvideo"),
+            _ => {}
+        }
+    }
+    // ISO base media files start with a sized `ftyp` box
+    if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+        return match &bytes[8..12] {
+            b"avif" => Some("image/avif"),
+            b"heic" | b"heix" => Some("image/heic"),
+            b"qt  " => Some("video/quicktime"),
+            b"M4A " => Some("audio/mp4"),
+            _ => Some("video/mp4"),
+        };
+    }
+
+    sniff_text(bytes)
+}
+
+/// Classifies markup and plain text; `None` for anything that looks binary.
+fn sniff_text(bytes: &[u8]) -> Option<&'static str> {
+    // a sniffed prefix may cut a multi-byte character in half; only the final
+    // (incomplete) sequence is allowed to be invalid
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(error) if error.error_len().is_none() => {
+            std::str::from_utf8(&bytes[..error.valid_up_to()]).ok()?
+        }
+        Err(_error) => return None,
+    };
+    if text
+        .bytes()
+        .any(|byte| byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0C | 0x1B))
+    {
+        return None;
+    }
+
+    let trimmed = text.trim_start_matches('\u{FEFF}').trim_start();
+    let starts_with = |prefix: &str| {
+        trimmed
+            .get(..prefix.len())
+            .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+    };
+    if [
+        "<!doctype html",
+        "<html",
+        "<head",
+        "<body",
+        "<script",
+        "<!--",
+    ]
+    .iter()
+    .any(|prefix| starts_with(prefix))
+    {
+        return Some("text/html; charset=utf-8");
+    }
+    if starts_with("<svg") || (starts_with("<?xml") && trimmed.contains("<svg")) {
+        return Some("image/svg+xml");
+    }
+    if starts_with("<?xml") {
+        return Some("application/xml; charset=utf-8");
+    }
+    Some("text/plain; charset=utf-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extension_lookup_ignores_case() {
+        assert_eq!(lookup_extension("WASM"), Some("application/wasm"));
+        assert_eq!(lookup_extension("Woff2"), Some("font/woff2"));
+        assert_eq!(lookup_extension("png"), Some("image/png"));
+        assert_eq!(lookup_extension("nope"), None);
+    }
+
+    #[test]
+    fn binary_signatures_are_recognized() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some("image/png"));
+        assert_eq!(sniff(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(sniff(b"\0asm\x01\0\0\0"), Some("application/wasm"));
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff(b"\0\0\0\x18ftypmp42\0\0\0\0"), Some("video/mp4"));
+        assert_eq!(sniff(b"\x01\x02\x03\x04binary"), None);
+    }
+
+    #[test]
+    fn markup_and_text_are_recognized() {
+        assert_eq!(
+            sniff(b"  <!DOCTYPE html><html></html>"),
+            Some("text/html; charset=utf-8")
+        );
+        assert_eq!(
+            sniff(b"<?xml version=\"1.0\"?><svg></svg>"),
+            Some("image/svg+xml")
+        );
+        assert_eq!(sniff(b"just words\n"), Some("text/plain; charset=utf-8"));
+        // a multi-byte character cut off by the sniff window is still text
+        assert_eq!(
+            sniff(&"caf\u{e9}".as_bytes()[..4]),
+            Some("text/plain; charset=utf-8")
+        );
+    }
+}
@@ -3,133 +3,512 @@
 //! [`process_connection`] owns an accepted stream, splits it into a buffered
 //! reader/writer pair, and runs the keep-alive loop: parse a request, route it,
 //! frame its body, invoke the responder, and write a framed response. Every
-//! recognized failure is mapped to a documented static error response so the
-//! connection task ends cleanly without ever stopping the server.
+//! recognized failure is rendered as an error response (the documented static
+//! page, or an application's [`crate::error_pages`] handler) so the connection
+//! task ends cleanly without ever stopping the server.
 
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Instant;
 
-use tokio::io::{AsyncBufRead, AsyncReadExt, BufReader, BufWriter};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter,
+};
 use tokio::net::TcpStream;
-use tokio::net::tcp::ReadHalf;
 
-use crate::body::{RequestBody, decide_request_body};
-use crate::encoding::chunked::ChunkedDecoder;
+use crate::access_log::AccessRecord;
+use crate::body::{
+    FramedBody, RequestBody, decide_request_body, decide_request_codings, remove_decoded_codings,
+};
+use crate::config::ServerConfig;
+use crate::encoding::decompress::decode_body;
 use crate::error::WebError;
+use crate::error_pages::ErrorContext;
+use crate::forwarded::resolve_client;
+use crate::limits::{drain_refused, report_shed};
+use crate::metrics::{Metered, Metrics};
 use crate::request::Request;
-use crate::responders::static_message::StaticResponder;
-use crate::response::Response;
+use crate::response::{Response, ResponseWriter};
 use crate::route::{RoutingError, parse_route_params};
 use crate::server::RouteMap;
+use crate::upgrade::{OnUpgrade, Upgraded};
+use crate::validation::Refusal;
+
+/// How long a connection may take to show whether it opens with the HTTP/2
+/// preface.
+#[cfg(feature = "http2")]
+const PREFACE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
 
 /// Runs the request lifecycle for a single accepted connection.
 ///
-/// Parses requests in a keep-alive loop until the connection should close.
+/// With the `http2` feature, a connection opening with the HTTP/2 preface
+/// (prior-knowledge `h2c`) is served by [`crate::http2::serve_connection`].
+///
+/// Parses requests in a keep-alive loop until the connection should close, or
+/// until a `101 Switching Protocols` response hands the stream to its
+/// [`crate::upgrade::OnUpgrade`] callback.
+/// Requests carrying `Expect: 100-continue` are routed and validated before
+/// `100 Continue` is sent, so a rejected upload never transmits its body.
+/// Request body bytes a responder leaves unread are drained (up to
+/// [`ServerConfig::with_drain_limit`]) so pipelined requests parse correctly;
+/// a larger remainder closes the connection after the response.
 /// Recognized request, routing, body, and responder failures are turned into
-/// the documented static error responses (`400`/`404`/`405`/`505`/responder
-/// status) and the connection is closed afterward. Returns [`WebError`] only for
-/// an unrecoverable socket write failure.
+/// the documented error responses (`400`/`404`/`405`/`505`/responder status),
+/// rendered by any handlers registered with
+/// [`ServerConfig::with_error_handler`], and the connection is closed
+/// afterward. Uses the default [`ServerConfig`]; see
+/// [`process_connection_with_config`]. Returns [`WebError`] only for an
+/// unrecoverable socket write failure.
 pub async fn process_connection(
+    stream: TcpStream,
+    routes: Arc<RouteMap<'_>>,
+) -> Result<(), WebError> {
+    process_connection_with_config(stream, routes, Arc::new(ServerConfig::default())).await
+}
+
+/// [`process_connection`] with `config` supplying the server-wide options.
+pub async fn process_connection_with_config(
     mut stream: TcpStream,
     routes: Arc<RouteMap<'_>>,
+    config: Arc<ServerConfig>,
 ) -> Result<(), WebError> {
-    let (reader, writer) = stream.split();
-    let mut buf_reader = BufReader::new(reader);
+    let addrs = ConnectionAddrs {
+        peer: stream.peer_addr().ok(),
+        local: stream.local_addr().ok(),
+    };
+    let _open = config.metrics.as_ref().map(Metrics::connection_opened);
+    #[cfg(feature = "http2")]
+    if starts_with_h2_preface(&stream).await {
+        return crate::http2::serve(stream, routes, config, addrs).await;
+    }
+
+    let upgrade = {
+        let (reader, writer) = stream.split();
+        serve_http1(reader, writer, &routes, &config, addrs).await?
+    };
+
+    if let Some((on_upgrade, read_ahead)) = upgrade {
+        on_upgrade.run(Upgraded::new(stream, read_ahead)).await;
+    }
+    Ok(())
+}
+
+/// Runs the HTTP/1.x keep-alive loop over a split connection.
+///
+/// Returns once the connection should close (including when the client closes
+/// it between requests), or, after a `101` response, the [`OnUpgrade`] callback
+/// with the bytes already read past the upgrade request. `addrs` are the
+/// connection's addresses, when known, for the requests and the access log.
+pub(crate) async fn serve_http1<R, W>(
+    reader: R,
+    writer: W,
+    routes: &RouteMap<'_>,
+    config: &ServerConfig,
+    addrs: ConnectionAddrs,
+) -> Result<Option<(OnUpgrade, Vec<u8>)>, WebError>
+where
+    R: AsyncRead + Unpin + Send + Sync,
+    W: AsyncWrite + Unpin + Send,
+    BufWriter<W>: ResponseWriter,
+{
+    let mut buf_reader = BufReader::new(Metered::new(reader, config.metrics.clone()));
     let mut buf_writer = BufWriter::new(writer);
 
     let mut keep_alive = true;
+    let mut upgrade = None;
     while keep_alive {
-        let mut response = match build_response(&mut buf_reader, &routes).await {
+        // a client that hangs up between requests is simply done
+        match buf_reader.fill_buf().await {
+            Ok(buffered) if !buffered.is_empty() => {}
+            _ => break,
+        }
+
+        let started = Instant::now();
+        let time = config.access_log.as_ref().map(|_| chrono::Local::now());
+        let _in_flight = config.metrics.as_ref().map(Metrics::request_started);
+        let mut seen = SeenRequest::new();
+        let admitted = match &config.connection_limits {
+            Some(limits) => limits.start_request(),
+            None => Ok(None),
+        };
+        let built = match &admitted {
+            Ok(_slot) => {
+                build_response(
+                    &mut buf_reader,
+                    &mut buf_writer,
+                    &mut seen,
+                    routes,
+                    config,
+                    addrs,
+                )
+                .await
+            }
+            Err(reason) => {
+                report_shed(config, addrs.peer, *reason);
+                Err(WebError::Overloaded(*reason))
+            }
+        };
+        let mut response = match built {
             Ok((response, alive)) => {
                 keep_alive = alive;
                 response
             }
-            Err(code) => {
+            Err(error) => {
                 // Any recognized failure closes the connection after replying.
                 keep_alive = false;
-                StaticResponder::from_standard_code(code).quick_response()
+                if let Some(metrics) = &config.metrics {
+                    metrics.observe_error(&error);
+                }
+                seen.ensure_request_id(config);
+                let overloaded = matches!(error, WebError::Overloaded(_));
+                let mut response = config.error_pages.render(&seen.error_context(error));
+                if let (true, Some(limits)) = (overloaded, &config.connection_limits) {
+                    limits.add_retry_after(&mut response);
+                }
+                for (name, value) in seen.refusal_headers.drain(..) {
+                    response.headers.insert(name, value);
+                }
+                response
             }
         };
+        seen.ensure_request_id(config);
+        if let (Some(request_ids), Some(id)) = (&config.request_ids, &seen.request_id) {
+            echo_request_id(&mut response, request_ids.header(), id);
+        }
         response.keep_alive = keep_alive;
-        response.respond(&mut buf_writer).await?;
+        response.version.clone_from(&seen.version);
+        let on_upgrade = match response.status.code {
+            101 => response.upgrade.take(),
+            _ => None,
+        };
+        let written = response
+            .respond(&mut buf_writer)
+            .await
+            .map_err(WebError::from);
+        let duration = started.elapsed();
+        if let (Some(access_log), Some(time)) = (&config.access_log, time) {
+            access_log.record(&AccessRecord {
+                time,
+                peer: addrs.peer,
+                method: seen.method.as_deref(),
+                uri: seen.uri.as_deref(),
+                version: &seen.version,
+                status: response.status.code,
+                body_bytes: response.body_bytes,
+                duration,
+                referer: seen.referer.as_deref(),
+                user_agent: seen.user_agent.as_deref(),
+                request_id: seen.request_id.as_deref(),
+            });
+        }
+        if let Some(metrics) = &config.metrics {
+            metrics.observe_request(
+                seen.route.as_deref(),
+                seen.method.as_deref(),
+                response.status.code,
+                duration,
+                response.body_bytes,
+            );
+            if let Err(error) = &written {
+                metrics.observe_error(error);
+            }
+        }
+        written?;
+        // writing may have had to give up keep-alive (a close-delimited body)
+        keep_alive = response.keep_alive;
+
+        // the shed request was never read; let the client take the 503 first
+        if admitted.is_err() {
+            drain_refused(&mut buf_reader, &mut buf_writer).await;
+            break;
+        }
+
+        // after a protocol switch the connection no longer speaks HTTP
+        if let Some(on_upgrade) = on_upgrade {
+            upgrade = Some((on_upgrade, buf_reader.buffer().to_vec()));
+            break;
+        }
     }
+    Ok(upgrade)
+}
 
-    Ok(())
+/// Peeks (without consuming) far enough to tell an HTTP/2 preface from an
+/// HTTP/1.x request line: no HTTP/1.x method starts with `PRI`. A client that
+/// stalls partway through those bytes for [`PREFACE_TIMEOUT`] is served as
+/// HTTP/1.x.
+#[cfg(feature = "http2")]
+async fn starts_with_h2_preface(stream: &TcpStream) -> bool {
+    let peek = async {
+        let mut buf = [0u8; 3];
+        loop {
+            let peeked = match stream.peek(&mut buf).await {
+                Ok(peeked) => peeked,
+                Err(_error) => return false,
+            };
+            if peeked == 0 || buf[..peeked] != crate::http2::PREFACE[..peeked] {
+                return false;
+            }
+            if peeked == buf.len() {
+                return true;
+            }
+            // a split first packet; wait briefly rather than spin on peek
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+    };
+    tokio::time::timeout(PREFACE_TIMEOUT, peek)
+        .await
+        .unwrap_or(false)
+}
+
+/// The two ends of a connection, as far as the transport reports them.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ConnectionAddrs {
+    /// The client, or the proxy connecting on its behalf.
+    pub(crate) peer: Option<SocketAddr>,
+    /// The server's end of the connection.
+    pub(crate) local: Option<SocketAddr>,
+}
+
+/// What is known of the request being served, kept outside [`build_response`]
+/// so a failure can be rendered with it and the request logged.
+struct SeenRequest {
+    method: Option<String>,
+    uri: Option<String>,
+    version: String,
+    /// Kept only when error handlers are registered.
+    headers: Option<HashMap<String, String>>,
+    /// Kept only when requests are logged.
+    user_agent: Option<String>,
+    referer: Option<String>,
+    /// The matched route's pattern, kept only for metrics.
+    route: Option<String>,
+    /// Assigned only when request ids are enabled.
+    request_id: Option<String>,
+    /// Headers a [`Refusal`] adds to its error response.
+    refusal_headers: Vec<(String, String)>,
+}
+
+impl SeenRequest {
+    fn new() -> SeenRequest {
+        SeenRequest {
+            method: None,
+            uri: None,
+            version: String::from("HTTP/1.1"),
+            headers: None,
+            user_agent: None,
+            referer: None,
+            route: None,
+            request_id: None,
+            refusal_headers: Vec::new(),
+        }
+    }
+
+    /// Whether the headers are wanted even for a request that failed routing.
+    fn wants_headers(config: &ServerConfig) -> bool {
+        !config.error_pages.is_empty()
+            || config.access_log.is_some()
+            || config.request_ids.is_some()
+    }
+
+    /// Keeps what the configuration has a use for from the parsed headers.
+    fn record_headers(&mut self, headers: &Option<HashMap<String, String>>, config: &ServerConfig) {
+        if config.access_log.is_some()
+            && let Some(headers) = headers
+        {
+            self.user_agent = headers.get("user-agent").cloned();
+            self.referer = headers.get("referer").cloned();
+        }
+        if !config.error_pages.is_empty() {
+            self.headers.clone_from(headers);
+        }
+        if let Some(request_ids) = &config.request_ids {
+            self.request_id = request_ids.assign(headers.as_ref());
+        }
+    }
+
+    /// Generates a request id for a request that failed before its headers
+    /// could supply or be given one.
+    fn ensure_request_id(&mut self, config: &ServerConfig) {
+        if let Some(request_ids) = &config.request_ids
+            && self.request_id.is_none()
+        {
+            self.request_id = request_ids.generate();
+        }
+    }
+
+    fn error_context(&mut self, error: WebError) -> ErrorContext {
+        let mut context = ErrorContext::new(error);
+        context.method.clone_from(&self.method);
+        context.uri.clone_from(&self.uri);
+        context.version.clone_from(&self.version);
+        context.headers = self.headers.take();
+        context.request_id.clone_from(&self.request_id);
+        context
+    }
 }
 
 /// Parses, routes, frames, and dispatches a single request.
 ///
 /// On success returns the responder's [`Response`] and whether the connection
-/// may be kept alive. On any recognized failure returns the documented client
-/// status code (`Err(code)`), which the caller renders as a static response.
-async fn build_response(
-    buf_reader: &mut BufReader<ReadHalf<'_>>,
+/// may be kept alive. On any recognized failure returns the [`WebError`], which
+/// the caller renders as an error response. `buf_writer` is only used for a
+/// `100 Continue` interim response. `seen` records the request line as soon as
+/// it parses, and whatever of the headers the error handlers and access log use.
+/// `addrs` are recorded on the request and resolve its client.
+async fn build_response<R, W>(
+    buf_reader: &mut BufReader<R>,
+    buf_writer: &mut BufWriter<W>,
+    seen: &mut SeenRequest,
     routes: &RouteMap<'_>,
-) -> Result<(Response, bool), u16> {
+    config: &ServerConfig,
+    addrs: ConnectionAddrs,
+) -> Result<(Response, bool), WebError>
+where
+    R: AsyncRead + Unpin + Send + Sync,
+    W: AsyncWrite + Unpin,
+{
     // --- request line + version ---
-    let mut request = match Request::new(buf_reader).await {
-        Ok(request) => request,
-        Err(error) => return Err(status_for(error)),
-    };
+    let mut request = Request::new(buf_reader).await?;
+    request.peer_addr = addrs.peer;
+    request.local_addr = addrs.local;
+    seen.version.clone_from(&request.version);
+    seen.method = Some(request.method.clone());
+    seen.uri = Some(request.uri.clone());
 
     // --- routing (404 vs 405) ---
     let route = match routes.find_best_route(&request) {
         Ok(route) => route,
-        Err(RoutingError::NotFound) => return Err(404),
-        Err(RoutingError::MethodNotAllowed) => return Err(405),
+        Err(error) => {
+            // the headers are still worth reading for the error handlers and log
+            if SeenRequest::wants_headers(config) && request.parse_headers(buf_reader).await.is_ok()
+            {
+                seen.record_headers(&request.headers, config);
+            }
+            return Err(WebError::Routing(error));
+        }
     };
+    if config.metrics.is_some() {
+        seen.route = Some(route.uri.clone());
+    }
     let responder = match routes.responder_for(route) {
         Some(responder) => responder,
-        None => return Err(500), // unreachable: route came from this map
+        // unreachable: route came from this map
+        None => return Err(WebError::Routing(RoutingError::NotFound)),
     };
     let params = parse_route_params(&request, route);
 
     // --- headers ---
-    if let Err(error) = request.parse_headers(buf_reader).await {
-        return Err(status_for(error));
-    }
+    request.parse_headers(buf_reader).await?;
+    seen.record_headers(&request.headers, config);
+    request.id.clone_from(&seen.request_id);
+    request.client = resolve_client(
+        config.trusted_proxies.as_ref(),
+        addrs.peer,
+        request.headers.as_ref(),
+    );
 
     // --- request body framing ---
-    let framing = match decide_request_body(request.headers.as_ref()) {
-        Ok(framing) => framing,
-        Err(_body_error) => return Err(400),
+    let framing = decide_request_body(request.headers.as_ref())?;
+    let codings = match &config.request_decoding {
+        Some(limits) => decide_request_codings(request.headers.as_ref(), limits.max_codings)?,
+        None => Vec::new(),
     };
-    let mut body_reader: Pin<Box<dyn AsyncBufRead + Send + Sync>> = Box::pin(&mut *buf_reader);
-    match framing {
-        RequestBody::None => {}
-        RequestBody::Length(length) => {
-            body_reader = Box::pin(body_reader.take(length));
-        }
-        RequestBody::Chunked => {
-            body_reader = Box::pin(BufReader::new(ChunkedDecoder::new(body_reader)));
-        }
-    }
+    // the framed body stays here so whatever the responder leaves unread can be
+    // drained before the next request is parsed
+    let mut framed = FramedBody::new(&framing, &mut *buf_reader);
+    request.trailers = framed.trailers();
+    let mut body_reader: Pin<Box<dyn AsyncBufRead + Send + Sync>> = Box::pin(&mut framed);
 
-    // --- keep-alive intent from the request ---
-    let mut keep_alive = true;
-    if let Some(headers) = &request.headers
-        && let Some(connection) = headers.get("connection")
-        && connection.to_lowercase().contains("close")
+    // --- optional transparent decompression, layered over the framed body ---
+    if let Some(limits) = &config.request_decoding
+        && framing != RequestBody::None
+        && !codings.is_empty()
     {
-        keep_alive = false;
+        body_reader = decode_body(body_reader, &codings, limits);
+        // these described the encoded bytes, not what the responder will read
+        if let Some(headers) = request.headers.as_mut() {
+            remove_decoded_codings(headers);
+        }
     }
 
-    request.set_message_body(Some(body_reader));
+    // --- keep-alive intent from the request (HTTP/1.0 must opt in) ---
+    let connection = request
+        .headers
+        .as_ref()
+        .and_then(|headers| headers.get("connection"))
+        .map(|connection| connection.to_lowercase());
+    let keep_alive = match (&connection, request.is_http_1_0()) {
+        (Some(connection), true) => connection.contains("keep-alive"),
+        (None, true) => false,
+        (Some(connection), false) => !connection.contains("close"),
+        (None, false) => true,
+    };
+
+    // --- expectations (only `100-continue` is known; HTTP/1.0 ignores them) ---
+    let expect = match request.is_http_1_0() {
+        true => None,
+        false => request.headers.as_ref().and_then(|h| h.get("expect")),
+    };
+    let expect_continue = match expect {
+        Some(expect) if expect.trim().eq_ignore_ascii_case("100-continue") => true,
+        Some(_unknown) => return Err(WebError::Responder(417)),
+        None => false,
+    };
 
     // --- validate + build ---
-    match responder.validate(&request, &params, None).await {
-        Ok(validation) => match responder
+    // `request` borrows `framed` from here on; moving it into this block drops
+    // it (on every path) before `framed` is drained or dropped
+    let response = {
+        let mut request = request;
+        request.set_message_body(Some(body_reader));
+        let validation = match responder.validate(&request, &params, None).await {
+            Ok(validation) => validation,
+            // the final status is sent instead of `100 Continue`; the body is never read
+            Err(status) => return Err(WebError::Responder(status.code)),
+        };
+        let validation = match Refusal::from_validation(validation) {
+            Ok(refusal) => {
+                seen.refusal_headers = refusal.headers;
+                return Err(WebError::Responder(refusal.status));
+            }
+            Err(validation) => validation,
+        };
+        if expect_continue && !matches!(framing, RequestBody::None | RequestBody::Length(0)) {
+            // a broken connection surfaces when the final response is written
+            let _ = send_continue(buf_writer).await;
+        }
+        responder
             .build_response(&mut request, &params, validation)
             .await
-        {
-            Ok(response) => Ok((response, keep_alive)),
-            Err(code) => Err(code),
-        },
-        Err(status) => Err(status.code),
+            .map_err(WebError::Responder)?
+    };
+
+    // --- unread body: drain it, or close rather than misparse it as a request ---
+    let drained = framed.drain(config.drain_limit()).await;
+    Ok((response, keep_alive && drained))
+}
+
+/// Sets the request id header on `response` unless the responder already set
+/// one (a proxied response may carry the upstream's).
+pub(crate) fn echo_request_id(response: &mut Response, header: &str, id: &str) {
+    if !response
+        .headers
+        .keys()
+        .any(|name| name.eq_ignore_ascii_case(header))
+    {
+        response.headers.insert(header.to_owned(), id.to_owned());
     }
 }
 
-/// Maps a parsing failure to the documented client status code.
-fn status_for(error: crate::request::RequestError) -> u16 {
-    WebError::from(error).client_status().unwrap_or(400)
+/// Writes the `100 Continue` interim response that releases an expecting
+/// client's body.
+async fn send_continue<W: AsyncWrite + Unpin>(
+    buf_writer: &mut BufWriter<W>,
+) -> std::io::Result<()> {
+    buf_writer
+        .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+        .await?;
+    buf_writer.flush().await
 }
@@ -0,0 +1,333 @@
+//! In-memory request rate limiting.
+//!
+//! A [`RateLimiter`] tracks a budget per key (a client IP, a route, or anything
+//! a [`crate::responders::rate_limit::RateLimitKey`] extracts) and decides
+//! whether each request fits. Wrap a responder in
+//! [`crate::responders::rate_limit::RateLimitResponder`] to answer requests over
+//! the limit with `429 Too Many Requests`, `Retry-After`, and the `RateLimit-*`
+//! headers, without running the wrapped responder.
+//!
+//! Two algorithms are available:
+//!
+//! - [`RateLimiter::token_bucket`] allows bursts of up to `limit` requests and
+//!   refills at `limit` per `window`, spread evenly.
+//! - [`RateLimiter::sliding_window`] allows `limit` requests in any `window`,
+//!   estimated from the current and previous fixed windows' counts.
+//!
+//! A limiter is cheap to clone and clones share their store, so one limiter can
+//! guard several routes (such as every login and password-reset endpoint). The
+//! store lives in this process only: each server instance limits on its own.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The store size below which idle keys are never pruned.
+const MIN_PRUNE_AT: usize = 1024;
+
+/// How a [`RateLimiter`] spends and restores its budget.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimitAlgorithm {
+    /// Bursts up to the limit, refilled continuously over the window.
+    TokenBucket,
+    /// At most the limit in any window-long span, approximately.
+    SlidingWindow,
+}
+
+/// The outcome of [`RateLimiter::check`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    /// Whether the request is within the limit (and was counted).
+    pub allowed: bool,
+    /// The requests allowed per window.
+    pub limit: u32,
+    /// The requests still allowed right now.
+    pub remaining: u32,
+    /// How long until the full budget is available again.
+    pub reset: Duration,
+    /// For a refused request, how long until a retry can succeed.
+    pub retry_after: Option<Duration>,
+}
+
+/// One key's budget.
+#[derive(Debug)]
+enum State {
+    /// Fractional tokens left as of `updated`.
+    Bucket { tokens: f64, updated: Instant },
+    /// Requests in the fixed window starting at `start`, and the one before.
+    Window {
+        start: Instant,
+        current: u32,
+        previous: u32,
+    },
+}
+
+#[derive(Debug)]
+struct Store {
+    states: HashMap<String, State>,
+    prune_at: usize,
+}
+
+/// Decides whether requests fit a per-key budget of `limit` per `window`.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    algorithm: RateLimitAlgorithm,
+    limit: u32,
+    window: Duration,
+    store: Arc<Mutex<Store>>,
+}
+
+impl RateLimiter {
+    /// A token bucket holding `limit` tokens, refilled at `limit` per `window`.
+    ///
+    /// Panics if `limit` is zero or `window` is empty.
+    pub fn token_bucket(limit: u32, window: Duration) -> RateLimiter {
+        RateLimiter::new(RateLimitAlgorithm::TokenBucket, limit, window)
+    }
+
+    /// A sliding window allowing `limit` requests per `window`.
+    ///
+    /// Panics if `limit` is zero or `window` is empty.
+    pub fn sliding_window(limit: u32, window: Duration) -> RateLimiter {
+        RateLimiter::new(RateLimitAlgorithm::SlidingWindow, limit, window)
+    }
+
+    fn new(algorithm: RateLimitAlgorithm, limit: u32, window: Duration) -> RateLimiter {
+        assert!(limit > 0, "rate_limit: the limit must be positive");
+        assert!(
+            !window.is_zero(),
+            "rate_limit: the window must not be empty"
+        );
+        RateLimiter {
+            algorithm,
+            limit,
+            window,
+            store: Arc::new(Mutex::new(Store {
+                states: HashMap::new(),
+                prune_at: MIN_PRUNE_AT,
+            })),
+        }
+    }
+
+    /// The algorithm in use.
+    pub fn algorithm(&self) -> RateLimitAlgorithm {
+        self.algorithm
+    }
+
+    /// The requests allowed per window.
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// The window the limit applies to.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Counts a request for `key` if it is within the limit.
+    pub fn check(&self, key: &str) -> RateLimitDecision {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> RateLimitDecision {
+        let mut store = self
+            .store
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if store.states.len() >= store.prune_at {
+            // forget keys whose budget is whole again; they behave as new keys
+            store.states.retain(|_key, state| !self.is_idle(state, now));
+            store.prune_at = (store.states.len() * 2).max(MIN_PRUNE_AT);
+        }
+        let state = store
+            .states
+            .entry(key.to_owned())
+            .or_insert_with(|| self.fresh(now));
+        match state {
+            State::Bucket { tokens, updated } => self.take_token(tokens, updated, now),
+            State::Window {
+                start,
+                current,
+                previous,
+            } => self.count_in_window(start, current, previous, now),
+        }
+    }
+
+    fn fresh(&self, now: Instant) -> State {
+        match self.algorithm {
+            RateLimitAlgorithm::TokenBucket => State::Bucket {
+                tokens: f64::from(self.limit),
+                updated: now,
+            },
+            RateLimitAlgorithm::SlidingWindow => State::Window {
+                start: now,
+                current: 0,
+                previous: 0,
+            },
+        }
+    }
+
+    /// Whether `state` has recovered its full budget by `now`.
+    fn is_idle(&self, state: &State, now: Instant) -> bool {
+        match state {
+            State::Bucket { tokens, updated } => {
+                tokens + self.refill(now.duration_since(*updated)) >= f64::from(self.limit)
+            }
+            State::Window { start, .. } => now.duration_since(*start) >= self.window * 2,
+        }
+    }
+
+    /// Tokens restored over `elapsed`.
+    fn refill(&self, elapsed: Duration) -> f64 {
+        elapsed.as_secs_f64() / self.window.as_secs_f64() * f64::from(self.limit)
+    }
+
+    /// How long refilling `tokens` takes.
+    fn refill_time(&self, tokens: f64) -> Duration {
+        Duration::from_secs_f64(
+            (tokens.max(0.0) / f64::from(self.limit)) * self.window.as_secs_f64(),
+        )
+    }
+
+    fn take_token(
+        &self,
+        tokens: &mut f64,
+        updated: &mut Instant,
+        now: Instant,
+    ) -> RateLimitDecision {
+        let limit = f64::from(self.limit);
+        *tokens = (*tokens + self.refill(now.duration_since(*updated))).min(limit);
+        *updated = now;
+        let allowed = *tokens >= 1.0;
+        if allowed {
+            *tokens -= 1.0;
+        }
+        RateLimitDecision {
+            allowed,
+            limit: self.limit,
+            remaining: tokens.floor() as u32,
+            reset: self.refill_time(limit - *tokens),
+            retry_after: (!allowed).then(|| self.refill_time(1.0 - *tokens)),
+        }
+    }
+
+    fn count_in_window(
+        &self,
+        start: &mut Instant,
+        current: &mut u32,
+        previous: &mut u32,
+        now: Instant,
+    ) -> RateLimitDecision {
+        // roll the fixed windows forward to the one containing `now`
+        let windows_passed = now.duration_since(*start).as_nanos() / self.window.as_nanos();
+        if windows_passed >= 1 {
+            *previous = if windows_passed == 1 { *current } else { 0 };
+            *current = 0;
+            *start += self.window * windows_passed.min(u128::from(u32::MAX)) as u32;
+        }
+        let into_window = now.duration_since(*start);
+        let previous_weight = 1.0 - into_window.as_secs_f64() / self.window.as_secs_f64();
+        let estimate = |current: u32| f64::from(*previous) * previous_weight + f64::from(current);
+
+        let allowed = estimate(*current + 1) <= f64::from(self.limit);
+        if allowed {
+            *current += 1;
+        }
+        let used = estimate(*current);
+        let remaining = (f64::from(self.limit) - used).max(0.0).floor() as u32;
+        let window_end = self.window - into_window;
+        let retry_after = (!allowed).then(|| {
+            match *previous {
+                // the previous window's share must shrink enough to admit one
+                previous if previous > 0 && *current < self.limit => {
+                    let excess = estimate(*current + 1) - f64::from(self.limit);
+                    let per_request = self.window.as_secs_f64() / f64::from(previous);
+                    Duration::from_secs_f64(excess * per_request).min(window_end)
+                }
+                _ => window_end,
+            }
+        });
+        RateLimitDecision {
+            allowed,
+            limit: self.limit,
+            remaining,
+            // both windows' counts are forgotten by the end of the next window
+            reset: match *current {
+                0 => window_end,
+                _ => window_end + self.window,
+            },
+            retry_after,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn token_buckets_allow_a_burst_then_refill_steadily() {
+        let limiter = RateLimiter::token_bucket(3, 3 * SECOND);
+        let start = Instant::now();
+        for remaining in [2, 1, 0] {
+            let decision = limiter.check_at("a", start);
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, remaining);
+        }
+        let refused = limiter.check_at("a", start);
+        assert!(!refused.allowed);
+        assert_eq!(refused.retry_after, Some(SECOND));
+        assert_eq!(refused.reset, 3 * SECOND);
+        // other keys have their own budget
+        assert!(limiter.check_at("b", start).allowed);
+
+        assert!(limiter.check_at("a", start + SECOND).allowed);
+        assert!(!limiter.check_at("a", start + SECOND).allowed);
+        let refilled = limiter.check_at("a", start + 10 * SECOND);
+        assert!(refilled.allowed);
+        assert_eq!(refilled.remaining, 2);
+    }
+
+    #[test]
+    fn sliding_windows_weigh_the_previous_window() {
+        let limiter = RateLimiter::sliding_window(4, 10 * SECOND);
+        let start = Instant::now();
+        for _ in 0..4 {
+            assert!(limiter.check_at("a", start).allowed);
+        }
+        let refused = limiter.check_at("a", start + 5 * SECOND);
+        assert!(!refused.allowed);
+        assert_eq!(refused.retry_after, Some(5 * SECOND));
+
+        // halfway into the next window the previous four count as two
+        let later = start + 15 * SECOND;
+        assert!(limiter.check_at("a", later).allowed);
+        let decision = limiter.check_at("a", later);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        let refused = limiter.check_at("a", later);
+        assert!(!refused.allowed);
+        // one previous request leaves the estimate every 2.5 seconds
+        assert_eq!(refused.retry_after, Some(Duration::from_millis(2500)));
+
+        // two idle windows forget everything
+        let fresh = limiter.check_at("a", start + 31 * SECOND);
+        assert!(fresh.allowed);
+        assert_eq!(fresh.remaining, 3);
+    }
+
+    #[test]
+    fn idle_keys_are_pruned_from_a_large_store() {
+        let limiter = RateLimiter::token_bucket(1, SECOND);
+        let start = Instant::now();
+        for key in 0..MIN_PRUNE_AT {
+            limiter.check_at(&key.to_string(), start);
+        }
+        limiter.check_at("late", start + 2 * SECOND);
+        let store = limiter.store.lock().unwrap();
+        assert_eq!(store.states.len(), 1);
+        assert_eq!(store.prune_at, MIN_PRUNE_AT);
+    }
+}
@@ -1,14 +1,16 @@
 //! Incoming requests: request-line and header parsing, plus the body reader.
 
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::pin::Pin;
 
-use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader};
-use tokio::net::tcp::ReadHalf;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
 
 use crate::constants::{MAX_HEADERS_SIZE, MAX_REQUEST_LINE_SIZE};
+use crate::encoding::chunked::Trailers;
+use crate::forwarded::ClientInfo;
 
-/// A parsed client request within the supported HTTP/1.1 scope.
+/// A parsed client request within the supported HTTP/1.1 (and HTTP/1.0) scope.
 ///
 /// [`Request::new`] parses the request line (and validates the version);
 /// [`Request::parse_headers`] reads the header block; the connection processor
@@ -20,12 +22,27 @@ pub struct Request<'r> {
     pub method: String,
     /// Request target path.
     pub uri: String,
-    /// HTTP version token; always `HTTP/1.1` for an accepted request.
+    /// HTTP version token; `HTTP/1.1` or `HTTP/1.0` for an accepted request.
     pub version: String,
     /// Lowercased header names mapped to comma-combined values.
     pub headers: Option<HashMap<String, String>>,
     /// The framed body reader, assigned by the connection processor.
     pub message_body: Option<Pin<Box<dyn AsyncBufRead + 'r + Send + Sync>>>,
+    /// Trailer fields of a chunked body, available once the body has been read
+    /// to its end.
+    pub trailers: Trailers,
+    /// The request id, when [`crate::config::ServerConfig::with_request_ids`] is
+    /// set; include it in log lines to correlate them with the access log.
+    pub id: Option<String>,
+    /// The address of the connection's peer: the client, or a proxy in front
+    /// of it. `None` when the transport does not report one.
+    pub peer_addr: Option<SocketAddr>,
+    /// The server address the connection was accepted on.
+    pub local_addr: Option<SocketAddr>,
+    /// The client's address, scheme, and host, resolved through any
+    /// [`crate::config::ServerConfig::with_trusted_proxies`] once the headers
+    /// are parsed.
+    pub client: ClientInfo,
 }
 
 /// Why a request could not be parsed or accepted.
@@ -45,7 +62,8 @@ pub enum RequestError {
     MaxRequestSizeError,
     /// An unsupported transfer/content coding was requested.
     EncodingNotSupportedError,
-    /// The request used an HTTP version other than `HTTP/1.1`. Holds the token.
+    /// The request used an HTTP version other than `HTTP/1.1` or `HTTP/1.0`.
+    /// Holds the token.
     UnsupportedVersion(String),
 }
 
@@ -56,15 +74,31 @@ impl From<std::io::Error> for RequestError {
 }
 
 impl<'r> Request<'r> {
+    /// Whether this is an `HTTP/1.0` request, which defaults to closing the
+    /// connection and cannot receive `chunked` bodies or interim responses.
+    pub fn is_http_1_0(&self) -> bool {
+        self.version == "HTTP/1.0"
+    }
+
+    /// The value of the header `name`, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        // names are stored lowercased; most callers already ask that way
+        let headers = self.headers.as_ref()?;
+        headers
+            .get(name)
+            .or_else(|| headers.get(&name.to_ascii_lowercase()))
+            .map(String::as_str)
+    }
+
     /// Parses the request line from `buf_reader` and validates the HTTP version.
     ///
     /// Returns [`RequestError::MalformedRequestError`] for a request line that is
     /// empty or does not have exactly three parts,
     /// [`RequestError::MaxURISizeError`] when the line exceeds
     /// [`MAX_REQUEST_LINE_SIZE`], and [`RequestError::UnsupportedVersion`] when
-    /// the version is not `HTTP/1.1`.
-    pub async fn new(
-        buf_reader: &mut BufReader<ReadHalf<'_>>,
+    /// the version is neither `HTTP/1.1` nor `HTTP/1.0`.
+    pub async fn new<R: AsyncBufRead + Unpin>(
+        buf_reader: &mut R,
     ) -> Result<Request<'r>, RequestError> {
         // read in the first line and split it into method, target, and version
         let mut line = String::new();
@@ -88,8 +122,8 @@ impl<'r> Request<'r> {
                 let uri = parts[1].to_string();
                 let version = parts[2].trim().to_string();
 
-                // enforce HTTP/1.1-only; any other version is rejected (505)
-                if version != "HTTP/1.1" {
+                // HTTP/1.1, or HTTP/1.0 for older clients; anything else is 505
+                if version != "HTTP/1.1" && version != "HTTP/1.0" {
                     return Err(RequestError::UnsupportedVersion(version));
                 }
 
@@ -100,6 +134,11 @@ impl<'r> Request<'r> {
                     version,
                     headers: None,
                     message_body: None, // assigned later based on body framing
+                    trailers: Trailers::default(),
+                    id: None,        // assigned once the headers are parsed
+                    peer_addr: None, // assigned by the connection processor
+                    local_addr: None,
+                    client: ClientInfo::default(),
                 })
             }
             // map the limit-reached signal to a header-size error
@@ -116,9 +155,9 @@ impl<'r> Request<'r> {
     /// Returns [`RequestError::MalformedRequestError`] for a header line missing
     /// its `:` separator and [`RequestError::MaxHeaderSizeError`] when the block
     /// exceeds [`MAX_HEADERS_SIZE`].
-    pub async fn parse_headers(
+    pub async fn parse_headers<R: AsyncBufRead + Unpin>(
         &mut self,
-        buf_reader: &mut BufReader<ReadHalf<'_>>,
+        buf_reader: &mut R,
     ) -> Result<(), RequestError> {
         let parse_result = read_headers(buf_reader).await?;
         self.total_size += parse_result.1;
@@ -136,8 +175,8 @@ impl<'r> Request<'r> {
 }
 
 /// Reads and parses the header block, returning the headers and bytes consumed.
-async fn read_headers(
-    buf_reader: &mut BufReader<ReadHalf<'_>>,
+async fn read_headers<R: AsyncBufRead + Unpin>(
+    buf_reader: &mut R,
 ) -> Result<(HashMap<String, String>, usize), RequestError> {
     let mut headers = HashMap::<String, String>::new();
     let reader = buf_reader.take(MAX_HEADERS_SIZE as u64);
@@ -181,7 +220,7 @@ async fn read_headers(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::io::AsyncWriteExt;
+    use tokio::io::{AsyncWriteExt, BufReader};
     use tokio::net::{TcpListener, TcpStream};
 
     /// Returns a connected (client, server) TCP pair on the loopback interface.
@@ -239,4 +278,14 @@ mod tests {
         assert!(matches!(result, Err(RequestError::MaxHeaderSizeError)));
         let _ = writer.await;
     }
+
+    #[tokio::test]
+    async fn headers_are_looked_up_case_insensitively() {
+        let mut raw: &[u8] = b"GET / HTTP/1.1\r\nX-Token: abc\r\n\r\n";
+        let mut request = Request::new(&mut raw).await.unwrap();
+        request.parse_headers(&mut raw).await.unwrap();
+        assert_eq!(request.header("x-token"), Some("abc"));
+        assert_eq!(request.header("X-Token"), Some("abc"));
+        assert_eq!(request.header("x-missing"), None);
+    }
 }
@@ -0,0 +1,145 @@
+//! Request identifiers for correlating logs across services.
+//!
+//! With [`RequestIds`] attached through
+//! [`crate::config::ServerConfig::with_request_ids`], every request gets an id:
+//! the caller's `X-Request-Id` when it sent a usable one, or a fresh
+//! [`webe_id::WebeId`] (as 16 hex digits). The id is stored in
+//! [`crate::request::Request::id`] for responders and their logging, echoed on
+//! the response (error responses included), passed to error handlers in
+//! [`crate::error_pages::ErrorContext::request_id`], and written to the access
+//! log.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use webe_id::Generator;
+
+/// The default request id header.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// The longest incoming id that is accepted rather than replaced.
+pub const MAX_REQUEST_ID_LENGTH: usize = 200;
+
+/// How request ids are accepted and generated.
+#[derive(Clone)]
+pub struct RequestIds {
+    generator: Arc<Mutex<Generator>>,
+    header: String,
+    trust_incoming: bool,
+}
+
+impl std::fmt::Debug for RequestIds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestIds")
+            .field("header", &self.header)
+            .field("trust_incoming", &self.trust_incoming)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RequestIds {
+    /// Generates ids with `generator` under [`REQUEST_ID_HEADER`], accepting
+    /// incoming ids.
+    ///
+    /// Give each server instance its own node id so generated ids never collide.
+    pub fn new(generator: Generator) -> RequestIds {
+        RequestIds {
+            generator: Arc::new(Mutex::new(generator)),
+            header: REQUEST_ID_HEADER.to_owned(),
+            trust_incoming: true,
+        }
+    }
+
+    /// Reads and echoes the id under `name` instead of [`REQUEST_ID_HEADER`].
+    pub fn with_header(mut self, name: &str) -> RequestIds {
+        self.header = name.to_owned();
+        self
+    }
+
+    /// Whether an incoming id is kept (the default) or always replaced, as an
+    /// edge server facing untrusted clients may prefer.
+    pub fn with_incoming(mut self, trust_incoming: bool) -> RequestIds {
+        self.trust_incoming = trust_incoming;
+        self
+    }
+
+    /// The request id header name.
+    pub fn header(&self) -> &str {
+        &self.header
+    }
+
+    /// The id for a request with `headers` (lowercased names): the incoming
+    /// one if it is trusted and usable, otherwise a generated one.
+    pub(crate) fn assign(&self, headers: Option<&HashMap<String, String>>) -> Option<String> {
+        let incoming = headers
+            .filter(|_| self.trust_incoming)
+            .and_then(|headers| headers.get(&self.header.to_ascii_lowercase()))
+            .map(|id| id.trim())
+            .filter(|id| usable(id));
+        match incoming {
+            Some(id) => Some(id.to_owned()),
+            None => self.generate(),
+        }
+    }
+
+    /// A fresh id, or `None` while the generator cannot produce one (its clock
+    /// moved backwards, or a millisecond's ids are used up).
+    pub(crate) fn generate(&self) -> Option<String> {
+        let mut generator = self
+            .generator
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        generator.generate().ok().map(|id| id.to_hex_string())
+    }
+}
+
+/// Whether an incoming id can be stored, echoed, and logged as is: non-empty,
+/// bounded, and visible ASCII without quotes or backslashes.
+fn usable(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id
+            .bytes()
+            .all(|byte| byte.is_ascii_graphic() && byte != b'"' && byte != b'\\')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webe_id::NodeId;
+
+    fn ids() -> RequestIds {
+        RequestIds::new(Generator::new(NodeId::from_u8(7)).unwrap())
+    }
+
+    fn headers(id: &str) -> HashMap<String, String> {
+        HashMap::from([("x-request-id".to_owned(), id.to_owned())])
+    }
+
+    #[test]
+    fn usable_incoming_ids_are_kept_and_others_replaced() {
+        let ids = ids();
+        assert_eq!(ids.assign(Some(&headers(" abc-123 "))).unwrap(), "abc-123");
+        for unusable in ["", "has space", "quo\"te", &"x".repeat(201)] {
+            let assigned = ids.assign(Some(&headers(unusable))).unwrap();
+            assert_ne!(assigned, unusable);
+            assert_eq!(assigned.len(), 16);
+        }
+        let first = ids.assign(None).unwrap();
+        assert_ne!(ids.assign(None).unwrap(), first);
+    }
+
+    #[test]
+    fn distrusted_or_renamed_headers_are_respected() {
+        let generated = ids()
+            .with_incoming(false)
+            .assign(Some(&headers("abc")))
+            .unwrap();
+        assert_ne!(generated, "abc");
+
+        let renamed = ids().with_header("X-Correlation-Id");
+        let incoming = HashMap::from([("x-correlation-id".to_owned(), "corr".to_owned())]);
+        assert_eq!(renamed.assign(Some(&incoming)).unwrap(), "corr");
+        assert_ne!(renamed.assign(Some(&headers("abc"))).unwrap(), "abc");
+    }
+}
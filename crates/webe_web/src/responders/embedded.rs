@@ -0,0 +1,408 @@
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Cursor;
+use std::path::Path;
+
+use async_trait::async_trait;
+
+use super::Request;
+use super::Responder;
+use super::Response;
+use super::Status;
+use super::Validation;
+use super::ValidationResult;
+use super::file::MimeTypeList;
+use crate::constants::MIME_OCTET_STREAM;
+use crate::mime::sniff;
+
+/// One file compiled into the binary.
+///
+/// Tables of assets are normally generated at build time by
+/// [`write_asset_manifest`], but can also be written by hand.
+#[derive(Debug)]
+pub struct EmbeddedAsset {
+    /// Path relative to the asset root, using `/` separators (e.g. `js/app.js`).
+    pub path: &'static str,
+    /// The uncompressed contents.
+    pub bytes: &'static [u8],
+    /// A strong entity tag for `bytes`, including its surrounding quotes.
+    pub etag: &'static str,
+    /// A precompressed `gzip` variant of `bytes`, if one was bundled.
+    pub gzip: Option<&'static [u8]>,
+    /// A precompressed `br` (Brotli) variant of `bytes`, if one was bundled.
+    pub brotli: Option<&'static [u8]>,
+}
+
+/// Serves an asset tree compiled into the binary, resolving a route parameter
+/// to an asset path.
+///
+/// Mirrors [`super::file::FileResponder`]'s `GET` behavior without touching the
+/// filesystem: the same MIME resolution, `index.html`/`index.htm` for directory
+/// paths, plus `ETag`/`If-None-Match` revalidation (`304`) and precompressed
+/// `br`/`gzip` variants chosen from the request's `Accept-Encoding`.
+pub struct EmbeddedResponder {
+    assets: HashMap<&'static str, &'static EmbeddedAsset>,
+    path_param: String,
+    use_index: bool,
+    mime_types: MimeTypeList,
+    sniff_content: bool,
+}
+
+impl EmbeddedResponder {
+    /// Creates a responder over `assets`.
+    ///
+    /// `path_param` names the route parameter whose value is the requested asset
+    /// path.
+    pub fn new(assets: &'static [EmbeddedAsset], path_param: String) -> EmbeddedResponder {
+        EmbeddedResponder {
+            assets: assets.iter().map(|asset| (asset.path, asset)).collect(),
+            path_param,
+            use_index: true,
+            mime_types: MimeTypeList::Default,
+            sniff_content: false,
+        }
+    }
+
+    /// Replaces how asset extensions resolve to MIME types.
+    pub fn with_mime_types(mut self, mime_types: MimeTypeList) -> EmbeddedResponder {
+        self.mime_types = mime_types;
+        self
+    }
+
+    /// Guesses the MIME type of assets without an extension from their leading
+    /// bytes (see [`crate::mime::sniff`]).
+    pub fn with_content_sniffing(mut self, sniff_content: bool) -> EmbeddedResponder {
+        self.sniff_content = sniff_content;
+        self
+    }
+
+    /// Looks up the asset for a request path, falling back to an index file for
+    /// directory-like paths. `..` segments never match an asset.
+    pub fn find_asset(&self, path: &str) -> Option<&'static EmbeddedAsset> {
+        let path = match path.split_once('?') {
+            Some((path, _query)) => path,
+            None => path,
+        };
+        let mut segments = Vec::new();
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => return None,
+                segment => segments.push(segment),
+            }
+        }
+        let key = segments.join("/");
+        if let Some(asset) = self.assets.get(key.as_str()) {
+            return Some(asset);
+        }
+        if self.use_index {
+            let prefix = if key.is_empty() { key } else { key + "/" };
+            for index in ["index.html", "index.htm"] {
+                if let Some(asset) = self.assets.get(format!("{prefix}{index}").as_str()) {
+                    return Some(asset);
+                }
+            }
+        }
+        None
+    }
+
+    fn mime_type(&self, asset: &EmbeddedAsset) -> &str {
+        let file_name = asset.path.rsplit('/').next().unwrap_or(asset.path);
+        match file_name.rsplit_once('.') {
+            Some((_stem, extension)) => self.mime_types.resolve(extension),
+            None if self.sniff_content => sniff(asset.bytes).unwrap_or(MIME_OCTET_STREAM),
+            None => MIME_OCTET_STREAM,
+        }
+    }
+
+    fn respond_with(&self, request: &Request, asset: &'static EmbeddedAsset) -> Response {
+        let accept_encoding = request.header("accept-encoding");
+        let (body, coding, etag) = match (asset.brotli, asset.gzip) {
+            (Some(brotli), _) if accepts_coding(accept_encoding, "br") => {
+                (brotli, Some("br"), variant_etag(asset.etag, "br"))
+            }
+            (_, Some(gzip)) if accepts_coding(accept_encoding, "gzip") => {
+                (gzip, Some("gzip"), variant_etag(asset.etag, "gzip"))
+            }
+            _ => (asset.bytes, None, asset.etag.to_owned()),
+        };
+
+        let not_modified = request
+            .header("if-none-match")
+            .is_some_and(|tags| etag_matches(tags, &etag));
+        let mut response = Response::new(if not_modified { 304 } else { 200 });
+        response.headers.insert("ETag".to_owned(), etag);
+        if asset.gzip.is_some() || asset.brotli.is_some() {
+            response
+                .headers
+                .insert("Vary".to_owned(), "Accept-Encoding".to_owned());
+        }
+        if not_modified {
+            return response;
+        }
+        response
+            .headers
+            .insert("Content-Type".to_owned(), self.mime_type(asset).to_owned());
+        response
+            .headers
+            .insert("X-Content-Type-Options".to_owned(), "nosniff".to_owned());
+        if let Some(coding) = coding {
+            response
+                .headers
+                .insert("Content-Encoding".to_owned(), coding.to_owned());
+        }
+        response
+            .headers
+            .insert("Content-Length".to_owned(), body.len().to_string());
+        response.message_body = Some(Box::pin(Cursor::new(body)));
+        response
+    }
+}
+
+#[async_trait]
+impl Responder for EmbeddedResponder {
+    async fn validate(
+        &self,
+        request: &Request,
+        params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> ValidationResult {
+        if request.method != "GET" {
+            return Err(Status::from_standard_code(405));
+        }
+        match params.iter().find(|(key, _value)| *key == self.path_param) {
+            Some((_key, path)) => match self.find_asset(path) {
+                Some(asset) => Ok(Some(Box::new(asset))),
+                None => Err(Status::from_standard_code(404)),
+            },
+            None => Err(Status::from_standard_code(500)), // no path provided
+        }
+    }
+
+    async fn build_response(
+        &self,
+        request: &mut Request,
+        _params: &Vec<(String, String)>,
+        validation: Validation,
+    ) -> Result<Response, u16> {
+        match validation.map(|any_box| any_box.downcast::<&'static EmbeddedAsset>()) {
+            Some(Ok(asset)) => Ok(self.respond_with(request, *asset)),
+            _ => Err(500),
+        }
+    }
+}
+
+/// Returns `true` when an `Accept-Encoding` value allows `coding` (explicitly or
+/// via `*`) with a non-zero quality.
+fn accepts_coding(accept_encoding: Option<&str>, coding: &str) -> bool {
+    let mut wildcard = false;
+    for entry in accept_encoding.unwrap_or("").split(',') {
+        let mut parts = entry.split(';');
+        let name = parts.next().unwrap_or("").trim();
+        let refused = parts.any(|param| {
+            param
+                .trim()
+                .strip_prefix("q=")
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .is_some_and(|q| q <= 0.0)
+        });
+        if name.eq_ignore_ascii_case(coding) {
+            return !refused;
+        }
+        if name == "*" {
+            wildcard = !refused;
+        }
+    }
+    wildcard
+}
+
+/// Derives the entity tag of a precompressed variant from the asset's tag.
+fn variant_etag(etag: &str, coding: &str) -> String {
+    format!("{}-{coding}\"", etag.trim_end_matches('"'))
+}
+
+/// Weak comparison of an `If-None-Match` list against `etag`.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Writes a Rust expression of type `&[EmbeddedAsset]` covering every file
+/// under `asset_dir` to `out_file`. Meant to be called from a build script.
+///
+/// Files named `<asset>.gz` or `<asset>.br` next to `<asset>` are attached as its
+/// precompressed variants rather than served on their own, and dotfiles are
+/// skipped. The contents are pulled in with `include_bytes!`, and a
+/// `cargo:rerun-if-changed` line is printed for the directory. Include the result
+/// with:
+///
+/// ```ignore
+/// // build.rs
+/// fn main() {
+///     let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("assets.rs");
+///     webe_web::responders::embedded::write_asset_manifest("web/dist", &out).unwrap();
+/// }
+///
+/// // src/main.rs
+/// use webe_web::responders::embedded::EmbeddedAsset;
+/// static ASSETS: &[EmbeddedAsset] = include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+/// ```
+///
+/// Returns an I/O error when the directory cannot be walked, a path is not
+/// UTF-8, or `out_file` cannot be written.
+pub fn write_asset_manifest(
+    asset_dir: impl AsRef<Path>,
+    out_file: impl AsRef<Path>,
+) -> std::io::Result<()> {
+    let root = asset_dir.as_ref().canonicalize()?;
+    let mut files = Vec::new();
+    collect_files(&root, &mut files)?;
+    files.sort();
+
+    let mut relative_paths = Vec::with_capacity(files.len());
+    for file in &files {
+        let relative = file
+            .strip_prefix(&root)
+            .map_err(|_error| std::io::Error::other("asset escaped the asset directory"))?;
+        let relative = relative.to_str().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("asset path is not UTF-8: {}", relative.display()),
+            )
+        })?;
+        relative_paths.push(relative.replace('\\', "/"));
+    }
+
+    let mut manifest =
+        String::from("// @generated by webe_web::responders::embedded::write_asset_manifest\n&[\n");
+    for (file, relative) in files.iter().zip(&relative_paths) {
+        // precompressed siblings are emitted with their base asset
+        let is_variant = [".gz", ".br"].iter().any(|suffix| {
+            relative
+                .strip_suffix(suffix)
+                .is_some_and(|base| relative_paths.iter().any(|path| path == base))
+        });
+        if is_variant {
+            continue;
+        }
+        let bytes = std::fs::read(file)?;
+        let variant = |suffix: &str| -> std::io::Result<String> {
+            let mut path = file.clone().into_os_string();
+            path.push(suffix);
+            let path = Path::new(&path);
+            Ok(if path.is_file() {
+                format!("Some(include_bytes!({}))", literal(path)?)
+            } else {
+                "None".to_owned()
+            })
+        };
+        let _ = write!(
+            manifest,
+            "    webe_web::responders::embedded::EmbeddedAsset {{\n        path: {:?},\n        bytes: include_bytes!({}),\n        etag: {:?},\n        gzip: {},\n        brotli: {},\n    }},\n",
+            relative,
+            literal(file)?,
+            format!("\"{:016x}\"", fnv1a(&bytes)),
+            variant(".gz")?,
+            variant(".br")?,
+        );
+    }
+    manifest.push_str("]\n");
+
+    println!("cargo:rerun-if-changed={}", root.display());
+    std::fs::write(out_file, manifest)
+}
+
+/// Recursively collects non-hidden regular files under `dir`.
+fn collect_files(dir: &Path, files: &mut Vec<std::path::PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Renders `path` as a Rust string literal.
+fn literal(path: &Path) -> std::io::Result<String> {
+    match path.to_str() {
+        Some(path) => Ok(format!("{path:?}")),
+        None => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("asset path is not UTF-8: {}", path.display()),
+        )),
+    }
+}
+
+/// 64-bit FNV-1a, used for stable build-time entity tags.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_encoding_honors_quality() {
+        assert!(accepts_coding(Some("gzip, deflate, br"), "br"));
+        assert!(accepts_coding(Some("br;q=0.5, gzip"), "br"));
+        assert!(!accepts_coding(Some("br;q=0, gzip"), "br"));
+        assert!(accepts_coding(Some("*"), "gzip"));
+        assert!(!accepts_coding(Some("*, gzip;q=0"), "gzip"));
+        assert!(!accepts_coding(None, "gzip"));
+    }
+
+    #[test]
+    fn etags_compare_weakly() {
+        assert!(etag_matches("\"abc\"", "\"abc\""));
+        assert!(etag_matches("W/\"abc\", \"def\"", "\"abc\""));
+        assert!(etag_matches("*", "\"abc\""));
+        assert!(!etag_matches("\"abd\"", "\"abc\""));
+        assert_eq!(variant_etag("\"abc\"", "br"), "\"abc-br\"");
+    }
+
+    #[test]
+    fn manifest_pairs_precompressed_variants() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("webe_web_embed_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("js")).unwrap();
+        std::fs::write(dir.join("index.html"), b"<html></html>").unwrap();
+        std::fs::write(dir.join("js/app.js"), b"run()").unwrap();
+        std::fs::write(dir.join("js/app.js.br"), b"br").unwrap();
+        std::fs::write(dir.join("orphan.gz"), b"gz").unwrap();
+        std::fs::write(dir.join(".DS_Store"), b"junk").unwrap();
+        let out = dir.with_extension("rs");
+
+        write_asset_manifest(&dir, &out).unwrap();
+        let manifest = std::fs::read_to_string(&out).unwrap();
+
+        assert!(manifest.contains("path: \"index.html\""));
+        assert!(manifest.contains("path: \"js/app.js\""));
+        assert!(manifest.contains("app.js.br\"))"), "{manifest}");
+        assert!(!manifest.contains("path: \"js/app.js.br\""));
+        // a compressed file with no base asset is served as itself
+        assert!(manifest.contains("path: \"orphan.gz\""));
+        assert!(!manifest.contains("DS_Store"));
+        assert!(manifest.contains(&format!("\"\\\"{:016x}\\\"\"", fnv1a(b"run()"))));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_file(&out);
+    }
+}
@@ -1,10 +1,12 @@
 use std::boxed::Box;
 use std::collections::HashMap;
 use std::fs::File;
-use std::path::{Path, PathBuf};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use async_trait::async_trait;
-use tokio::io::BufReader;
+use tokio::io::AsyncReadExt;
 
 use super::Request;
 use super::Responder;
@@ -12,24 +14,61 @@ use super::Response;
 use super::Status;
 use super::Validation;
 use super::ValidationResult;
-use crate::constants::{DEFAULT_MIME_TYPES, MIME_OCTET_STREAM};
+use super::listing::DirectoryListing;
+use crate::constants::MIME_OCTET_STREAM;
+use crate::mime::{SNIFF_LENGTH, lookup_extension, sniff};
 
 /// How a [`FileResponder`] resolves file extensions to MIME types.
+///
+/// Extensions are always matched case-insensitively.
 pub enum MimeTypeList {
     /// Use the crate's built-in [`crate::constants::DEFAULT_MIME_TYPES`] table.
     Default,
-    /// Use a caller-supplied list of `(extension, mime_type)` pairs.
+    /// Merge caller-supplied `(extension, mime_type)` pairs over the built-in
+    /// table: custom entries win, and unlisted extensions fall back to the default.
     Custom(Vec<(String, String)>),
+    /// Use only the caller-supplied `(extension, mime_type)` pairs.
+    Exclusive(Vec<(String, String)>),
+}
+
+impl MimeTypeList {
+    /// Returns the MIME type for `extension` (without the dot), matched
+    /// case-insensitively, or [`crate::constants::MIME_OCTET_STREAM`] when it is
+    /// unknown.
+    pub fn resolve(&self, extension: &str) -> &str {
+        let found = match self {
+            MimeTypeList::Default => lookup_extension(extension),
+            MimeTypeList::Custom(list) => {
+                lookup_custom(list, extension).or_else(|| lookup_extension(extension))
+            }
+            MimeTypeList::Exclusive(list) => lookup_custom(list, extension),
+        };
+        found.unwrap_or(MIME_OCTET_STREAM)
+    }
 }
 
 /// Serves files from a mount point, resolving a route parameter to a path.
+///
+/// `GET` serves files (and index files or opt-in listings for directories).
+/// `PUT` uploads atomically: the body is streamed to a hidden temporary file in
+/// the target's directory and renamed over the target only once it has fully
+/// arrived, answering `201` for a new file and `204` for a replaced one. `MKCOL`
+/// creates a directory (`201`) and `DELETE` removes a file or empty directory
+/// (`204`). Every operation is confined to the mount point.
 pub struct FileResponder {
     mount_point: PathBuf,
     path_param: String, // specifies the route parameter that provides file path relative to mount point
     use_index: bool,
     mime_types: MimeTypeList,
+    sniff_content: bool,
+    listing: Option<DirectoryListing>,
+    create_dirs: bool,
+    max_upload_size: Option<u64>,
 }
 
+/// Distinguishes concurrent uploads' temporary files within one process.
+static UPLOAD_COUNTER: AtomicU64 = AtomicU64::new(0);
+
 /// Why a [`FileResponder`] could not be constructed.
 #[derive(Debug)]
 pub enum FileResponderError {
@@ -54,39 +93,87 @@ impl FileResponder {
                 path_param,
                 use_index: true,
                 mime_types: MimeTypeList::Default,
+                sniff_content: false,
+                listing: None,
+                create_dirs: false,
+                max_upload_size: None,
             }),
             Err(_error) => Err(FileResponderError::BadPath),
         }
     }
 
-    /// Returns the MIME type for `file_path` based on its extension.
+    /// Renders a listing for directories that have no index file, instead of
+    /// answering `404`. Listings never include entries that resolve outside the
+    /// mount point, and hide dotfiles unless [`DirectoryListing::show_hidden`]
+    /// is set.
+    pub fn with_directory_listing(mut self, listing: DirectoryListing) -> FileResponder {
+        self.listing = Some(listing);
+        self
+    }
+
+    /// Lets `PUT` create missing parent directories (inside the mount point)
+    /// instead of answering `409 Conflict`.
+    pub fn with_create_dirs(mut self, create_dirs: bool) -> FileResponder {
+        self.create_dirs = create_dirs;
+        self
+    }
+
+    /// Caps a single `PUT` upload at `max_bytes`. Larger uploads are answered
+    /// with `413` and leave any existing file untouched.
+    pub fn with_upload_limit(mut self, max_bytes: u64) -> FileResponder {
+        self.max_upload_size = Some(max_bytes);
+        self
+    }
+
+    /// Replaces how file extensions resolve to MIME types.
+    pub fn with_mime_types(mut self, mime_types: MimeTypeList) -> FileResponder {
+        self.mime_types = mime_types;
+        self
+    }
+
+    /// Guesses the MIME type of files without an extension from their leading
+    /// bytes (see [`crate::mime::sniff`]) instead of always sending
+    /// [`crate::constants::MIME_OCTET_STREAM`].
+    ///
+    /// Only enable this for trusted content: a sniffed `text/html` upload is
+    /// rendered by browsers as a page.
+    pub fn with_content_sniffing(mut self, sniff_content: bool) -> FileResponder {
+        self.sniff_content = sniff_content;
+        self
+    }
+
+    /// Returns the MIME type for `file_path` based on its extension, matched
+    /// case-insensitively.
     ///
     /// Falls back to [`crate::constants::MIME_OCTET_STREAM`] when the extension
     /// is unknown.
     pub fn find_mime_type(&self, file_path: &Path) -> &str {
-        match file_path.extension() {
-            Some(extension) => match &self.mime_types {
-                MimeTypeList::Default => {
-                    match DEFAULT_MIME_TYPES
-                        .iter()
-                        .find(|mime_type| mime_type.0 == extension)
-                    {
-                        Some(result) => result.1,
-                        None => MIME_OCTET_STREAM,
-                    }
-                }
-                MimeTypeList::Custom(list) => match list
-                    .iter()
-                    .find(|mime_type| mime_type.0.as_str() == extension)
-                {
-                    Some(result) => result.1.as_str(),
-                    None => MIME_OCTET_STREAM,
-                },
-            },
+        match file_path.extension().and_then(|ext| ext.to_str()) {
+            Some(extension) => self.mime_types.resolve(extension),
             None => MIME_OCTET_STREAM,
         }
     }
 
+    /// Resolves the MIME type for an opened file, sniffing its leading bytes when
+    /// enabled and the path has no extension. Leaves `file` positioned at the start.
+    fn resolve_mime_type(&self, path: &Path, file: &mut File) -> String {
+        if self.sniff_content && path.extension().is_none() {
+            let mut head = Vec::with_capacity(SNIFF_LENGTH);
+            let read = file
+                .by_ref()
+                .take(SNIFF_LENGTH as u64)
+                .read_to_end(&mut head);
+            let rewound = file.seek(SeekFrom::Start(0));
+            if read.is_ok()
+                && rewound.is_ok()
+                && let Some(mime) = sniff(&head)
+            {
+                return mime.to_owned();
+            }
+        }
+        self.find_mime_type(path).to_owned()
+    }
+
     fn validate_get_path(&self, file_path: PathBuf) -> ValidationResult {
         // make sure that the full path is a child of the mount point
         // this also makes sure the file or directory actually exists
@@ -104,6 +191,9 @@ impl FileResponder {
                             return Ok(Some(Box::new(abs_file_path.join("index.htm"))));
                         }
                     }
+                    if self.listing.is_some() && abs_file_path.is_dir() {
+                        return Ok(Some(Box::new(abs_file_path))); // rendered as a listing
+                    }
                     Err(Status::from_standard_code(404))
                 } else {
                     Err(Status::from_standard_code(404)) // not in mounted directory or not a file
@@ -113,18 +203,78 @@ impl FileResponder {
         }
     }
 
-    // just make sure the full path is within the mount point
-    fn validate_put_path(&self, file_path: PathBuf) -> ValidationResult {
-        if file_path.starts_with(&self.mount_point) {
-            if file_path.is_dir() {
-                return Err(Status::from_standard_code(404)); // path is a dir, can't replace dirs
+    /// Resolves a request path for a write (`PUT`, `MKCOL`, `DELETE`) to a path
+    /// inside the mount point.
+    ///
+    /// Only plain path segments are accepted, so `..` can never climb out, and the
+    /// nearest existing ancestor must canonicalize inside the mount point, so a
+    /// symlinked directory cannot redirect the write elsewhere. The mount point
+    /// itself is never a write target.
+    fn resolve_write_path(&self, path_string: &str) -> Result<PathBuf, Status> {
+        let mut relative = PathBuf::new();
+        for component in Path::new(path_string).components() {
+            match component {
+                Component::Normal(part) => relative.push(part),
+                Component::CurDir => {}
+                _ => return Err(Status::from_standard_code(404)), // `..`, roots, prefixes
             }
-            if file_path.is_symlink() {
-                return Err(Status::from_standard_code(404)); // can't replace symlinks
+        }
+        if relative.as_os_str().is_empty() {
+            return Err(Status::from_standard_code(405)); // can't replace the mount point
+        }
+        let target = self.mount_point.join(relative);
+
+        let mut ancestor = target.parent();
+        while let Some(dir) = ancestor {
+            if dir.exists() {
+                return match dir.canonicalize() {
+                    Ok(abs_dir) if abs_dir.starts_with(&self.mount_point) => Ok(target),
+                    _ => Err(Status::from_standard_code(404)), // escapes the mount point
+                };
             }
-            Ok(Some(Box::new(file_path)))
-        } else {
-            Err(Status::from_standard_code(404)) // not in mounted directory or not a file
+            ancestor = dir.parent();
+        }
+        Err(Status::from_standard_code(404))
+    }
+
+    fn validate_put_path(&self, request: &Request, path_string: &str) -> ValidationResult {
+        let file_path = self.resolve_write_path(path_string)?;
+        if file_path.is_symlink() {
+            return Err(Status::from_standard_code(404)); // can't replace symlinks
+        }
+        if file_path.is_dir() {
+            return Err(Status::from_standard_code(404)); // path is a dir, can't replace dirs
+        }
+        if !self.create_dirs && !file_path.parent().is_some_and(|parent| parent.is_dir()) {
+            return Err(Status::from_standard_code(409)); // missing parent directory
+        }
+        // reject a declared oversize body before any of it is read
+        if let Some(max) = self.max_upload_size
+            && let Some(length) = declared_length(request)
+            && length > max
+        {
+            return Err(Status::from_standard_code(413));
+        }
+        Ok(Some(Box::new(file_path)))
+    }
+
+    fn validate_mkcol_path(&self, path_string: &str) -> ValidationResult {
+        let dir_path = self.resolve_write_path(path_string)?;
+        if dir_path.symlink_metadata().is_ok() {
+            return Err(Status::from_standard_code(405)); // something already exists there
+        }
+        if !dir_path.parent().is_some_and(|parent| parent.is_dir()) {
+            return Err(Status::from_standard_code(409)); // missing parent directory
+        }
+        Ok(Some(Box::new(dir_path)))
+    }
+
+    fn validate_delete_path(&self, path_string: &str) -> ValidationResult {
+        let path = self.resolve_write_path(path_string)?;
+        match path.symlink_metadata() {
+            Ok(meta) if meta.is_file() || meta.is_dir() => Ok(Some(Box::new(path))),
+            Ok(_symlink) => Err(Status::from_standard_code(404)), // can't delete symlinks
+            Err(_error) => Err(Status::from_standard_code(404)),
         }
     }
 
@@ -135,19 +285,19 @@ impl FileResponder {
             Ok(meta) => {
                 let size = meta.len();
                 match File::open(path_box.as_ref()) {
-                    Ok(file) => {
+                    Ok(mut file) => {
                         // build the response
                         let mut headers = HashMap::<String, String>::new();
                         headers.insert("Content-Length".to_owned(), size.to_string());
                         headers.insert(
                             "Content-Type".to_owned(),
-                            self.find_mime_type(&path_box).to_string(),
+                            self.resolve_mime_type(&path_box, &mut file),
                         );
+                        // browsers must trust the declared type, never guess their own
+                        headers.insert("X-Content-Type-Options".to_owned(), "nosniff".to_owned());
                         let mut response = Response::new(200);
                         response.headers = headers;
-                        response.message_body =
-                            Some(Box::pin(BufReader::new(tokio::fs::File::from_std(file))));
-                        Ok(response)
+                        Ok(response.with_file_body(file, size))
                     }
                     Err(_error) => Err(500),
                 }
@@ -156,23 +306,118 @@ impl FileResponder {
         }
     }
 
+    // streams the body into a temporary sibling file, then renames it over the
+    // target so readers never observe a partially written file
     async fn respond_to_put(
         &self,
         request: &mut Request<'_>,
         path: Box<PathBuf>,
     ) -> Result<Response, u16> {
-        let create_file_result = tokio::fs::File::create(path.as_ref()).await;
-        match create_file_result {
-            Ok(mut file) => match request.message_body.as_mut() {
-                Some(body) => match tokio::io::copy_buf(body, &mut file).await {
-                    Ok(_) => Ok(Response::new(204)),
-                    Err(_) => Err(500),
-                },
-                None => Err(400),
+        let parent = match path.parent() {
+            Some(parent) => parent,
+            None => return Err(500),
+        };
+        if self.create_dirs && tokio::fs::create_dir_all(parent).await.is_err() {
+            return Err(500);
+        }
+        let existed = path.is_file();
+        let expected = declared_length(request);
+        let body = match request.message_body.as_mut() {
+            Some(body) => body,
+            None => return Err(400),
+        };
+
+        let temp_path = temp_upload_path(&path);
+        let mut temp_file = match tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+            .await
+        {
+            Ok(file) => file,
+            Err(_error) => return Err(500),
+        };
+
+        // read one byte past the limit so an oversize body is detectable
+        let limit = self
+            .max_upload_size
+            .map_or(u64::MAX, |max| max.saturating_add(1));
+        let copied = tokio::io::copy_buf(&mut body.take(limit), &mut temp_file).await;
+        let outcome = match copied {
+            Ok(size) if self.max_upload_size.is_some_and(|max| size > max) => Err(413),
+            // the connection ended before the declared body arrived
+            Ok(size) if expected.is_some_and(|length| size != length) => Err(400),
+            Ok(_size) => match temp_file.sync_all().await {
+                Ok(()) => Ok(()),
+                Err(_error) => Err(500),
             },
-            Err(_err) => Err(500),
+            Err(_error) => Err(400),
+        };
+        drop(temp_file);
+
+        let outcome = match outcome {
+            Ok(()) => tokio::fs::rename(&temp_path, path.as_ref())
+                .await
+                .map_err(|_error| 500u16),
+            Err(code) => Err(code),
+        };
+        match outcome {
+            Ok(()) => Ok(Response::new(if existed { 204 } else { 201 })),
+            Err(code) => {
+                let _ = tokio::fs::remove_file(&temp_path).await;
+                Err(code)
+            }
         }
     }
+
+    async fn respond_to_mkcol(&self, path: Box<PathBuf>) -> Result<Response, u16> {
+        match tokio::fs::create_dir(path.as_ref()).await {
+            Ok(()) => Ok(Response::new(201)),
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => Err(405),
+            Err(_error) => Err(409),
+        }
+    }
+
+    async fn respond_to_delete(&self, path: Box<PathBuf>) -> Result<Response, u16> {
+        let removed = if path.is_dir() {
+            tokio::fs::remove_dir(path.as_ref()).await
+        } else {
+            tokio::fs::remove_file(path.as_ref()).await
+        };
+        match removed {
+            Ok(()) => Ok(Response::new(204)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Err(404),
+            // most commonly a directory that still has entries
+            Err(_error) => Err(409),
+        }
+    }
+}
+
+/// Returns the request's declared `Content-Length`, if any.
+fn declared_length(request: &Request) -> Option<u64> {
+    request
+        .headers
+        .as_ref()
+        .and_then(|headers| headers.get("content-length"))
+        .and_then(|length| length.trim().parse::<u64>().ok())
+}
+
+/// A hidden, unique temporary path next to `target`, so the final rename stays
+/// on one filesystem and is atomic.
+fn temp_upload_path(target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let unique = UPLOAD_COUNTER.fetch_add(1, Ordering::Relaxed);
+    target.with_file_name(format!(".{name}.{}.{unique}.upload", std::process::id()))
+}
+
+/// Finds `extension` in a caller-supplied MIME list, ignoring ASCII case.
+fn lookup_custom<'l>(list: &'l [(String, String)], extension: &str) -> Option<&'l str> {
+    list.iter()
+        .find(|mime_type| mime_type.0.eq_ignore_ascii_case(extension))
+        .map(|mime_type| mime_type.1.as_str())
 }
 
 #[async_trait]
@@ -191,9 +436,23 @@ impl Responder for FileResponder {
                 file_path.push(&self.mount_point);
                 file_path.push(PathBuf::from(path_string));
 
+                // a listing takes its sort order from the query string
+                if let (Some(_listing), "GET", Some((path, _query))) = (
+                    &self.listing,
+                    request.method.as_str(),
+                    path_string.split_once('?'),
+                ) {
+                    let directory = self.mount_point.join(path);
+                    if directory.is_dir() {
+                        file_path = directory;
+                    }
+                }
+
                 match request.method.as_str() {
                     "GET" => return self.validate_get_path(file_path),
-                    "PUT" => return self.validate_put_path(file_path),
+                    "PUT" => return self.validate_put_path(request, path_string),
+                    "MKCOL" => return self.validate_mkcol_path(path_string),
+                    "DELETE" => return self.validate_delete_path(path_string),
                     _ => return Err(Status::from_standard_code(405)), // method not allowed
                 }
             }
@@ -213,8 +472,19 @@ impl Responder for FileResponder {
                 match any_box.downcast::<PathBuf>() {
                     Ok(path_box) => {
                         match request.method.as_str() {
-                            "GET" => return self.respond_to_get(request, path_box),
+                            "GET" => {
+                                if let Some(listing) = &self.listing
+                                    && path_box.is_dir()
+                                {
+                                    return listing
+                                        .respond(request, &path_box, &self.mount_point)
+                                        .await;
+                                }
+                                return self.respond_to_get(request, path_box);
+                            }
                             "PUT" => return self.respond_to_put(request, path_box).await,
+                            "MKCOL" => return self.respond_to_mkcol(path_box).await,
+                            "DELETE" => return self.respond_to_delete(path_box).await,
                             _ => return Err(405), // method not allowed
                         }
                     }
@@ -0,0 +1,272 @@
+use std::io::Cursor;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use chrono::{TimeZone, Utc};
+use serde::Serialize;
+
+use super::Request;
+use super::Response;
+use super::split_query;
+
+/// The column a directory listing is ordered by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListingSort {
+    /// Order by entry name.
+    Name,
+    /// Order by file size in bytes.
+    Size,
+    /// Order by last-modified time.
+    Modified,
+}
+
+/// Opt-in directory listing ("autoindex") options for a
+/// [`super::file::FileResponder`].
+///
+/// A listed directory renders as HTML, or as JSON when the request's `Accept`
+/// header names `application/json`. Directories always sort before files. The
+/// configured order can be overridden per request with `?sort=name|size|modified`
+/// and `?order=asc|desc`.
+#[derive(Clone, Debug)]
+pub struct DirectoryListing {
+    /// Include entries whose names start with `.`. Off by default.
+    pub show_hidden: bool,
+    /// The default sort column.
+    pub sort: ListingSort,
+    /// Sort in descending order by default.
+    pub descending: bool,
+}
+
+impl Default for DirectoryListing {
+    fn default() -> Self {
+        DirectoryListing {
+            show_hidden: false,
+            sort: ListingSort::Name,
+            descending: false,
+        }
+    }
+}
+
+/// One rendered directory entry.
+#[derive(Serialize)]
+struct ListingEntry {
+    name: String,
+    directory: bool,
+    /// Size in bytes; `None` for directories.
+    size: Option<u64>,
+    /// Last-modified time in seconds since the Unix epoch, when available.
+    modified: Option<u64>,
+}
+
+/// The JSON rendering of a listing.
+#[derive(Serialize)]
+struct ListingBody<'a> {
+    path: &'a str,
+    entries: &'a [ListingEntry],
+}
+
+impl DirectoryListing {
+    /// Renders the listing of `dir` (already validated to be inside `mount_point`)
+    /// for `request`. Entries that resolve outside `mount_point`, such as symlinks
+    /// pointing elsewhere, are never listed.
+    pub(crate) async fn respond(
+        &self,
+        request: &Request<'_>,
+        dir: &Path,
+        mount_point: &Path,
+    ) -> Result<Response, u16> {
+        let (path, query) = split_query(&request.uri);
+        let mut entries = self.read_entries(dir, mount_point).await?;
+        self.sort_entries(&mut entries, query);
+
+        let wants_json = request
+            .headers
+            .as_ref()
+            .and_then(|headers| headers.get("accept"))
+            .is_some_and(|accept| accept.to_lowercase().contains("application/json"));
+
+        let (content_type, body) = if wants_json {
+            let body = ListingBody {
+                path,
+                entries: &entries,
+            };
+            match serde_json::to_vec(&body) {
+                Ok(bytes) => ("application/json; charset=utf-8", bytes),
+                Err(_error) => return Err(500),
+            }
+        } else {
+            let is_root = dir == mount_point;
+            (
+                "text/html; charset=utf-8",
+                render_html(path, &entries, is_root).into_bytes(),
+            )
+        };
+
+        let mut response = Response::new(200);
+        response
+            .headers
+            .insert("Content-Type".to_owned(), content_type.to_owned());
+        response
+            .headers
+            .insert("Content-Length".to_owned(), body.len().to_string());
+        response.message_body = Some(Box::pin(Cursor::new(body)));
+        Ok(response)
+    }
+
+    async fn read_entries(&self, dir: &Path, mount_point: &Path) -> Result<Vec<ListingEntry>, u16> {
+        let mut read_dir = tokio::fs::read_dir(dir).await.map_err(|_| 500u16)?;
+        let mut entries = Vec::new();
+        while let Some(entry) = read_dir.next_entry().await.map_err(|_| 500u16)? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !self.show_hidden && name.starts_with('.') {
+                continue;
+            }
+            // follow symlinks, but only list targets that stay inside the mount
+            match tokio::fs::canonicalize(entry.path()).await {
+                Ok(target) if target.starts_with(mount_point) => {}
+                _ => continue,
+            }
+            let meta = match tokio::fs::metadata(entry.path()).await {
+                Ok(meta) => meta,
+                Err(_error) => continue, // vanished or unreadable; skip it
+            };
+            let modified = meta
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|since| since.as_secs());
+            entries.push(ListingEntry {
+                name,
+                directory: meta.is_dir(),
+                size: if meta.is_dir() {
+                    None
+                } else {
+                    Some(meta.len())
+                },
+                modified,
+            });
+        }
+        Ok(entries)
+    }
+
+    fn sort_entries(&self, entries: &mut [ListingEntry], query: Option<&str>) {
+        let mut sort = self.sort;
+        let mut descending = self.descending;
+        for pair in query.unwrap_or("").split('&') {
+            match pair.split_once('=') {
+                Some(("sort", "name")) => sort = ListingSort::Name,
+                Some(("sort", "size")) => sort = ListingSort::Size,
+                Some(("sort", "modified")) => sort = ListingSort::Modified,
+                Some(("order", "asc")) => descending = false,
+                Some(("order", "desc")) => descending = true,
+                _ => {}
+            }
+        }
+
+        entries.sort_by(|a, b| {
+            let by_column = match sort {
+                ListingSort::Name => a.name.cmp(&b.name),
+                ListingSort::Size => a.size.cmp(&b.size).then_with(|| a.name.cmp(&b.name)),
+                ListingSort::Modified => a
+                    .modified
+                    .cmp(&b.modified)
+                    .then_with(|| a.name.cmp(&b.name)),
+            };
+            let by_column = if descending {
+                by_column.reverse()
+            } else {
+                by_column
+            };
+            // directories first, regardless of direction
+            b.directory.cmp(&a.directory).then(by_column)
+        });
+    }
+}
+
+fn render_html(path: &str, entries: &[ListingEntry], is_root: bool) -> String {
+    let base = path.trim_end_matches('/');
+    let title = escape_html(&format!("{base}/"));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {title}</title></head>\n<body>\n<h1>Index of {title}</h1>\n<table>\n<tr><th><a href=\"?sort=name\">Name</a></th><th><a href=\"?sort=size\">Size</a></th><th><a href=\"?sort=modified\">Modified</a></th></tr>\n"
+    );
+    if !is_root {
+        let parent = match base.rsplit_once('/') {
+            Some((parent, _name)) => format!("{parent}/"),
+            None => "/".to_owned(),
+        };
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}\">../</a></td><td></td><td></td></tr>\n",
+            escape_html(&parent)
+        ));
+    }
+    for entry in entries {
+        let suffix = if entry.directory { "/" } else { "" };
+        let href = format!("{}/{}{suffix}", base, encode_path_segment(&entry.name));
+        let size = entry.size.map(|size| size.to_string()).unwrap_or_default();
+        let modified = entry.modified.map(format_timestamp).unwrap_or_default();
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}\">{}{suffix}</a></td><td>{size}</td><td>{modified}</td></tr>\n",
+            escape_html(&href),
+            escape_html(&entry.name),
+        ));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+/// Escapes the characters that are significant inside HTML text and attributes.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent-encodes a file name for use as a single URL path segment.
+fn encode_path_segment(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DD HH:MM` (UTC).
+fn format_timestamp(secs: u64) -> String {
+    i64::try_from(secs)
+        .ok()
+        .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_are_formatted_as_utc() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13");
+    }
+
+    #[test]
+    fn names_are_escaped_and_encoded() {
+        assert_eq!(escape_html("<a&b>"), "&lt;a&amp;b&gt;");
+        assert_eq!(encode_path_segment("a b/c.txt"), "a%20b%2Fc.txt");
+    }
+}
@@ -0,0 +1,52 @@
+use std::io::Cursor;
+
+use async_trait::async_trait;
+
+use super::Request;
+use super::Responder;
+use super::Response;
+use super::Validation;
+use crate::metrics::Metrics;
+
+/// The Prometheus text exposition format content type.
+const EXPOSITION_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Renders a [`Metrics`] registry for a Prometheus scrape.
+///
+/// Register it with the same registry given to
+/// [`crate::config::ServerConfig::with_metrics`], typically on `GET /metrics`.
+pub struct MetricsResponder {
+    metrics: Metrics,
+}
+
+impl MetricsResponder {
+    /// Creates a responder exposing `metrics`.
+    pub fn new(metrics: Metrics) -> MetricsResponder {
+        MetricsResponder { metrics }
+    }
+}
+
+#[async_trait]
+impl Responder for MetricsResponder {
+    async fn build_response(
+        &self,
+        _request: &mut Request,
+        _params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        let bytes = self.metrics.render().into_bytes();
+        let mut response = Response::new(200);
+        response.headers.insert(
+            "Content-Type".to_owned(),
+            EXPOSITION_CONTENT_TYPE.to_owned(),
+        );
+        response
+            .headers
+            .insert("Content-Length".to_owned(), bytes.len().to_string());
+        response
+            .headers
+            .insert("Cache-Control".to_owned(), "no-store".to_owned());
+        response.message_body = Some(Box::pin(Cursor::new(bytes)));
+        Ok(response)
+    }
+}
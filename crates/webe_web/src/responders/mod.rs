@@ -1,13 +1,29 @@
 //! The [`Responder`] trait and the crate's built-in responders.
 
+/// Responder for static assets compiled into the binary.
+pub mod embedded;
 /// File-serving responder.
 pub mod file;
+/// Opt-in directory listings for the file-serving responder.
+pub mod listing;
+/// Prometheus metrics responder.
+pub mod metrics;
 /// `OPTIONS` preflight responder.
 pub mod options;
+/// Reverse-proxy responder forwarding to an upstream HTTP/1.1 server.
+pub mod proxy;
+/// Rate-limiting wrapper answering `429 Too Many Requests`.
+pub mod rate_limit;
+/// Redirect responder and redirecting wrappers (trailing slash, canonical host).
+pub mod redirect;
 /// Single-page-application fallback responder.
 pub mod spa;
+/// Server-sent events (`text/event-stream`) responder.
+pub mod sse;
 /// Fixed status + message responder.
 pub mod static_message;
+/// WebSocket upgrade responder.
+pub mod websocket;
 
 use async_trait::async_trait;
 
@@ -56,3 +72,11 @@ pub trait Responder: Send + Sync {
         validation: Validation,
     ) -> Result<Response, u16>;
 }
+
+/// Splits a request target into its path and optional query string.
+pub(crate) fn split_query(target: &str) -> (&str, Option<&str>) {
+    match target.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (target, None),
+    }
+}
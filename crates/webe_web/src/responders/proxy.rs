@@ -0,0 +1,685 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, ReadBuf, Take,
+};
+use tokio::net::TcpStream;
+
+use super::Request;
+use super::Responder;
+use super::Response;
+use super::Status;
+use super::Validation;
+use crate::constants::{MAX_HEADERS_SIZE, WEBE_BUFFER_SIZE};
+use crate::encoding::chunked::ChunkedDecoder;
+use crate::encoding::chunked_encoder::encode_chunked;
+
+/// Headers that describe a single connection and are never forwarded, in
+/// either direction (RFC 9110 §7.6.1). Names listed in a `Connection` header
+/// are dropped as well.
+const HOP_BY_HOP: [&str; 9] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Forwards requests to an upstream HTTP/1.1 server and relays its responses.
+///
+/// The method, path, query, and end-to-end headers are forwarded; hop-by-hop
+/// headers are stripped and `X-Forwarded-For`, `X-Forwarded-Host`,
+/// `X-Forwarded-Proto`, and `Forwarded` are added, extending any chain the
+/// request already carried with its peer. Request and response bodies are streamed, never
+/// buffered whole. Upstream connections are kept in a small idle pool and reused
+/// once a response body has been read to its end.
+///
+/// An unreachable upstream, a malformed upstream response, or an upstream
+/// switching protocols with `101` answers `502`; an upstream that does not
+/// connect or start responding within the configured timeouts answers `504`.
+///
+/// Repeated upstream response headers are comma-combined, except `Set-Cookie`,
+/// whose values are relayed one per line.
+pub struct ProxyResponder {
+    host: String,
+    port: u16,
+    base_path: String,
+    path_param: Option<String>,
+    preserve_host: bool,
+    connect_timeout: Duration,
+    response_timeout: Duration,
+    pool: Arc<ConnectionPool>,
+}
+
+/// Why a [`ProxyResponder`] could not be constructed.
+#[derive(Debug)]
+pub enum ProxyResponderError {
+    /// The upstream was not an `http://host[:port][/path]` URL.
+    BadUpstream(String),
+}
+
+impl std::fmt::Display for ProxyResponderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyResponderError::BadUpstream(upstream) => write!(
+                f,
+                "proxy: upstream '{upstream}' is not an http://host[:port][/path] URL"
+            ),
+        }
+    }
+}
+
+impl ProxyResponder {
+    /// Creates a responder forwarding to `upstream`, an
+    /// `http://host[:port][/path]` URL. A path on the upstream URL is prefixed to
+    /// every forwarded path.
+    ///
+    /// By default the whole request target is forwarded; see
+    /// [`ProxyResponder::with_path_param`] to forward only part of it. Returns
+    /// [`ProxyResponderError::BadUpstream`] for any other URL form.
+    pub fn new(upstream: &str) -> Result<ProxyResponder, ProxyResponderError> {
+        let bad = || ProxyResponderError::BadUpstream(upstream.to_owned());
+        let rest = upstream.strip_prefix("http://").ok_or_else(bad)?;
+        let (authority, base_path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse::<u16>().map_err(|_| bad())?),
+            None => (authority, 80),
+        };
+        if host.is_empty() || base_path.contains(['?', '#']) {
+            return Err(bad());
+        }
+        Ok(ProxyResponder {
+            host: host.to_owned(),
+            port,
+            base_path: base_path.trim_end_matches('/').to_owned(),
+            path_param: None,
+            preserve_host: false,
+            connect_timeout: Duration::from_secs(5),
+            response_timeout: Duration::from_secs(30),
+            pool: Arc::new(ConnectionPool::new(16, Duration::from_secs(60))),
+        })
+    }
+
+    /// Forwards only the value of the route parameter `path_param` (plus the
+    /// request's query string), so `/api/legacy/<rest>` can map onto the
+    /// upstream's root.
+    pub fn with_path_param(mut self, path_param: String) -> ProxyResponder {
+        self.path_param = Some(path_param);
+        self
+    }
+
+    /// Sends the client's `Host` header upstream instead of the upstream's own
+    /// authority.
+    pub fn with_preserve_host(mut self, preserve_host: bool) -> ProxyResponder {
+        self.preserve_host = preserve_host;
+        self
+    }
+
+    /// Sets how long to wait for an upstream connection (`504` when exceeded).
+    /// Defaults to 5 seconds.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> ProxyResponder {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Sets how long to wait for the upstream's response headers once the
+    /// request has been sent (`504` when exceeded). Defaults to 30 seconds.
+    pub fn with_response_timeout(mut self, timeout: Duration) -> ProxyResponder {
+        self.response_timeout = timeout;
+        self
+    }
+
+    /// Sets how many idle upstream connections are kept, and for how long.
+    /// Defaults to 16 connections for 60 seconds; `0` disables reuse.
+    pub fn with_pool(mut self, max_idle: usize, idle_timeout: Duration) -> ProxyResponder {
+        self.pool = Arc::new(ConnectionPool::new(max_idle, idle_timeout));
+        self
+    }
+
+    /// The upstream request target for `request`.
+    fn upstream_target(&self, request: &Request, params: &[(String, String)]) -> String {
+        let (path, query) = match request.uri.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (request.uri.as_str(), None),
+        };
+        let path = match &self.path_param {
+            Some(name) => match params.iter().find(|(key, _value)| key == name) {
+                // the terminal parameter may have captured the query string
+                Some((_key, value)) => value.split('?').next().unwrap_or(""),
+                None => "",
+            },
+            None => path,
+        };
+        let mut target = format!("{}/{}", self.base_path, path.trim_start_matches('/'));
+        if let Some(query) = query {
+            target.push('?');
+            target.push_str(query);
+        }
+        target
+    }
+
+    /// Decides how the forwarded body is framed from the body the request
+    /// carries: its `Content-Length` when one remains, otherwise `chunked`
+    /// whenever there is a body at all (such as one decoded by
+    /// [`crate::config::ServerConfig::with_request_decoding`], which drops the
+    /// encoded length).
+    async fn body_framing(request: &mut Request<'_>) -> std::io::Result<BodyFraming> {
+        let headers = request.headers.as_ref();
+        if let Some(length) = headers.and_then(|headers| headers.get("content-length")) {
+            return Ok(BodyFraming::Length(length.trim().to_owned()));
+        }
+        if headers.is_some_and(|headers| headers.contains_key("transfer-encoding")) {
+            return Ok(BodyFraming::Chunked);
+        }
+        let carries_body = match request.message_body.as_mut() {
+            Some(body) => !body.fill_buf().await?.is_empty(),
+            None => false,
+        };
+        Ok(match carries_body {
+            true => BodyFraming::Chunked,
+            false => BodyFraming::None,
+        })
+    }
+
+    /// Serializes the upstream request head for a body framed as `framing`.
+    fn request_head(&self, request: &Request, target: &str, framing: &BodyFraming) -> String {
+        let empty = HashMap::new();
+        let headers = request.headers.as_ref().unwrap_or(&empty);
+        let client_host = request.client.host.clone();
+
+        let mut head = format!("{} {} HTTP/1.1\r\n", request.method, target);
+        let host = match (&client_host, self.preserve_host) {
+            (Some(client_host), true) => client_host.clone(),
+            _ if self.port == 80 => self.host.clone(),
+            _ => format!("{}:{}", self.host, self.port),
+        };
+        head.push_str(&format!("Host: {host}\r\n"));
+        for (name, value) in forwardable(headers) {
+            let replaced = matches!(
+                name.as_str(),
+                "forwarded" | "x-forwarded-for" | "x-forwarded-host" | "x-forwarded-proto"
+            );
+            if name == "host" || name == "content-length" || replaced {
+                continue;
+            }
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+
+        // describe the client-facing side of this hop
+        let proto = request.client.scheme.as_str();
+        let peer = request.peer_addr.map(|peer| peer.ip().to_canonical());
+        if let Some(peer) = peer {
+            let forwarded_for = match headers.get("x-forwarded-for") {
+                Some(existing) => format!("{existing}, {peer}"),
+                None => peer.to_string(),
+            };
+            head.push_str(&format!("X-Forwarded-For: {forwarded_for}\r\n"));
+        }
+        if let Some(client_host) = &client_host {
+            head.push_str(&format!("X-Forwarded-Host: {client_host}\r\n"));
+        }
+        head.push_str(&format!("X-Forwarded-Proto: {proto}\r\n"));
+        let mut element = format!("proto={proto}");
+        if let Some(client_host) = &client_host {
+            element = format!("host=\"{}\";{element}", client_host.replace('"', ""));
+        }
+        match peer {
+            Some(IpAddr::V6(peer)) => element = format!("for=\"[{peer}]\";{element}"),
+            Some(IpAddr::V4(peer)) => element = format!("for={peer};{element}"),
+            None => {}
+        }
+        let forwarded = match headers.get("forwarded") {
+            Some(existing) => format!("{existing}, {element}"),
+            None => element,
+        };
+        head.push_str(&format!("Forwarded: {forwarded}\r\n"));
+
+        match framing {
+            BodyFraming::Length(length) => head.push_str(&format!("Content-Length: {length}\r\n")),
+            BodyFraming::Chunked => head.push_str("Transfer-Encoding: chunked\r\n"),
+            BodyFraming::None => {}
+        }
+        head.push_str("\r\n");
+        head
+    }
+
+    async fn connect(&self) -> Result<BufReader<TcpStream>, u16> {
+        let address = (self.host.as_str(), self.port);
+        match tokio::time::timeout(self.connect_timeout, TcpStream::connect(address)).await {
+            Ok(Ok(stream)) => {
+                let _ = stream.set_nodelay(true);
+                Ok(BufReader::new(stream))
+            }
+            Ok(Err(_error)) => Err(502),
+            Err(_elapsed) => Err(504),
+        }
+    }
+
+    /// Sends the request head and body over `connection`.
+    async fn send_request(
+        connection: &mut BufReader<TcpStream>,
+        head: &str,
+        framing: &BodyFraming,
+        body: Option<&mut Pin<Box<dyn AsyncBufRead + '_ + Send + Sync>>>,
+    ) -> std::io::Result<()> {
+        let stream = connection.get_mut();
+        stream.write_all(head.as_bytes()).await?;
+        match (framing, body) {
+            (BodyFraming::Length(_), Some(body)) => {
+                let mut buf = [0u8; WEBE_BUFFER_SIZE];
+                loop {
+                    let read = body.read(&mut buf).await?;
+                    if read == 0 {
+                        break;
+                    }
+                    stream.write_all(&buf[..read]).await?;
+                }
+            }
+            (BodyFraming::Chunked, Some(body)) => encode_chunked(body, stream).await?,
+            (BodyFraming::Chunked, None) => stream.write_all(b"0\r\n\r\n").await?,
+            _ => {}
+        }
+        stream.flush().await
+    }
+}
+
+#[async_trait]
+impl Responder for ProxyResponder {
+    async fn build_response(
+        &self,
+        request: &mut Request,
+        params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        let target = self.upstream_target(request, params);
+        // a client body that cannot be read is the client's failure
+        let framing = Self::body_framing(request).await.map_err(|_error| 400u16)?;
+        let head = self.request_head(request, &target, &framing);
+
+        // a pooled connection may have been closed upstream while idle; a
+        // bodyless request can safely be retried once on a fresh connection
+        let mut connection = match self.pool.take() {
+            Some(connection) => {
+                let mut connection = connection;
+                let sent = Self::send_request(
+                    &mut connection,
+                    &head,
+                    &framing,
+                    request.message_body.as_mut(),
+                )
+                .await;
+                let retry = framing == BodyFraming::None
+                    && match &sent {
+                        Ok(()) => {
+                            let waited =
+                                tokio::time::timeout(self.response_timeout, connection.fill_buf())
+                                    .await;
+                            match waited {
+                                Ok(Ok(buf)) => buf.is_empty(),
+                                Ok(Err(_error)) => true,
+                                Err(_elapsed) => return Err(504),
+                            }
+                        }
+                        Err(_error) => true,
+                    };
+                if retry {
+                    let mut fresh = self.connect().await?;
+                    Self::send_request(&mut fresh, &head, &framing, None)
+                        .await
+                        .map_err(|_error| 502u16)?;
+                    fresh
+                } else {
+                    sent.map_err(|_error| 502u16)?;
+                    connection
+                }
+            }
+            None => {
+                let mut connection = self.connect().await?;
+                Self::send_request(
+                    &mut connection,
+                    &head,
+                    &framing,
+                    request.message_body.as_mut(),
+                )
+                .await
+                .map_err(|_error| 502u16)?;
+                connection
+            }
+        };
+
+        let upstream =
+            match tokio::time::timeout(self.response_timeout, read_response_head(&mut connection))
+                .await
+            {
+                Ok(Ok(upstream)) => upstream,
+                Ok(Err(_error)) => return Err(502),
+                Err(_elapsed) => return Err(504),
+            };
+
+        // the upgrade header is never forwarded, and the proxy cannot follow the
+        // connection into another protocol; it is dropped rather than pooled
+        if upstream.code == 101 {
+            return Err(502);
+        }
+
+        let mut response = Response::from_status(Status {
+            code: upstream.code,
+            reason: upstream.reason.clone(),
+        });
+        let connection_close = upstream
+            .header("connection")
+            .is_some_and(|value| value.to_lowercase().contains("close"));
+        for (name, value) in forwardable(&upstream.headers) {
+            if name != "content-length" {
+                response
+                    .headers
+                    .insert(upstream.names[name].clone(), value.clone());
+            }
+        }
+        for cookie in &upstream.set_cookies {
+            response.append_header("Set-Cookie", cookie);
+        }
+
+        let bodyless = request.method == "HEAD"
+            || upstream.code == 204
+            || upstream.code == 304
+            || (100..200).contains(&upstream.code);
+        let chunked = upstream
+            .header("transfer-encoding")
+            .is_some_and(|value| value.to_lowercase().contains("chunked"));
+        let length = upstream
+            .header("content-length")
+            .and_then(|value| value.trim().parse::<u64>().ok());
+
+        let pool = self.pool.clone();
+        response.message_body = if bodyless {
+            if let Some(length) = length {
+                response
+                    .headers
+                    .insert("Content-Length".to_owned(), length.to_string());
+            }
+            if !connection_close {
+                pool.put(connection);
+            }
+            None
+        } else if chunked {
+            // the upstream connection is not returned to the pool once the body
+            // has been read, so it closes
+            let decoder = ChunkedDecoder::new(connection);
+            response.trailers = Some(decoder.trailers());
+            Some(Box::pin(decoder))
+        } else if let Some(length) = length {
+            response
+                .headers
+                .insert("Content-Length".to_owned(), length.to_string());
+            Some(Box::pin(PooledBody {
+                reader: Some(connection.take(length)),
+                pool: (!connection_close).then_some(pool),
+            }))
+        } else if upstream.header("content-length").is_some() {
+            return Err(502);
+        } else {
+            // delimited by the upstream closing the connection
+            Some(Box::pin(connection))
+        };
+        Ok(response)
+    }
+}
+
+/// How the forwarded request body is framed.
+#[derive(Debug, PartialEq, Eq)]
+enum BodyFraming {
+    None,
+    Length(String),
+    Chunked,
+}
+
+/// Returns the end-to-end headers of a (lowercased) header map.
+fn forwardable(headers: &HashMap<String, String>) -> impl Iterator<Item = (&String, &String)> {
+    let listed: Vec<String> = headers
+        .get("connection")
+        .map(|value| {
+            value
+                .split(',')
+                .map(|token| token.trim().to_lowercase())
+                .collect()
+        })
+        .unwrap_or_default();
+    headers.iter().filter(move |(name, _value)| {
+        !HOP_BY_HOP.contains(&name.as_str()) && !listed.iter().any(|token| token == *name)
+    })
+}
+
+/// A parsed upstream status line and header block.
+struct UpstreamHead {
+    code: u16,
+    reason: String,
+    /// Lowercased names mapped to comma-combined values, except `set-cookie`.
+    headers: HashMap<String, String>,
+    /// Lowercased names mapped to the first spelling the upstream sent.
+    names: HashMap<String, String>,
+    /// Each `Set-Cookie` value, kept apart since cookie dates contain commas.
+    set_cookies: Vec<String>,
+}
+
+impl UpstreamHead {
+    fn header(&self, name: &str) -> Option<&String> {
+        self.headers.get(name)
+    }
+}
+
+/// Reads a final (non-`1xx`) response head, skipping interim responses.
+async fn read_response_head(
+    connection: &mut BufReader<TcpStream>,
+) -> std::io::Result<UpstreamHead> {
+    let malformed = || std::io::Error::from(std::io::ErrorKind::InvalidData);
+    loop {
+        let mut limited = (&mut *connection).take(MAX_HEADERS_SIZE as u64);
+        let mut line = String::new();
+        limited.read_line(&mut line).await?;
+        let mut parts = line.trim_end().splitn(3, ' ');
+        let version = parts.next().unwrap_or("");
+        if !version.starts_with("HTTP/1.") {
+            return Err(malformed());
+        }
+        let code = parts
+            .next()
+            .and_then(|code| code.parse::<u16>().ok())
+            .filter(|code| (100..600).contains(code))
+            .ok_or_else(malformed)?;
+        let reason = parts.next().unwrap_or("").to_owned();
+
+        let mut headers = HashMap::new();
+        let mut names = HashMap::new();
+        let mut set_cookies = Vec::new();
+        loop {
+            let mut line = String::new();
+            if limited.read_line(&mut line).await? == 0 {
+                return Err(malformed());
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line.split_once(':').ok_or_else(malformed)?;
+            let lowercase = name.trim().to_lowercase();
+            if lowercase == "set-cookie" {
+                set_cookies.push(value.trim().to_owned());
+                continue;
+            }
+            names
+                .entry(lowercase.clone())
+                .or_insert_with(|| name.trim().to_owned());
+            headers
+                .entry(lowercase)
+                .and_modify(|existing: &mut String| {
+                    existing.push_str(", ");
+                    existing.push_str(value.trim());
+                })
+                .or_insert_with(|| value.trim().to_owned());
+        }
+
+        // 101 hands the connection over to another protocol; it is final
+        if (100..200).contains(&code) && code != 101 {
+            continue;
+        }
+        return Ok(UpstreamHead {
+            code,
+            reason,
+            headers,
+            names,
+            set_cookies,
+        });
+    }
+}
+
+/// Idle upstream connections, most recently used last.
+struct ConnectionPool {
+    idle: Mutex<Vec<(BufReader<TcpStream>, Instant)>>,
+    max_idle: usize,
+    idle_timeout: Duration,
+}
+
+impl ConnectionPool {
+    fn new(max_idle: usize, idle_timeout: Duration) -> ConnectionPool {
+        ConnectionPool {
+            idle: Mutex::new(Vec::new()),
+            max_idle,
+            idle_timeout,
+        }
+    }
+
+    fn take(&self) -> Option<BufReader<TcpStream>> {
+        let mut idle = self.idle.lock().ok()?;
+        while let Some((connection, since)) = idle.pop() {
+            if since.elapsed() < self.idle_timeout {
+                return Some(connection);
+            }
+        }
+        None
+    }
+
+    fn put(&self, connection: BufReader<TcpStream>) {
+        // leftover bytes mean the upstream sent more than it framed
+        if !connection.buffer().is_empty() {
+            return;
+        }
+        if let Ok(mut idle) = self.idle.lock() {
+            if idle.len() >= self.max_idle {
+                return;
+            }
+            idle.push((connection, Instant::now()));
+        }
+    }
+}
+
+/// A length-delimited upstream response body that returns its connection to the
+/// pool once fully read.
+struct PooledBody {
+    reader: Option<Take<BufReader<TcpStream>>>,
+    pool: Option<Arc<ConnectionPool>>,
+}
+
+impl PooledBody {
+    fn release(&mut self) {
+        if let (Some(reader), Some(pool)) = (self.reader.take(), self.pool.take())
+            && reader.limit() == 0
+        {
+            pool.put(reader.into_inner());
+        }
+    }
+}
+
+impl AsyncRead for PooledBody {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let available = match self.as_mut().poll_fill_buf(cx) {
+            Poll::Ready(Ok(available)) => available,
+            Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+            Poll::Pending => return Poll::Pending,
+        };
+        let amount = available.len().min(buf.remaining());
+        buf.put_slice(&available[..amount]);
+        self.consume(amount);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncBufRead for PooledBody {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<&[u8]>> {
+        let this = self.get_mut();
+        let at_end = match this.reader.as_mut() {
+            Some(reader) => match Pin::new(reader).poll_fill_buf(cx) {
+                Poll::Ready(Ok(available)) => available.is_empty(),
+                Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                Poll::Pending => return Poll::Pending,
+            },
+            None => true,
+        };
+        if at_end {
+            this.release();
+            return Poll::Ready(Ok(&[]));
+        }
+        match this.reader.as_mut() {
+            Some(reader) => Pin::new(reader).poll_fill_buf(cx),
+            None => Poll::Ready(Ok(&[])),
+        }
+    }
+
+    fn consume(self: Pin<&mut Self>, amount: usize) {
+        if let Some(reader) = self.get_mut().reader.as_mut() {
+            Pin::new(reader).consume(amount);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upstream_urls_are_parsed() {
+        let proxy = ProxyResponder::new("http://127.0.0.1:8080/base/").unwrap();
+        assert_eq!(
+            (proxy.host.as_str(), proxy.port, proxy.base_path.as_str()),
+            ("127.0.0.1", 8080, "/base")
+        );
+        let proxy = ProxyResponder::new("http://internal").unwrap();
+        assert_eq!((proxy.port, proxy.base_path.as_str()), (80, ""));
+        assert!(ProxyResponder::new("https://internal").is_err());
+        assert!(ProxyResponder::new("http://internal:port").is_err());
+        assert!(ProxyResponder::new("http://:80").is_err());
+    }
+
+    #[test]
+    fn hop_by_hop_headers_are_not_forwarded() {
+        let headers: HashMap<String, String> = [
+            ("connection", "keep-alive, x-session-hop"),
+            ("keep-alive", "timeout=5"),
+            ("transfer-encoding", "chunked"),
+            ("x-session-hop", "1"),
+            ("accept", "*/*"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .collect();
+        let forwarded: Vec<&String> = forwardable(&headers).map(|(name, _value)| name).collect();
+        assert_eq!(forwarded, vec!["accept"]);
+    }
+}
@@ -0,0 +1,180 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use super::Request;
+use super::Responder;
+use super::Response;
+use super::Validation;
+use super::ValidationResult;
+use super::static_message::StaticResponder;
+use crate::rate_limit::{RateLimitDecision, RateLimiter};
+use crate::validation::Refusal;
+
+/// A key extractor: the key to count a request under, or `None` to exempt it.
+type KeyFn = Arc<dyn Fn(&Request, &Vec<(String, String)>) -> Option<String> + Send + Sync>;
+
+/// What a [`RateLimitResponder`] counts requests under.
+#[derive(Clone)]
+pub enum RateLimitKey {
+    /// The client's IP address, as resolved through any trusted proxies (see
+    /// [`crate::forwarded`]). Requests with no known address share one budget.
+    ClientIp,
+    /// The request method and path (without the query): every client shares
+    /// the route's budget.
+    Route,
+    /// A custom extractor, given the request and its route parameters.
+    Custom(KeyFn),
+}
+
+impl RateLimitKey {
+    /// A custom key extractor; returning `None` exempts the request.
+    pub fn custom<F>(extract: F) -> RateLimitKey
+    where
+        F: Fn(&Request, &Vec<(String, String)>) -> Option<String> + Send + Sync + 'static,
+    {
+        RateLimitKey::Custom(Arc::new(extract))
+    }
+
+    // The `&Vec` parameter matches the responder API; see `Responder`.
+    #[allow(clippy::ptr_arg)]
+    fn extract(&self, request: &Request, params: &Vec<(String, String)>) -> Option<String> {
+        match self {
+            RateLimitKey::ClientIp => Some(match request.client.ip {
+                Some(ip) => ip.to_string(),
+                None => "unknown".to_owned(),
+            }),
+            RateLimitKey::Route => {
+                let path = request.uri.split('?').next().unwrap_or_default();
+                Some(format!("{} {path}", request.method))
+            }
+            RateLimitKey::Custom(extract) => extract(request, params),
+        }
+    }
+}
+
+/// Wraps another responder, answering requests over a [`RateLimiter`]'s limit
+/// with `429 Too Many Requests` and passing the rest through.
+///
+/// Requests are counted during validation, and a refused request is answered
+/// there with a [`Refusal`]: it never reaches the wrapped responder, and its
+/// body is never read. Refusals carry `Retry-After` (whole seconds), and every
+/// counted response
+/// carries `RateLimit-Limit`, `RateLimit-Remaining`, and `RateLimit-Reset`
+/// (seconds until the full budget is back). Requests are keyed by client IP
+/// unless [`RateLimitResponder::with_key`] says otherwise.
+///
+/// To slow password guessing, wrap a login responder:
+/// `RateLimitResponder::new(RateLimiter::sliding_window(5, Duration::from_secs(60)), login)`.
+pub struct RateLimitResponder<R: Responder> {
+    limiter: RateLimiter,
+    key: RateLimitKey,
+    internal_responder: R,
+}
+
+/// A validation carrying the decision alongside the wrapped responder's own.
+struct Counted {
+    decision: RateLimitDecision,
+    inner: Validation,
+}
+
+impl<R: Responder> RateLimitResponder<R> {
+    /// Wraps `internal_responder`, limiting each client IP with `limiter`.
+    pub fn new(limiter: RateLimiter, internal_responder: R) -> RateLimitResponder<R> {
+        RateLimitResponder {
+            limiter,
+            key: RateLimitKey::ClientIp,
+            internal_responder,
+        }
+    }
+
+    /// Counts requests under `key` instead of the client IP.
+    pub fn with_key(mut self, key: RateLimitKey) -> RateLimitResponder<R> {
+        self.key = key;
+        self
+    }
+}
+
+#[async_trait]
+impl<R: Responder> Responder for RateLimitResponder<R> {
+    async fn validate(
+        &self,
+        request: &Request,
+        params: &Vec<(String, String)>,
+        validation: Validation,
+    ) -> ValidationResult {
+        let Some(key) = self.key.extract(request, params) else {
+            return self
+                .internal_responder
+                .validate(request, params, validation)
+                .await;
+        };
+        let decision = self.limiter.check(&key);
+        if !decision.allowed {
+            let retry_after = decision.retry_after.unwrap_or(decision.reset);
+            let refusal = rate_limit_headers(&decision)
+                .into_iter()
+                .fold(Refusal::new(429), |refusal, (name, value)| {
+                    refusal.with_header(name, &value)
+                })
+                .with_header("Retry-After", &whole_seconds(retry_after));
+            return Ok(Some(Box::new(refusal)));
+        }
+        let inner = self
+            .internal_responder
+            .validate(request, params, validation)
+            .await?;
+        Ok(Some(Box::new(Counted { decision, inner })))
+    }
+
+    async fn build_response(
+        &self,
+        request: &mut Request,
+        params: &Vec<(String, String)>,
+        validation: Validation,
+    ) -> Result<Response, u16> {
+        // an outer wrapper kept the refusal from the server; answer it here
+        let validation = match Refusal::from_validation(validation) {
+            Ok(refusal) => {
+                let mut response =
+                    StaticResponder::from_standard_code(refusal.status).quick_response();
+                for (name, value) in refusal.headers {
+                    response.headers.insert(name, value);
+                }
+                return Ok(response);
+            }
+            Err(validation) => validation,
+        };
+        let (decision, validation) = match validation.map(|any_box| any_box.downcast::<Counted>()) {
+            Some(Ok(counted)) => (Some(counted.decision), counted.inner),
+            Some(Err(any_box)) => (None, Some(any_box)),
+            None => (None, None),
+        };
+        let mut response = self
+            .internal_responder
+            .build_response(request, params, validation)
+            .await?;
+        if let Some(decision) = decision {
+            for (name, value) in rate_limit_headers(&decision) {
+                response.headers.insert(name.to_owned(), value);
+            }
+        }
+        Ok(response)
+    }
+}
+
+/// The `RateLimit-*` headers describing `decision`.
+fn rate_limit_headers(decision: &RateLimitDecision) -> [(&'static str, String); 3] {
+    [
+        ("RateLimit-Limit", decision.limit.to_string()),
+        ("RateLimit-Remaining", decision.remaining.to_string()),
+        ("RateLimit-Reset", whole_seconds(decision.reset)),
+    ]
+}
+
+/// `duration` in whole seconds, rounded up so a client never retries early.
+fn whole_seconds(duration: Duration) -> String {
+    let seconds = duration.as_secs() + u64::from(duration.subsec_nanos() > 0);
+    seconds.to_string()
+}
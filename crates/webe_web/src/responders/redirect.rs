@@ -0,0 +1,403 @@
+use async_trait::async_trait;
+
+use super::Request;
+use super::Responder;
+use super::Response;
+use super::Validation;
+use super::ValidationResult;
+use super::split_query;
+
+/// The redirect status codes a redirect responder can answer with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RedirectStatus {
+    /// `301 Moved Permanently`; clients may change `POST` to `GET`.
+    MovedPermanently,
+    /// `302 Found`; clients may change `POST` to `GET`.
+    Found,
+    /// `303 See Other`; the follow-up request is always a `GET`.
+    SeeOther,
+    /// `307 Temporary Redirect`; the method and body are kept.
+    TemporaryRedirect,
+    /// `308 Permanent Redirect`; the method and body are kept.
+    PermanentRedirect,
+}
+
+impl RedirectStatus {
+    /// The numeric status code.
+    pub fn code(self) -> u16 {
+        match self {
+            RedirectStatus::MovedPermanently => 301,
+            RedirectStatus::Found => 302,
+            RedirectStatus::SeeOther => 303,
+            RedirectStatus::TemporaryRedirect => 307,
+            RedirectStatus::PermanentRedirect => 308,
+        }
+    }
+}
+
+/// Answers every request with a redirect to a templated target.
+///
+/// Every route parameter name in the target (e.g. `<id>`) is replaced by its
+/// captured value, so a route `/old/<id>` with target `/new/items/<id>` sends
+/// `/old/42` to `/new/items/42`. Values are percent-encoded where a path needs
+/// it, and leading slashes they bring to the start of a local target are
+/// collapsed, so `/old//evil.example` cannot redirect off-site.
+pub struct RedirectResponder {
+    status: RedirectStatus,
+    target: String,
+    keep_query: bool,
+}
+
+impl RedirectResponder {
+    /// Creates a responder redirecting to `target` with `status`.
+    pub fn new(status: RedirectStatus, target: String) -> RedirectResponder {
+        RedirectResponder {
+            status,
+            target,
+            keep_query: false,
+        }
+    }
+
+    /// Appends the request's query string to the redirect target. Off by default.
+    pub fn with_query_string(mut self, keep_query: bool) -> RedirectResponder {
+        self.keep_query = keep_query;
+        self
+    }
+
+    /// Expands the target template for `request` and its route `params`.
+    pub fn location(&self, request: &Request, params: &[(String, String)]) -> String {
+        let mut location = self.target.clone();
+        for (name, value) in params {
+            // a terminal parameter may have captured the query string
+            let value = value.split('?').next().unwrap_or("");
+            location = location.replace(name.as_str(), &encode_path(value));
+        }
+        // a captured `//host` must not turn a local target into another site's
+        if !self.target.starts_with("//") && location.starts_with("//") {
+            location = format!("/{}", location.trim_start_matches('/'));
+        }
+        match split_query(&request.uri).1 {
+            Some(query) if self.keep_query => append_query(location, query),
+            _ => location,
+        }
+    }
+}
+
+#[async_trait]
+impl Responder for RedirectResponder {
+    async fn build_response(
+        &self,
+        request: &mut Request,
+        params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        Ok(redirect_response(
+            self.status,
+            self.location(request, params),
+        ))
+    }
+}
+
+/// Where a request path's trailing slash should end up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Redirect `/docs` to `/docs/`. Paths whose last segment has a file
+    /// extension (e.g. `/app.js`) are left alone.
+    Add,
+    /// Redirect `/docs/` to `/docs`. The root path `/` is left alone.
+    Remove,
+}
+
+/// A redirect decided during validation, carried to `build_response`.
+struct PendingRedirect(String);
+
+/// Wraps another responder, redirecting requests whose path does not follow the
+/// trailing-slash policy and passing the rest through.
+///
+/// The query string is kept. Redirects use `308` by default so methods and
+/// bodies survive; see [`TrailingSlashResponder::with_status`].
+pub struct TrailingSlashResponder<R: Responder> {
+    policy: TrailingSlash,
+    status: RedirectStatus,
+    internal_responder: R,
+}
+
+impl<R: Responder> TrailingSlashResponder<R> {
+    /// Wraps `internal_responder` with the trailing-slash `policy`.
+    pub fn new(policy: TrailingSlash, internal_responder: R) -> TrailingSlashResponder<R> {
+        TrailingSlashResponder {
+            policy,
+            status: RedirectStatus::PermanentRedirect,
+            internal_responder,
+        }
+    }
+
+    /// Sets the redirect status. Defaults to `308`.
+    pub fn with_status(mut self, status: RedirectStatus) -> TrailingSlashResponder<R> {
+        self.status = status;
+        self
+    }
+
+    /// The normalized target for `uri`, or `None` when it already conforms.
+    fn normalize(&self, uri: &str) -> Option<String> {
+        let (path, query) = split_query(uri);
+        let normalized = match self.policy {
+            TrailingSlash::Remove if path.len() > 1 && path.ends_with('/') => {
+                let trimmed = path.trim_end_matches('/');
+                if trimmed.is_empty() { "/" } else { trimmed }.to_owned()
+            }
+            TrailingSlash::Add if !path.ends_with('/') => {
+                let last_segment = path.rsplit('/').next().unwrap_or(path);
+                if last_segment.contains('.') {
+                    return None;
+                }
+                format!("{path}/")
+            }
+            _ => return None,
+        };
+        // a path starting `//` must not become a scheme-relative, off-site target
+        let normalized = format!("/{}", normalized.trim_start_matches('/'));
+        Some(match query {
+            Some(query) => append_query(normalized, query),
+            None => normalized,
+        })
+    }
+}
+
+#[async_trait]
+impl<R: Responder> Responder for TrailingSlashResponder<R> {
+    async fn validate(
+        &self,
+        request: &Request,
+        params: &Vec<(String, String)>,
+        validation: Validation,
+    ) -> ValidationResult {
+        match self.normalize(&request.uri) {
+            Some(location) => Ok(Some(Box::new(PendingRedirect(location)))),
+            None => {
+                self.internal_responder
+                    .validate(request, params, validation)
+                    .await
+            }
+        }
+    }
+
+    async fn build_response(
+        &self,
+        request: &mut Request,
+        params: &Vec<(String, String)>,
+        validation: Validation,
+    ) -> Result<Response, u16> {
+        match take_pending(validation) {
+            Ok(location) => Ok(redirect_response(self.status, location)),
+            Err(validation) => {
+                self.internal_responder
+                    .build_response(request, params, validation)
+                    .await
+            }
+        }
+    }
+}
+
+/// Wraps another responder, redirecting requests that arrive under any host
+/// other than the canonical one (and, optionally, over plain HTTP) and passing
+/// the rest through.
+///
+/// The path and query are kept. Redirects use `301` by default; see
+/// [`CanonicalHostResponder::with_status`].
+pub struct CanonicalHostResponder<R: Responder> {
+    host: String,
+    https: bool,
+    status: RedirectStatus,
+    internal_responder: R,
+}
+
+impl<R: Responder> CanonicalHostResponder<R> {
+    /// Wraps `internal_responder`, redirecting to `host` (e.g. `www.example.com`,
+    /// or `example.com:8443` with a port).
+    pub fn new(host: String, internal_responder: R) -> CanonicalHostResponder<R> {
+        CanonicalHostResponder {
+            host,
+            https: false,
+            status: RedirectStatus::MovedPermanently,
+            internal_responder,
+        }
+    }
+
+    /// Also redirects requests that did not arrive over HTTPS to `https://`.
+    ///
+    /// This server does not terminate TLS itself, so a request counts as HTTPS
+    /// only when a TLS-terminating proxy trusted through
+    /// [`crate::config::ServerConfig::with_trusted_proxies`] said so (see
+    /// [`crate::forwarded`]); the headers of any other peer are ignored.
+    pub fn with_https(mut self, https: bool) -> CanonicalHostResponder<R> {
+        self.https = https;
+        self
+    }
+
+    /// Sets the redirect status. Defaults to `301`.
+    pub fn with_status(mut self, status: RedirectStatus) -> CanonicalHostResponder<R> {
+        self.status = status;
+        self
+    }
+
+    /// The canonical URL for `request`, or `None` when it already conforms.
+    fn canonicalize(&self, request: &Request) -> Option<String> {
+        let secure = request.client.scheme == "https";
+        let host_matches = request
+            .client
+            .host
+            .as_ref()
+            .is_some_and(|host| host.trim().eq_ignore_ascii_case(&self.host));
+        if host_matches && (secure || !self.https) {
+            return None;
+        }
+        let scheme = if self.https || secure {
+            "https"
+        } else {
+            "http"
+        };
+        Some(format!("{scheme}://{}{}", self.host, request.uri))
+    }
+}
+
+#[async_trait]
+impl<R: Responder> Responder for CanonicalHostResponder<R> {
+    async fn validate(
+        &self,
+        request: &Request,
+        params: &Vec<(String, String)>,
+        validation: Validation,
+    ) -> ValidationResult {
+        match self.canonicalize(request) {
+            Some(location) => Ok(Some(Box::new(PendingRedirect(location)))),
+            None => {
+                self.internal_responder
+                    .validate(request, params, validation)
+                    .await
+            }
+        }
+    }
+
+    async fn build_response(
+        &self,
+        request: &mut Request,
+        params: &Vec<(String, String)>,
+        validation: Validation,
+    ) -> Result<Response, u16> {
+        match take_pending(validation) {
+            Ok(location) => Ok(redirect_response(self.status, location)),
+            Err(validation) => {
+                self.internal_responder
+                    .build_response(request, params, validation)
+                    .await
+            }
+        }
+    }
+}
+
+/// Takes a [`PendingRedirect`] out of `validation`, or hands the validation back
+/// untouched for the wrapped responder.
+fn take_pending(validation: Validation) -> Result<String, Validation> {
+    match validation {
+        Some(any_box) => match any_box.downcast::<PendingRedirect>() {
+            Ok(pending) => Ok(pending.0),
+            Err(any_box) => Err(Some(any_box)),
+        },
+        None => Err(None),
+    }
+}
+
+/// A bodyless redirect to `location`.
+fn redirect_response(status: RedirectStatus, location: String) -> Response {
+    let mut response = Response::new(status.code());
+    response.headers.insert("Location".to_owned(), location);
+    response
+        .headers
+        .insert("Content-Length".to_owned(), "0".to_owned());
+    response
+}
+
+/// Appends `query` to `location`, which may already carry a query string.
+fn append_query(location: String, query: &str) -> String {
+    if query.is_empty() {
+        location
+    } else if location.contains('?') {
+        format!("{location}&{query}")
+    } else {
+        format!("{location}?{query}")
+    }
+}
+
+/// Percent-encodes the bytes of a captured path that may not appear in one,
+/// keeping `/` and existing `%` escapes.
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => encoded.push(byte as char),
+            b'-' | b'.' | b'_' | b'~' | b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+'
+            | b',' | b';' | b'=' | b':' | b'@' | b'/' | b'%' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responders::static_message::StaticResponder;
+
+    fn trailing(policy: TrailingSlash) -> TrailingSlashResponder<StaticResponder> {
+        TrailingSlashResponder::new(policy, StaticResponder::new(200, String::new()))
+    }
+
+    #[test]
+    fn trailing_slashes_are_normalized() {
+        let remove = trailing(TrailingSlash::Remove);
+        assert_eq!(remove.normalize("/docs/"), Some("/docs".to_owned()));
+        assert_eq!(
+            remove.normalize("/docs//?a=1"),
+            Some("/docs?a=1".to_owned())
+        );
+        assert_eq!(remove.normalize("/"), None);
+        assert_eq!(remove.normalize("/docs"), None);
+
+        let add = trailing(TrailingSlash::Add);
+        assert_eq!(add.normalize("/docs?a=1"), Some("/docs/?a=1".to_owned()));
+        assert_eq!(add.normalize("/docs/"), None);
+        assert_eq!(add.normalize("/js/app.js"), None);
+    }
+
+    #[test]
+    fn normalized_paths_stay_on_site() {
+        let remove = trailing(TrailingSlash::Remove);
+        assert_eq!(
+            remove.normalize("//evil.example/"),
+            Some("/evil.example".to_owned())
+        );
+        assert_eq!(remove.normalize("///a//?x=1"), Some("/a?x=1".to_owned()));
+
+        let add = trailing(TrailingSlash::Add);
+        assert_eq!(add.normalize("//evil"), Some("/evil/".to_owned()));
+        assert_eq!(
+            add.normalize("//evil.example/a"),
+            Some("/evil.example/a/".to_owned())
+        );
+    }
+
+    #[test]
+    fn queries_are_appended_to_existing_ones() {
+        assert_eq!(append_query("/a?x=1".to_owned(), "y=2"), "/a?x=1&y=2");
+        assert_eq!(append_query("/a".to_owned(), "y=2"), "/a?y=2");
+        assert_eq!(append_query("/a".to_owned(), ""), "/a");
+    }
+
+    #[test]
+    fn captured_paths_are_percent_encoded() {
+        assert_eq!(encode_path("a b/%20c\"x"), "a%20b/%20c%22x");
+        assert_eq!(encode_path("\\\\evil.example"), "%5C%5Cevil.example");
+        assert_eq!(encode_path("/a;v=1/@b"), "/a;v=1/@b");
+    }
+}
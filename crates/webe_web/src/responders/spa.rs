@@ -4,8 +4,11 @@ use super::Response;
 use super::Validation;
 use super::ValidationResult;
 
+use super::embedded::{EmbeddedAsset, EmbeddedResponder};
 use super::file::{FileResponder, FileResponderError};
 
+use std::path::Path;
+
 use async_trait::async_trait;
 
 /// A single-page-app fallback responder.
@@ -13,13 +16,21 @@ use async_trait::async_trait;
 /// Captures stray endpoints that the SPA handles client-side (for example, a
 /// user refreshing on a deep link such as `/flash/23434455`) by serving a single
 /// application index file. Internally delegates to a [`FileResponder`] mounted on
-/// the application file.
+/// the application file, or to an [`EmbeddedResponder`] when the app is compiled
+/// into the binary ([`SpaResponder::from_embedded`]).
+///
+/// With [`SpaResponder::with_assets_first`], the requested path is served when it
+/// exists, and only navigation requests (`Accept: text/html` for a path without a
+/// file extension) fall back to the app file; a missing asset such as
+/// `/js/missing.js` is a real `404`. This lets one route serve both the app's
+/// assets and its deep links.
 ///
 /// > Renamed from `SPAResponder` in the web crate revamp (see the crate README
 /// > migration notes).
 pub struct SpaResponder {
     app_file_path: String,
-    file_responder: FileResponder,
+    inner: Box<dyn Responder>,
+    assets_param: Option<String>,
 }
 
 impl SpaResponder {
@@ -34,11 +45,45 @@ impl SpaResponder {
         match FileResponder::new(mount_point, String::new()) {
             Ok(file_responder) => Ok(SpaResponder {
                 app_file_path,
-                file_responder,
+                inner: Box::new(file_responder),
+                assets_param: None,
             }),
             Err(error) => Err(error),
         }
     }
+
+    /// Creates a responder that always serves the embedded asset at
+    /// `app_file_path` (e.g. `index.html`) from `assets`.
+    pub fn from_embedded(assets: &'static [EmbeddedAsset], app_file_path: String) -> SpaResponder {
+        SpaResponder {
+            app_file_path,
+            inner: Box::new(EmbeddedResponder::new(assets, String::new())),
+            assets_param: None,
+        }
+    }
+
+    /// Serves the path captured by the route parameter `path_param` when it
+    /// exists, falling back to the app file only for navigation requests.
+    pub fn with_assets_first(mut self, path_param: String) -> SpaResponder {
+        self.assets_param = Some(path_param);
+        self
+    }
+}
+
+/// A navigation request asks for an HTML document at a path without a file
+/// extension, as a browser does when following or reloading a deep link.
+fn is_navigation(request: &Request, path: &str) -> bool {
+    let accepts_html = request
+        .headers
+        .as_ref()
+        .and_then(|headers| headers.get("accept"))
+        .is_some_and(|accept| accept.to_lowercase().contains("text/html"));
+    let path = match path.split_once('?') {
+        Some((path, _query)) => path,
+        None => path,
+    };
+    let last_segment = path.rsplit('/').next().unwrap_or(path);
+    accepts_html && Path::new(last_segment).extension().is_none()
 }
 
 #[async_trait]
@@ -46,13 +91,34 @@ impl Responder for SpaResponder {
     async fn validate(
         &self,
         request: &Request,
-        _params: &Vec<(String, String)>,
+        params: &Vec<(String, String)>,
         validation: Validation,
     ) -> ValidationResult {
-        // pass on to the internal file responder, but fudge the param so the
+        let requested = self
+            .assets_param
+            .as_ref()
+            .and_then(|name| params.iter().find(|(key, _value)| key == name))
+            .map(|(_key, path)| path.clone());
+        let validation = match requested {
+            Some(path) => {
+                let asset_params = vec![(String::new(), path.clone())];
+                match self
+                    .inner
+                    .validate(request, &asset_params, validation)
+                    .await
+                {
+                    Ok(validation) => return Ok(validation),
+                    Err(status) if status.code == 404 && is_navigation(request, &path) => None,
+                    Err(status) => return Err(status),
+                }
+            }
+            None => validation,
+        };
+
+        // pass on to the internal responder, but fudge the param so the
         // app file path becomes the complete path (empty param name)
         let fudged_params = vec![(String::new(), self.app_file_path.clone())];
-        self.file_responder
+        self.inner
             .validate(request, &fudged_params, validation)
             .await
     }
@@ -63,8 +129,6 @@ impl Responder for SpaResponder {
         params: &Vec<(String, String)>,
         validation: Validation,
     ) -> Result<Response, u16> {
-        self.file_responder
-            .build_response(request, params, validation)
-            .await
+        self.inner.build_response(request, params, validation).await
     }
 }
@@ -0,0 +1,324 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures_core::Stream;
+use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+use tokio::sync::mpsc;
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+use super::Request;
+use super::Responder;
+use super::Response;
+use super::Status;
+use super::Validation;
+use super::ValidationResult;
+
+/// One server-sent event.
+///
+/// Built with [`Event::new`] and chained `with_*` calls. Multi-line `data` is
+/// sent as one `data:` line per line and reassembled by the browser.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Event {
+    id: Option<String>,
+    event: Option<String>,
+    data: String,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    /// Creates an unnamed (`message`) event carrying `data`.
+    pub fn new(data: impl Into<String>) -> Event {
+        Event {
+            data: data.into(),
+            ..Event::default()
+        }
+    }
+
+    /// Sets the event ID, which the browser echoes back as `Last-Event-ID` when
+    /// it reconnects. Line breaks are removed.
+    pub fn with_id(mut self, id: impl Into<String>) -> Event {
+        self.id = Some(single_line(id.into()));
+        self
+    }
+
+    /// Sets the event type, dispatched to `addEventListener(type, ..)` in the
+    /// browser. Line breaks are removed.
+    pub fn with_event(mut self, event: impl Into<String>) -> Event {
+        self.event = Some(single_line(event.into()));
+        self
+    }
+
+    /// Tells the browser how long to wait before reconnecting.
+    pub fn with_retry(mut self, retry: Duration) -> Event {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Serializes the event in the `text/event-stream` format.
+    pub fn encode(&self) -> String {
+        let mut encoded = String::new();
+        if let Some(id) = &self.id {
+            encoded.push_str(&format!("id: {id}\n"));
+        }
+        if let Some(event) = &self.event {
+            encoded.push_str(&format!("event: {event}\n"));
+        }
+        if let Some(retry) = self.retry {
+            encoded.push_str(&format!("retry: {}\n", retry.as_millis()));
+        }
+        let data = self.data.replace("\r\n", "\n").replace('\r', "\n");
+        for line in data.split('\n') {
+            encoded.push_str(&format!("data: {line}\n"));
+        }
+        encoded.push('\n');
+        encoded
+    }
+}
+
+fn single_line(value: String) -> String {
+    value.replace(['\r', '\n'], "")
+}
+
+/// An event source that can be sent to a client as a `text/event-stream`
+/// response.
+///
+/// Created from any [`Stream`] of [`Event`]s or from the receiving half of a
+/// tokio channel. The response ends when the source does; when the client
+/// disconnects, the source is dropped, which closes a channel's sender side.
+pub struct EventStream {
+    events: Pin<Box<dyn Stream<Item = Event> + Send>>,
+    keep_alive: Duration,
+}
+
+impl EventStream {
+    /// Wraps a stream of events. Keep-alive comments are sent every 15 seconds
+    /// while the stream is idle.
+    pub fn new(events: impl Stream<Item = Event> + Send + 'static) -> EventStream {
+        EventStream {
+            events: Box::pin(events),
+            keep_alive: Duration::from_secs(15),
+        }
+    }
+
+    /// Sets how long the stream may stay idle before a keep-alive comment is sent
+    /// (which also detects disconnected clients).
+    pub fn with_keep_alive(mut self, keep_alive: Duration) -> EventStream {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Builds the `200 text/event-stream` response. It has no `Content-Length`,
+    /// so it is sent chunked (or, to an `HTTP/1.0` client, until the connection
+    /// closes) and each event is flushed as it is produced.
+    pub fn into_response(self) -> Response {
+        let mut response = Response::new(200);
+        response
+            .headers
+            .insert("Content-Type".to_owned(), "text/event-stream".to_owned());
+        response
+            .headers
+            .insert("Cache-Control".to_owned(), "no-cache".to_owned());
+        // ask buffering proxies to pass events through as they arrive
+        response
+            .headers
+            .insert("X-Accel-Buffering".to_owned(), "no".to_owned());
+
+        let period = self.keep_alive.max(Duration::from_millis(1));
+        let mut keep_alive = tokio::time::interval_at(Instant::now() + period, period);
+        keep_alive.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        response.message_body = Some(Box::pin(EventBody {
+            events: Some(self.events),
+            keep_alive,
+            pending: Vec::new(),
+            position: 0,
+        }));
+        response.with_flushed_chunks()
+    }
+}
+
+impl From<mpsc::Receiver<Event>> for EventStream {
+    fn from(receiver: mpsc::Receiver<Event>) -> EventStream {
+        EventStream::new(ReceiverStream { receiver })
+    }
+}
+
+impl From<mpsc::UnboundedReceiver<Event>> for EventStream {
+    fn from(receiver: mpsc::UnboundedReceiver<Event>) -> EventStream {
+        EventStream::new(UnboundedReceiverStream { receiver })
+    }
+}
+
+struct ReceiverStream {
+    receiver: mpsc::Receiver<Event>,
+}
+
+impl Stream for ReceiverStream {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+struct UnboundedReceiverStream {
+    receiver: mpsc::UnboundedReceiver<Event>,
+}
+
+impl Stream for UnboundedReceiverStream {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// The response body: encoded events, interleaved with keep-alive comments
+/// whenever the source stays idle for a whole keep-alive period.
+struct EventBody {
+    /// `None` once the source has ended.
+    events: Option<Pin<Box<dyn Stream<Item = Event> + Send>>>,
+    keep_alive: Interval,
+    pending: Vec<u8>,
+    position: usize,
+}
+
+impl AsyncRead for EventBody {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let available = match self.as_mut().poll_fill_buf(cx) {
+            Poll::Ready(Ok(available)) => available,
+            Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+            Poll::Pending => return Poll::Pending,
+        };
+        let amount = available.len().min(buf.remaining());
+        buf.put_slice(&available[..amount]);
+        self.consume(amount);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncBufRead for EventBody {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.position >= this.pending.len() {
+            let events = match this.events.as_mut() {
+                Some(events) => events,
+                None => return Poll::Ready(Ok(&[])),
+            };
+            this.pending.clear();
+            this.position = 0;
+            match events.as_mut().poll_next(cx) {
+                Poll::Ready(Some(event)) => {
+                    this.pending.extend_from_slice(event.encode().as_bytes());
+                    this.keep_alive.reset();
+                }
+                Poll::Ready(None) => {
+                    this.events = None;
+                    return Poll::Ready(Ok(&[]));
+                }
+                Poll::Pending => match this.keep_alive.poll_tick(cx) {
+                    Poll::Ready(_instant) => this.pending.extend_from_slice(b": keep-alive\n\n"),
+                    Poll::Pending => return Poll::Pending,
+                },
+            }
+        }
+        Poll::Ready(Ok(&this.pending[this.position..]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amount: usize) {
+        let this = self.get_mut();
+        this.position = (this.position + amount).min(this.pending.len());
+    }
+}
+
+/// Answers `GET` requests with a server-sent event stream opened per request.
+///
+/// `open` receives the request, its route parameters, and the client's
+/// `Last-Event-ID` (sent when the browser reconnects), and returns the
+/// [`EventStream`] to send, so a reconnecting client can resume after the last
+/// event it saw.
+pub struct SseResponder<F>
+where
+    F: Fn(&Request<'_>, &[(String, String)], Option<&str>) -> EventStream + Send + Sync,
+{
+    open: F,
+    keep_alive: Option<Duration>,
+}
+
+impl<F> SseResponder<F>
+where
+    F: Fn(&Request<'_>, &[(String, String)], Option<&str>) -> EventStream + Send + Sync,
+{
+    /// Creates a responder that opens each client's event stream with `open`.
+    pub fn new(open: F) -> SseResponder<F> {
+        SseResponder {
+            open,
+            keep_alive: None,
+        }
+    }
+
+    /// Overrides the keep-alive period of every stream this responder opens.
+    pub fn with_keep_alive(mut self, keep_alive: Duration) -> SseResponder<F> {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+}
+
+#[async_trait]
+impl<F> Responder for SseResponder<F>
+where
+    F: Fn(&Request<'_>, &[(String, String)], Option<&str>) -> EventStream + Send + Sync,
+{
+    async fn validate(
+        &self,
+        request: &Request,
+        _params: &Vec<(String, String)>,
+        validation: Validation,
+    ) -> ValidationResult {
+        if request.method != "GET" {
+            return Err(Status::from_standard_code(405));
+        }
+        Ok(validation)
+    }
+
+    async fn build_response(
+        &self,
+        request: &mut Request,
+        params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        let last_event_id = request
+            .headers
+            .as_ref()
+            .and_then(|headers| headers.get("last-event-id"))
+            .map(|id| id.trim());
+        let mut stream = (self.open)(request, params, last_event_id);
+        if let Some(keep_alive) = self.keep_alive {
+            stream = stream.with_keep_alive(keep_alive);
+        }
+        Ok(stream.into_response())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_encoded_line_by_line() {
+        let event = Event::new("first\nsecond\r\nthird")
+            .with_id("7\n")
+            .with_event("progress")
+            .with_retry(Duration::from_millis(1500));
+        assert_eq!(
+            event.encode(),
+            "id: 7\nevent: progress\nretry: 1500\ndata: first\ndata: second\ndata: third\n\n"
+        );
+        assert_eq!(Event::new("").encode(), "data: \n\n");
+    }
+}
@@ -0,0 +1,141 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use super::Request;
+use super::Responder;
+use super::Response;
+use super::Status;
+use super::Validation;
+use super::ValidationResult;
+use crate::upgrade::OnUpgrade;
+use crate::websocket::{WebSocket, WebSocketConfig, accept_key, is_valid_key};
+
+/// Accepts WebSocket upgrades and runs a handler for each connection.
+///
+/// Validates the opening handshake (`GET`, `Upgrade: websocket`,
+/// `Connection: Upgrade`, and a well-formed `Sec-WebSocket-Key`, otherwise
+/// `400`), answers an unsupported `Sec-WebSocket-Version` with `426`, and
+/// otherwise switches protocols and calls `on_connect` with the [`WebSocket`]
+/// and the route parameters. The connection closes when the handler returns.
+pub struct WebSocketResponder<F> {
+    on_connect: Arc<F>,
+    config: WebSocketConfig,
+    protocols: Vec<String>,
+}
+
+impl<F, Fut> WebSocketResponder<F>
+where
+    F: Fn(WebSocket, Vec<(String, String)>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    /// Creates a responder that hands each accepted connection to `on_connect`.
+    pub fn new(on_connect: F) -> WebSocketResponder<F> {
+        WebSocketResponder {
+            on_connect: Arc::new(on_connect),
+            config: WebSocketConfig::default(),
+            protocols: Vec::new(),
+        }
+    }
+
+    /// Sets the frame and message size limits.
+    pub fn with_config(mut self, config: WebSocketConfig) -> WebSocketResponder<F> {
+        self.config = config;
+        self
+    }
+
+    /// Sets the supported subprotocols, in order of preference. The first one
+    /// the client also offers in `Sec-WebSocket-Protocol` is selected; see
+    /// [`WebSocket::protocol`].
+    pub fn with_protocols(mut self, protocols: Vec<String>) -> WebSocketResponder<F> {
+        self.protocols = protocols;
+        self
+    }
+}
+
+fn has_token(value: Option<&str>, token: &str) -> bool {
+    value.is_some_and(|value| {
+        value
+            .split(',')
+            .any(|candidate| candidate.trim().eq_ignore_ascii_case(token))
+    })
+}
+
+#[async_trait]
+impl<F, Fut> Responder for WebSocketResponder<F>
+where
+    F: Fn(WebSocket, Vec<(String, String)>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    async fn validate(
+        &self,
+        request: &Request,
+        _params: &Vec<(String, String)>,
+        validation: Validation,
+    ) -> ValidationResult {
+        if request.method != "GET" {
+            return Err(Status::from_standard_code(405));
+        }
+        let handshake = has_token(request.header("upgrade"), "websocket")
+            && has_token(request.header("connection"), "upgrade")
+            && request
+                .header("sec-websocket-key")
+                .is_some_and(is_valid_key);
+        if !handshake {
+            return Err(Status::from_standard_code(400));
+        }
+        Ok(validation)
+    }
+
+    async fn build_response(
+        &self,
+        request: &mut Request,
+        params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        if request
+            .header("sec-websocket-version")
+            .map(|version| version.trim())
+            != Some("13")
+        {
+            let mut response = Response::new(426);
+            response
+                .headers
+                .insert("Sec-WebSocket-Version".to_owned(), "13".to_owned());
+            response
+                .headers
+                .insert("Content-Length".to_owned(), "0".to_owned());
+            return Ok(response);
+        }
+        let key = request.header("sec-websocket-key").ok_or(400u16)?;
+        let accept = accept_key(key);
+        let offered = request.header("sec-websocket-protocol").unwrap_or("");
+        let protocol = self
+            .protocols
+            .iter()
+            .find(|supported| has_token(Some(offered), supported))
+            .cloned();
+
+        let on_connect = self.on_connect.clone();
+        let config = self.config.clone();
+        let params = params.clone();
+        let selected = protocol.clone();
+        let mut response = Response::new(101).with_upgrade(
+            "websocket",
+            OnUpgrade::new(move |upgraded| {
+                let socket = WebSocket::from_stream(upgraded, config).with_protocol(selected);
+                on_connect(socket, params)
+            }),
+        );
+        response
+            .headers
+            .insert("Sec-WebSocket-Accept".to_owned(), accept);
+        if let Some(protocol) = protocol {
+            response
+                .headers
+                .insert("Sec-WebSocket-Protocol".to_owned(), protocol);
+        }
+        Ok(response)
+    }
+}
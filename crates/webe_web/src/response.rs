@@ -2,28 +2,83 @@
 
 use std::collections::HashMap;
 use std::pin::Pin;
+use std::task::{Context, Poll};
 
-use tokio::io::{AsyncBufRead, AsyncReadExt, AsyncWriteExt, BufWriter};
+use tokio::io::{
+    AsyncBufRead, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter, ReadBuf,
+};
+use tokio::net::TcpStream;
 use tokio::net::tcp::WriteHalf;
 
 use super::status::Status;
 use crate::body::{ResponseFraming, decide_response_framing};
 use crate::constants::WEBE_BUFFER_SIZE;
-use crate::encoding::chunked_encoder::encode_chunked;
+use crate::encoding::chunked::Trailers;
+use crate::encoding::chunked_encoder::write_chunked;
+use crate::sendfile::{SendFile, send_file};
+use crate::streaming::BodyReceiver;
+use crate::upgrade::OnUpgrade;
 
 /// A response: status, headers, an optional streamed body, and a connection
 /// preference. Written to the client with explicit framing by [`Response::respond`].
+///
+/// Build one with [`Response::new`] or [`Response::from_status`]; the struct is
+/// `#[non_exhaustive]`, so fields can be added without breaking callers.
+#[non_exhaustive]
 pub struct Response {
     /// Status line code + reason.
     pub status: Status,
     /// Whether the connection may be kept alive after this response.
     pub keep_alive: bool,
+    /// HTTP version for the status line, matching the request's (`HTTP/1.1`
+    /// unless the connection processor saw an `HTTP/1.0` request).
+    pub version: String,
     /// Response headers.
     pub headers: HashMap<String, String>,
-    /// Optional streamed body reader.
+    /// Header lines added by [`Response::append_header`], written after
+    /// [`Response::headers`] without being combined with them.
+    pub(crate) appended_headers: Vec<(String, String)>,
+    /// Optional streamed body reader. Setting it replaces a file body set by
+    /// [`Response::with_file_body`].
     pub message_body: Option<Pin<Box<dyn AsyncBufRead + Send>>>,
+    /// Trailer fields sent after the body, read once the body has ended. Only
+    /// a chunked body (or an HTTP/2 stream) can carry them.
+    pub trailers: Option<Trailers>,
+    /// The body set by [`Response::with_file_body`], sent only while
+    /// [`Response::message_body`] is `None`.
+    pub(crate) file_body: Option<FileBody>,
+    /// Whether each chunk of a chunked or close-delimited body is flushed as
+    /// soon as it is read; see [`Response::with_flushed_chunks`].
+    pub(crate) flush_chunks: bool,
+    /// Body bytes (before any chunked framing) written by [`Response::respond`],
+    /// for the access log.
+    pub(crate) body_bytes: u64,
+    /// For a `101 Switching Protocols` response, the callback that takes over
+    /// the connection once the response is written.
+    pub upgrade: Option<OnUpgrade>,
 }
 
+/// A buffered connection writer a [`Response`] can be written to.
+///
+/// Implemented for the write half of a TCP connection, which also enables
+/// zero-copy file bodies, and for the write half of any other split transport,
+/// such as the in-memory connections of [`crate::testing`].
+pub trait ResponseWriter: AsyncWrite + Unpin + Send {
+    /// The TCP socket being written to, through which file bodies can be sent
+    /// with `sendfile`.
+    fn tcp_socket(&self) -> Option<&TcpStream> {
+        None
+    }
+}
+
+impl ResponseWriter for BufWriter<WriteHalf<'_>> {
+    fn tcp_socket(&self) -> Option<&TcpStream> {
+        Some(self.get_ref().as_ref())
+    }
+}
+
+impl<T: AsyncWrite + Send> ResponseWriter for BufWriter<tokio::io::WriteHalf<T>> {}
+
 /// Why writing a response failed.
 #[derive(Debug)]
 pub enum ResponseError {
@@ -48,8 +103,15 @@ impl Response {
         Response {
             status: Status::from_standard_code(status),
             keep_alive: true,
+            version: "HTTP/1.1".to_owned(),
             headers: HashMap::<String, String>::new(),
+            appended_headers: Vec::new(),
             message_body: None,
+            trailers: None,
+            file_body: None,
+            flush_chunks: false,
+            body_bytes: 0,
+            upgrade: None,
         }
     }
 
@@ -58,26 +120,125 @@ impl Response {
         Response {
             status,
             keep_alive: true,
+            version: "HTTP/1.1".to_owned(),
             headers: HashMap::<String, String>::new(),
+            appended_headers: Vec::new(),
             message_body: None,
+            trailers: None,
+            file_body: None,
+            flush_chunks: false,
+            body_bytes: 0,
+            upgrade: None,
+        }
+    }
+
+    /// Adds a header line that is sent as is, alongside any in
+    /// [`Response::headers`] with the same name, for fields such as `Set-Cookie`
+    /// whose values cannot be comma-combined into one line.
+    pub fn append_header(&mut self, name: &str, value: &str) {
+        self.appended_headers
+            .push((name.to_owned(), value.to_owned()));
+    }
+
+    /// Sets `trailers` to be sent after the body. See [`Response::trailers`].
+    pub fn with_trailers(mut self, trailers: Trailers) -> Response {
+        self.trailers = Some(trailers);
+        self
+    }
+
+    /// Flushes each chunk of a chunked body (or, for an `HTTP/1.0` client, of
+    /// the close-delimited body sent instead) to the client as soon as it is
+    /// read, for bodies whose parts must arrive as they are produced (such as
+    /// server-sent events). Chunks are otherwise buffered like any other write.
+    pub fn with_flushed_chunks(mut self) -> Response {
+        self.flush_chunks = true;
+        self
+    }
+
+    /// Uses a [`crate::streaming::body_channel`] receiver as the body, along
+    /// with the trailers its sender finishes with.
+    pub fn with_streamed_body(mut self, body: BodyReceiver) -> Response {
+        self.trailers = Some(body.trailers());
+        self.message_body = Some(Box::pin(body));
+        self
+    }
+
+    /// Uses the first `length` bytes of `file` as the body, with a matching
+    /// `Content-Length`.
+    ///
+    /// Over an HTTP/1.x TCP connection on Linux the bytes are moved to the
+    /// socket kernel-side with `sendfile`; otherwise (or if the file system
+    /// cannot) they are copied like any other body. Setting
+    /// [`Response::message_body`] afterwards replaces the file.
+    pub fn with_file_body(mut self, file: std::fs::File, length: u64) -> Response {
+        self.headers
+            .insert("Content-Length".to_owned(), length.to_string());
+        self.message_body = None;
+        self.file_body = Some(FileBody { file, length });
+        self
+    }
+
+    /// Turns this into a `101 Switching Protocols` response to `protocol` (sent as
+    /// the `Upgrade` header) whose connection is handed to `on_upgrade` after it
+    /// is written. See [`crate::upgrade`].
+    pub fn with_upgrade(mut self, protocol: &str, on_upgrade: OnUpgrade) -> Response {
+        self.status = Status::from_standard_code(101);
+        self.headers
+            .insert("Upgrade".to_owned(), protocol.to_owned());
+        self.message_body = None;
+        self.file_body = None;
+        self.upgrade = Some(on_upgrade);
+        self
+    }
+
+    /// Takes the body to send: [`Response::message_body`] if set, otherwise the
+    /// [`Response::with_file_body`] file.
+    fn take_body(&mut self) -> Option<Body> {
+        match (self.message_body.take(), self.file_body.take()) {
+            (Some(reader), _) => Some(Body::Stream(reader)),
+            (None, Some(file_body)) => Some(Body::File(file_body)),
+            (None, None) => None,
+        }
+    }
+
+    /// Takes the body to send as a reader, for writers that cannot use
+    /// `sendfile`.
+    #[cfg(feature = "http2")]
+    pub(crate) fn take_body_reader(&mut self) -> Option<Pin<Box<dyn AsyncBufRead + Send>>> {
+        match self.take_body()? {
+            Body::Stream(reader) => Some(reader),
+            Body::File(file_body) => Some(file_body.into_reader()),
         }
     }
 
     /// Writes the response to `buf_writer` with explicit body framing.
     ///
     /// A body with a `Content-Length` header is sent verbatim; a body without a
-    /// known length is streamed with `Transfer-Encoding: chunked`; a bodyless
-    /// response sends neither framing header. The `Connection` header is set from
-    /// [`Response::keep_alive`]. Returns [`ResponseError::ReadError`] if the body
-    /// reader fails and [`ResponseError::WriteError`] on a socket write failure.
-    pub async fn respond(
+    /// known length is streamed with `Transfer-Encoding: chunked`, or for an
+    /// `HTTP/1.0` [`Response::version`] written until the connection closes; a
+    /// bodyless response sends neither framing header. The `Connection` header
+    /// is set from [`Response::keep_alive`] (or to `Upgrade` for a `101`
+    /// response). [`Response::trailers`] follow a chunked body's final chunk and
+    /// are dropped for other framings. Returns [`ResponseError::ReadError`] if the body reader fails
+    /// and [`ResponseError::WriteError`] on a socket write failure.
+    pub async fn respond<W: ResponseWriter>(
         &mut self,
-        buf_writer: &mut BufWriter<WriteHalf<'_>>,
+        buf_writer: &mut W,
     ) -> Result<(), ResponseError> {
-        let framing = decide_response_framing(self.message_body.is_some(), &self.headers);
+        let body = self.take_body();
+        let mut framing = decide_response_framing(body.is_some(), &self.headers);
+        if framing == ResponseFraming::Chunked && self.version == "HTTP/1.0" {
+            // HTTP/1.0 has no chunked coding; closing the connection ends the body
+            framing = ResponseFraming::CloseDelimited;
+            self.keep_alive = false;
+            self.headers
+                .retain(|key, _| !key.eq_ignore_ascii_case("transfer-encoding"));
+        }
 
         // reconcile the Connection header from the keep-alive preference
-        let connection = if self.keep_alive {
+        let connection = if self.status.code == 101 {
+            "Upgrade"
+        } else if self.keep_alive {
             "keep-alive"
         } else {
             "close"
@@ -94,12 +255,16 @@ impl Response {
         }
 
         // write the status line
-        let status_line = format!("HTTP/1.1 {} {}\r\n", self.status.code, self.status.reason);
+        let status_line = format!(
+            "{} {} {}\r\n",
+            self.version, self.status.code, self.status.reason
+        );
         if buf_writer.write_all(status_line.as_bytes()).await.is_err() {
             return Err(ResponseError::WriteError);
         }
         // write the headers
-        for (key, val) in self.headers.iter() {
+        let appended = self.appended_headers.iter().map(|(key, val)| (key, val));
+        for (key, val) in self.headers.iter().chain(appended) {
             let header = format!("{key}: {val}\r\n");
             if buf_writer.write_all(header.as_bytes()).await.is_err() {
                 return Err(ResponseError::WriteError);
@@ -110,10 +275,26 @@ impl Response {
             return Err(ResponseError::WriteError);
         }
 
+        // a file body goes kernel-side when it can, leaving nothing to copy
+        let mut body_reader = match body {
+            Some(Body::File(file_body)) => {
+                if framing == ResponseFraming::Length
+                    && send_file_body(buf_writer, &file_body.file, file_body.length).await?
+                {
+                    self.body_bytes = file_body.length;
+                    return Ok(());
+                }
+                Some(file_body.into_reader())
+            }
+            Some(Body::Stream(reader)) => Some(reader),
+            None => None,
+        };
+
         // write the body according to the chosen framing
-        match (framing, &mut self.message_body) {
+        let flush_each = self.flush_chunks && framing == ResponseFraming::CloseDelimited;
+        match (framing, &mut body_reader) {
             (ResponseFraming::None, _) | (_, None) => {}
-            (ResponseFraming::Length, Some(body_reader)) => {
+            (ResponseFraming::Length | ResponseFraming::CloseDelimited, Some(body_reader)) => {
                 let mut buf = [0u8; WEBE_BUFFER_SIZE];
                 loop {
                     match body_reader.read(&mut buf).await {
@@ -122,13 +303,25 @@ impl Response {
                             if buf_writer.write_all(&buf[0..size]).await.is_err() {
                                 return Err(ResponseError::WriteError);
                             }
+                            if flush_each && buf_writer.flush().await.is_err() {
+                                return Err(ResponseError::WriteError);
+                            }
+                            self.body_bytes += size as u64;
                         }
                         Err(_error) => return Err(ResponseError::ReadError),
                     }
                 }
             }
             (ResponseFraming::Chunked, Some(body_reader)) => {
-                if encode_chunked(body_reader, buf_writer).await.is_err() {
+                let trailers = self.trailers.as_ref();
+                let mut counted = CountingReader {
+                    inner: body_reader,
+                    count: &mut self.body_bytes,
+                };
+                if write_chunked(&mut counted, buf_writer, trailers, self.flush_chunks)
+                    .await
+                    .is_err()
+                {
                     return Err(ResponseError::WriteError);
                 }
             }
@@ -141,3 +334,70 @@ impl Response {
         }
     }
 }
+
+/// Counts the bytes read through it into `count`.
+struct CountingReader<'a, R> {
+    inner: &'a mut R,
+    count: &'a mut u64,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CountingReader<'_, R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let polled = Pin::new(&mut *this.inner).poll_read(cx, buf);
+        *this.count += (buf.filled().len() - before) as u64;
+        polled
+    }
+}
+
+/// Sends the first `length` bytes of a [`Response::with_file_body`] file with
+/// `sendfile`, returning `false` (with nothing sent) when the caller must copy
+/// the body instead: the connection is not TCP or `sendfile` is unavailable.
+async fn send_file_body<W: ResponseWriter>(
+    buf_writer: &mut W,
+    file: &std::fs::File,
+    length: u64,
+) -> Result<bool, ResponseError> {
+    if buf_writer.tcp_socket().is_none() {
+        return Ok(false);
+    }
+    // the head must reach the socket before the file does
+    if buf_writer.flush().await.is_err() {
+        return Err(ResponseError::WriteError);
+    }
+    let Some(socket) = buf_writer.tcp_socket() else {
+        return Ok(false);
+    };
+    match send_file(socket, file, length).await {
+        Ok(SendFile::Sent) => Ok(true),
+        Ok(SendFile::Unsupported) => Ok(false),
+        Err(_error) => Err(ResponseError::WriteError),
+    }
+}
+
+/// A response body as [`Response::respond`] sends it.
+enum Body {
+    /// Read from [`Response::message_body`].
+    Stream(Pin<Box<dyn AsyncBufRead + Send>>),
+    /// A [`Response::with_file_body`] file.
+    File(FileBody),
+}
+
+/// The first `length` bytes of a [`Response::with_file_body`] file.
+pub(crate) struct FileBody {
+    file: std::fs::File,
+    length: u64,
+}
+
+impl FileBody {
+    /// A reader over the body, for when the file cannot be sent with `sendfile`.
+    fn into_reader(self) -> Pin<Box<dyn AsyncBufRead + Send>> {
+        let file = tokio::fs::File::from_std(self.file);
+        Box::pin(BufReader::new(file.take(self.length)))
+    }
+}
@@ -237,6 +237,11 @@ mod tests {
             version: "HTTP/1.1".to_owned(),
             headers: None,
             message_body: None,
+            trailers: Default::default(),
+            id: None,
+            peer_addr: None,
+            local_addr: None,
+            client: Default::default(),
         }
     }
 
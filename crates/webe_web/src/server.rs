@@ -2,7 +2,7 @@
 //!
 //! [`Server`] binds a TCP listener and, on [`Server::start`], accepts connections
 //! and hands each one to the per-connection [`crate::processor`] loop on its own
-//! task. Routing types live in [`crate::route`] and are re-exported here for
+//! task, within any [`crate::limits::ConnectionLimits`]. Routing types live in [`crate::route`] and are re-exported here for
 //! source compatibility.
 
 use std::net::{Ipv4Addr, SocketAddr};
@@ -10,8 +10,10 @@ use std::sync::Arc;
 
 use tokio::net::TcpListener;
 
+use crate::config::ServerConfig;
 use crate::error::WebError;
-use crate::processor::process_connection;
+use crate::limits::{refuse_connection, report_shed};
+use crate::processor::process_connection_with_config;
 use crate::request::RequestError;
 use crate::response::ResponseError;
 
@@ -26,6 +28,7 @@ pub struct Server {
     /// The configured bind port (`0` requests an OS-assigned port).
     pub port: u16,
     listener: TcpListener,
+    config: Arc<ServerConfig>,
 }
 
 /// Legacy server failure type, retained for source compatibility.
@@ -68,11 +71,24 @@ impl Server {
                 ip: *ip,
                 port: *port,
                 listener,
+                config: Arc::new(ServerConfig::default()),
             }),
             Err(error) => Err(WebError::Bind(error)),
         }
     }
 
+    /// Replaces the server-wide [`ServerConfig`] used for every connection
+    /// accepted after this call.
+    pub fn with_config(mut self, config: ServerConfig) -> Server {
+        self.config = Arc::new(config);
+        self
+    }
+
+    /// Returns the server-wide configuration.
+    pub fn config(&self) -> &ServerConfig {
+        &self.config
+    }
+
     /// Returns the actual local address the server is bound to.
     ///
     /// Useful when binding with port `0` to discover the OS-assigned port.
@@ -85,14 +101,53 @@ impl Server {
     /// Blocks the current task while the server runs. Returns
     /// [`WebError::Accept`] if accepting a connection fails. Per-connection
     /// failures are isolated to their own task and never stop the server.
+    /// With [`ServerConfig::with_connection_limits`], accepting pauses at the
+    /// connection limit, or connections over a limit are answered `503` and
+    /// closed without being served.
     pub async fn start(&self, routes: RouteMap<'static>) -> Result<(), WebError> {
         let routes_arc = Arc::new(routes);
+        let limits = self.config.connection_limits.as_ref();
         loop {
+            // at the connection limit in pause mode, wait for a slot to free up
+            let reserved = match limits.and_then(|limits| limits.pause_semaphore()) {
+                Some(semaphore) => Some(match Arc::clone(semaphore).try_acquire_owned() {
+                    Ok(permit) => permit,
+                    Err(_) => {
+                        if let Some(access_log) = &self.config.access_log {
+                            access_log.accept_paused();
+                        }
+                        // the semaphore is never closed
+                        let acquired = Arc::clone(semaphore).acquire_owned().await;
+                        acquired.expect("connection semaphore closed")
+                    }
+                }),
+                None => None,
+            };
             match self.listener.accept().await {
-                Ok((stream, _socket)) => {
+                Ok((stream, peer)) => {
+                    let slot = match limits
+                        .map(|limits| limits.admit_connection(peer.ip(), reserved))
+                    {
+                        Some(Err(reason)) => {
+                            report_shed(&self.config, Some(peer), reason);
+                            tokio::spawn(refuse_connection(stream, self.config.clone(), reason));
+                            continue;
+                        }
+                        Some(Ok(slot)) => Some(slot),
+                        None => None,
+                    };
                     let process_routes = routes_arc.clone();
+                    let process_config = self.config.clone();
                     tokio::spawn(async move {
-                        let _ = process_connection(stream, process_routes).await;
+                        let _slot = slot;
+                        let config = process_config.clone();
+                        if let Err(error) =
+                            process_connection_with_config(stream, process_routes, process_config)
+                                .await
+                            && let Some(access_log) = &config.access_log
+                        {
+                            access_log.connection_error(Some(peer), &error);
+                        }
                     });
                 }
                 Err(error) => return Err(WebError::Accept(error)),
@@ -43,6 +43,7 @@ impl Status {
             304 => "Not Modified",
             305 => "Use Proxy",
             307 => "Temporary Redirect",
+            308 => "Permanent Redirect",
             400 => "Bad Request",
             401 => "Unauthorized",
             402 => "Payment Required",
@@ -61,6 +62,7 @@ impl Status {
             415 => "Unsupported Media Type",
             416 => "Requested range not satisfiable",
             417 => "Expectation Failed",
+            429 => "Too Many Requests",
             500 => "Internal Server Error",
             501 => "Not Implemented",
             502 => "Bad Gateway",
@@ -0,0 +1,187 @@
+//! Response bodies produced incrementally by application code.
+//!
+//! [`body_channel`] pairs a [`BodySender`], which a spawned task pushes chunks
+//! into, with a [`BodyReceiver`] that becomes the response body through
+//! [`crate::response::Response::with_streamed_body`]. Chunks are written as they
+//! arrive (chunked when no `Content-Length` is set; add
+//! [`crate::response::Response::with_flushed_chunks`] for each to reach the
+//! client without waiting for the write buffer to fill), and the sender can
+//! finish the body with trailer fields, such as a checksum, sent after the final
+//! chunk.
+//!
+//! The channel is bounded, so a producer faster than the client waits in
+//! [`BodySender::send`] instead of buffering the body in memory.
+
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+use tokio::sync::mpsc;
+
+use crate::encoding::chunked::Trailers;
+
+/// Creates a streamed body holding at most `capacity` unsent chunks (at least 1).
+pub fn body_channel(capacity: usize) -> (BodySender, BodyReceiver) {
+    let (sender, receiver) = mpsc::channel(capacity.max(1));
+    let trailers = Trailers::default();
+    (
+        BodySender {
+            sender,
+            trailers: trailers.clone(),
+        },
+        BodyReceiver {
+            receiver,
+            chunk: Vec::new(),
+            position: 0,
+            trailers,
+        },
+    )
+}
+
+/// The body is no longer being read: the client disconnected or the response
+/// was dropped. The producer should stop.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BodyClosed;
+
+impl std::fmt::Display for BodyClosed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the response body is no longer being read")
+    }
+}
+
+impl std::error::Error for BodyClosed {}
+
+/// The producing half of a streamed body.
+///
+/// Dropping the sender ends the body normally, without trailers; use
+/// [`BodySender::finish`] to add them or [`BodySender::abort`] to signal that the
+/// body is incomplete.
+pub struct BodySender {
+    sender: mpsc::Sender<io::Result<Vec<u8>>>,
+    trailers: Trailers,
+}
+
+impl BodySender {
+    /// Queues `chunk`, waiting while the channel is full. Empty chunks are
+    /// skipped, since an empty read would end the body early.
+    pub async fn send(&self, chunk: impl Into<Vec<u8>>) -> Result<(), BodyClosed> {
+        let chunk = chunk.into();
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        self.sender.send(Ok(chunk)).await.map_err(|_| BodyClosed)
+    }
+
+    /// Ends the body, sending `trailers` after the final chunk. Trailers are only
+    /// written for a chunked HTTP/1.1 body or an HTTP/2 stream; fields that may
+    /// not appear in a trailer (such as `Content-Length`) are dropped.
+    pub fn finish(self, trailers: HashMap<String, String>) {
+        self.trailers.fill(trailers);
+    }
+
+    /// Ends the body with an error. The response is cut off without its final
+    /// chunk (or the HTTP/2 stream is reset), so the client sees it as incomplete.
+    pub async fn abort(self, error: io::Error) {
+        let _ = self.sender.send(Err(error)).await;
+    }
+
+    /// Whether the body is no longer being read, so producing more is pointless.
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
+/// The reading half of a streamed body, used as a response body.
+pub struct BodyReceiver {
+    receiver: mpsc::Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    position: usize,
+    trailers: Trailers,
+}
+
+impl BodyReceiver {
+    /// A handle to the trailers, filled by [`BodySender::finish`] before the body
+    /// ends.
+    pub fn trailers(&self) -> Trailers {
+        self.trailers.clone()
+    }
+}
+
+impl AsyncBufRead for BodyReceiver {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.position == this.chunk.len() {
+            match this.receiver.poll_recv(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    this.chunk = chunk;
+                    this.position = 0;
+                }
+                Poll::Ready(Some(Err(error))) => return Poll::Ready(Err(error)),
+                Poll::Ready(None) => {} // every sender is gone: end of body
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(&this.chunk[this.position..]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amount: usize) {
+        let this = self.get_mut();
+        this.position = (this.position + amount).min(this.chunk.len());
+    }
+}
+
+impl AsyncRead for BodyReceiver {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let available = match self.as_mut().poll_fill_buf(cx) {
+            Poll::Ready(Ok(available)) => available,
+            Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+            Poll::Pending => return Poll::Pending,
+        };
+        let amount = available.len().min(buf.remaining());
+        buf.put_slice(&available[..amount]);
+        self.consume(amount);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn chunks_arrive_in_order_and_trailers_are_set_at_the_end() {
+        let (sender, mut receiver) = body_channel(1);
+        let trailers = receiver.trailers();
+        tokio::spawn(async move {
+            sender.send("first ").await.unwrap();
+            sender.send(Vec::new()).await.unwrap();
+            sender.send(b"second".to_vec()).await.unwrap();
+            sender.finish(HashMap::from([("X-Count".to_owned(), "2".to_owned())]));
+        });
+        let mut body = String::new();
+        receiver.read_to_string(&mut body).await.unwrap();
+        assert_eq!(body, "first second");
+        assert_eq!(trailers.get().unwrap().get("x-count").unwrap(), "2");
+    }
+
+    #[tokio::test]
+    async fn abort_fails_the_read_and_a_dropped_receiver_closes_the_sender() {
+        let (sender, mut receiver) = body_channel(4);
+        sender.send("partial").await.unwrap();
+        sender.abort(io::Error::other("export failed")).await;
+        let error = receiver.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(error.to_string(), "export failed");
+
+        let (sender, receiver) = body_channel(4);
+        drop(receiver);
+        assert!(sender.is_closed());
+        assert_eq!(sender.send("late").await, Err(BodyClosed));
+    }
+}
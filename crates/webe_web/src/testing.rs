@@ -0,0 +1,444 @@
+//! In-memory testing for responders and route maps.
+//!
+//! [`TestClient`] serves a [`RouteMap`] over an in-memory duplex connection
+//! running the same HTTP/1.x connection loop as
+//! [`crate::processor::process_connection`], so requests go through real
+//! parsing, routing, validation, body framing, and response writing without
+//! binding a socket. Requests are built with [`TestRequest`]; responses come back
+//! parsed as [`TestResponse`].
+//!
+//! ```
+//! # use webe_web::responders::static_message::StaticResponder;
+//! # use webe_web::server::Route;
+//! use webe_web::testing::{TestClient, TestRequest};
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let client = TestClient::for_responder(
+//!     Route::new("GET", "/hello"),
+//!     StaticResponder::new(200, "hi".to_owned()),
+//! );
+//! let response = client.send(TestRequest::get("/hello")).await;
+//! assert_eq!(response.status, 200);
+//! assert_eq!(response.body_string(), "hi");
+//! # });
+//! ```
+//!
+//! Connections that switch protocols (`101`) are closed after the response,
+//! since the in-memory transport cannot be handed to an upgrade callback.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::config::ServerConfig;
+use crate::processor::{ConnectionAddrs, serve_http1};
+use crate::responders::Responder;
+use crate::route::{Route, RouteMap};
+
+/// Bytes the in-memory connection buffers in each direction.
+const DUPLEX_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A request to send through a [`TestClient`].
+///
+/// Defaults to `HTTP/1.1` with a `Host: localhost` header; a body gets a
+/// `Content-Length` unless a framing header was set explicitly.
+#[derive(Clone, Debug)]
+pub struct TestRequest {
+    method: String,
+    target: String,
+    version: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl TestRequest {
+    /// Creates a request for `method` and `target` (path plus query string).
+    pub fn new(method: &str, target: &str) -> TestRequest {
+        TestRequest {
+            method: method.to_owned(),
+            target: target.to_owned(),
+            version: "HTTP/1.1".to_owned(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Creates a `GET` request for `target`.
+    pub fn get(target: &str) -> TestRequest {
+        TestRequest::new("GET", target)
+    }
+
+    /// Creates a `POST` request for `target`.
+    pub fn post(target: &str) -> TestRequest {
+        TestRequest::new("POST", target)
+    }
+
+    /// Creates a `PUT` request for `target`.
+    pub fn put(target: &str) -> TestRequest {
+        TestRequest::new("PUT", target)
+    }
+
+    /// Creates a `DELETE` request for `target`.
+    pub fn delete(target: &str) -> TestRequest {
+        TestRequest::new("DELETE", target)
+    }
+
+    /// Sets the HTTP version token, e.g. `HTTP/1.0`.
+    pub fn with_version(mut self, version: &str) -> TestRequest {
+        self.version = version.to_owned();
+        self
+    }
+
+    /// Adds a header. Repeated names are sent as separate header lines.
+    pub fn with_header(mut self, name: &str, value: &str) -> TestRequest {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Sets the body.
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> TestRequest {
+        self.body = body.into();
+        self
+    }
+
+    /// Sets a JSON body with a matching `Content-Type`.
+    pub fn with_json<T: serde::Serialize>(self, value: &T) -> serde_json::Result<TestRequest> {
+        let body = serde_json::to_vec(value)?;
+        Ok(self
+            .with_header("Content-Type", "application/json")
+            .with_body(body))
+    }
+
+    /// The request as it is sent on the wire.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let has = |name: &str| {
+            self.headers
+                .iter()
+                .any(|(key, _)| key.eq_ignore_ascii_case(name))
+        };
+        let mut head = format!("{} {} {}\r\n", self.method, self.target, self.version);
+        if !has("host") {
+            head.push_str("Host: localhost\r\n");
+        }
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        if !self.body.is_empty() && !has("content-length") && !has("transfer-encoding") {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        head.push_str("\r\n");
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+}
+
+/// A parsed response received by a [`TestClient`].
+#[derive(Clone, Debug)]
+pub struct TestResponse {
+    /// The status line's HTTP version.
+    pub version: String,
+    /// The status code.
+    pub status: u16,
+    /// The reason phrase.
+    pub reason: String,
+    /// Header fields in the order they were received.
+    pub headers: Vec<(String, String)>,
+    /// The body, with any chunked framing removed.
+    pub body: Vec<u8>,
+    /// Trailer fields after a chunked body, names lowercased.
+    pub trailers: HashMap<String, String>,
+}
+
+impl TestResponse {
+    /// The first value of header `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Every value of header `name`, in the order received.
+    pub fn header_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The body as text, with invalid UTF-8 replaced.
+    pub fn body_string(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// The body parsed as JSON.
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+/// Sends requests to a [`RouteMap`] over in-memory connections.
+///
+/// Each call opens a fresh connection, writes the requests, closes the client's
+/// write side, and collects every response until the server closes the
+/// connection.
+pub struct TestClient<'r> {
+    routes: RouteMap<'r>,
+    config: ServerConfig,
+    addrs: ConnectionAddrs,
+}
+
+impl<'r> TestClient<'r> {
+    /// Serves `routes` with the default [`ServerConfig`].
+    pub fn new(routes: RouteMap<'r>) -> TestClient<'r> {
+        TestClient {
+            routes,
+            config: ServerConfig::default(),
+            addrs: ConnectionAddrs::default(),
+        }
+    }
+
+    /// Serves a single `responder` on `route`.
+    pub fn for_responder<T: Responder + 'r>(route: Route, responder: T) -> TestClient<'r> {
+        let mut routes = RouteMap::new();
+        routes.add_route(route, responder);
+        TestClient::new(routes)
+    }
+
+    /// Serves with `config` instead of the default.
+    pub fn with_config(mut self, config: ServerConfig) -> TestClient<'r> {
+        self.config = config;
+        self
+    }
+
+    /// Connects from `peer`, as seen in [`crate::request::Request::peer_addr`]
+    /// and used for trusted-proxy resolution; by default the peer is unknown.
+    pub fn with_peer_addr(mut self, peer: SocketAddr) -> TestClient<'r> {
+        self.addrs.peer = Some(peer);
+        self
+    }
+
+    /// Sends one request on its own connection and returns its response.
+    ///
+    /// Panics if the server sent no response, which only happens when the
+    /// request was not a complete HTTP message.
+    pub async fn send(&self, request: TestRequest) -> TestResponse {
+        self.send_all(vec![request])
+            .await
+            .into_iter()
+            .next()
+            .expect("the server should answer the request")
+    }
+
+    /// Pipelines `requests` on one connection and returns the responses,
+    /// which stop early if the server closes the connection.
+    pub async fn send_all(&self, requests: Vec<TestRequest>) -> Vec<TestResponse> {
+        let methods: Vec<String> = requests
+            .iter()
+            .map(|request| request.method.clone())
+            .collect();
+        let raw: Vec<u8> = requests.iter().flat_map(TestRequest::to_bytes).collect();
+        parse_responses(&self.exchange(&raw).await, &methods)
+    }
+
+    /// Sends `raw` bytes on one connection and returns every response, for
+    /// requests [`TestRequest`] cannot express (such as malformed ones).
+    pub async fn send_raw(&self, raw: &[u8]) -> Vec<TestResponse> {
+        parse_responses(&self.exchange(raw).await, &[])
+    }
+
+    /// Writes `raw` to a fresh connection and reads until the server closes it.
+    async fn exchange(&self, raw: &[u8]) -> Vec<u8> {
+        let (client, server) = tokio::io::duplex(DUPLEX_BUFFER_SIZE);
+        let (server_reader, server_writer) = tokio::io::split(server);
+        let (mut client_reader, mut client_writer) = tokio::io::split(client);
+
+        let serve = async {
+            // a write failure or upgrade just ends the connection
+            let _ = serve_http1(
+                server_reader,
+                server_writer,
+                &self.routes,
+                &self.config,
+                self.addrs,
+            )
+            .await;
+        };
+        let send = async {
+            // the server may close before reading everything, e.g. after a 400
+            let _ = client_writer.write_all(raw).await;
+            let _ = client_writer.shutdown().await;
+        };
+        let receive = async {
+            let mut received = Vec::new();
+            let _ = client_reader.read_to_end(&mut received).await;
+            received
+        };
+        let ((), (), received) = tokio::join!(serve, send, receive);
+        received
+    }
+}
+
+/// Parses consecutive responses from `raw`. `methods` holds the request
+/// methods in order, so responses to `HEAD` are known to have no body.
+fn parse_responses(raw: &[u8], methods: &[String]) -> Vec<TestResponse> {
+    let mut responses = Vec::new();
+    let mut rest = raw;
+    while !rest.is_empty() {
+        let head_request = methods
+            .get(responses.len())
+            .is_some_and(|method| method.eq_ignore_ascii_case("HEAD"));
+        let Some((response, remaining)) = parse_response(rest, head_request) else {
+            break;
+        };
+        rest = remaining;
+        // interim responses precede the final one for the same request
+        if (100..200).contains(&response.status) && response.status != 101 {
+            continue;
+        }
+        responses.push(response);
+    }
+    responses
+}
+
+/// Splits one line (without its CRLF) off the front of `raw`.
+fn take_line(raw: &[u8]) -> Option<(&str, &[u8])> {
+    let end = raw.windows(2).position(|window| window == b"\r\n")?;
+    let line = std::str::from_utf8(&raw[..end]).ok()?;
+    Some((line, &raw[end + 2..]))
+}
+
+/// Parses one response from the front of `raw`, returning it and the bytes
+/// after it.
+fn parse_response(raw: &[u8], head_request: bool) -> Option<(TestResponse, &[u8])> {
+    let (status_line, mut rest) = take_line(raw)?;
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts.next()?.to_owned();
+    let status = parts.next()?.parse::<u16>().ok()?;
+    let reason = parts.next().unwrap_or("").to_owned();
+
+    let mut headers = Vec::new();
+    loop {
+        let (line, remaining) = take_line(rest)?;
+        rest = remaining;
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':')?;
+        headers.push((name.trim().to_owned(), value.trim().to_owned()));
+    }
+    let mut response = TestResponse {
+        version,
+        status,
+        reason,
+        headers,
+        body: Vec::new(),
+        trailers: HashMap::new(),
+    };
+
+    let bodyless = head_request || (100..200).contains(&status) || status == 204 || status == 304;
+    let chunked = response
+        .header("transfer-encoding")
+        .is_some_and(|coding| coding.to_ascii_lowercase().contains("chunked"));
+    let length = response
+        .header("content-length")
+        .and_then(|length| length.parse::<usize>().ok());
+    if bodyless {
+        // no body follows
+    } else if chunked {
+        loop {
+            let (size_line, remaining) = take_line(rest)?;
+            let size = size_line.split(';').next()?.trim();
+            let size = usize::from_str_radix(size, 16).ok()?;
+            rest = remaining;
+            if size == 0 {
+                loop {
+                    let (line, remaining) = take_line(rest)?;
+                    rest = remaining;
+                    let Some((name, value)) = line.split_once(':') else {
+                        break;
+                    };
+                    response
+                        .trailers
+                        .insert(name.trim().to_ascii_lowercase(), value.trim().to_owned());
+                }
+                break;
+            }
+            response.body.extend_from_slice(rest.get(..size)?);
+            rest = rest.get(size + 2..)?;
+        }
+    } else if let Some(length) = length {
+        response.body = rest.get(..length)?.to_vec();
+        rest = &rest[length..];
+    } else {
+        // close-delimited: the body is everything the server sent
+        response.body = rest.to_vec();
+        rest = &[];
+    }
+    Some((response, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responders::static_message::StaticResponder;
+
+    fn client() -> TestClient<'static> {
+        TestClient::for_responder(
+            Route::new("GET", "/hello"),
+            StaticResponder::new(200, "hello".to_owned()),
+        )
+    }
+
+    #[tokio::test]
+    async fn pipelined_requests_get_responses_in_order() {
+        let responses = client()
+            .send_all(vec![
+                TestRequest::get("/hello"),
+                TestRequest::get("/missing"),
+                TestRequest::new("HEAD", "/hello"),
+            ])
+            .await;
+        let statuses: Vec<u16> = responses.iter().map(|response| response.status).collect();
+        // the 404 closes the connection, so the HEAD request is never answered
+        assert_eq!(statuses, [200, 404]);
+        assert_eq!(responses[0].body_string(), "hello");
+        assert_eq!(responses[0].version, "HTTP/1.1");
+    }
+
+    #[tokio::test]
+    async fn raw_bytes_reach_the_parser_unchanged() {
+        let responses = client().send_raw(b"GET /hello HTTP/9.9\r\n\r\n").await;
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].status, 505);
+    }
+
+    #[test]
+    fn parses_chunked_bodies_with_trailers_and_skips_interim_responses() {
+        let raw = b"HTTP/1.1 100 Continue\r\n\r\n\
+            HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nSet-Cookie: a=1\r\nSet-Cookie: b=2\r\n\r\n\
+            3\r\nabc\r\n0\r\nDigest: x\r\n\r\n\
+            HTTP/1.1 204 No Content\r\n\r\n";
+        let responses = parse_responses(raw, &[]);
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0].body, b"abc");
+        assert_eq!(responses[0].trailers.get("digest").unwrap(), "x");
+        let cookies: Vec<&str> = responses[0].header_values("set-cookie").collect();
+        assert_eq!(cookies, ["a=1", "b=2"]);
+        assert_eq!(responses[1].status, 204);
+    }
+
+    #[test]
+    fn request_bytes_default_the_host_and_length() {
+        let bytes = TestRequest::post("/echo")
+            .with_header("X-Test", "1")
+            .with_body("hi")
+            .to_bytes();
+        assert_eq!(
+            bytes,
+            b"POST /echo HTTP/1.1\r\nHost: localhost\r\nX-Test: 1\r\nContent-Length: 2\r\n\r\nhi"
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Handing a connection off to another protocol after `101 Switching Protocols`.
+//!
+//! A responder that accepts an `Upgrade` request returns a `101` [`Response`]
+//! carrying an [`OnUpgrade`] callback (see [`Response::with_upgrade`]). The
+//! connection processor writes the `101` response, stops parsing HTTP on that
+//! connection, and passes the raw stream to the callback as an [`Upgraded`].
+//!
+//! [`Response`]: crate::response::Response
+//! [`Response::with_upgrade`]: crate::response::Response::with_upgrade
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+/// The raw connection after a protocol switch.
+///
+/// Bytes the client sent right after its upgrade request that were already
+/// buffered by the HTTP parser are replayed first, so nothing is lost.
+pub struct Upgraded {
+    stream: TcpStream,
+    read_ahead: Vec<u8>,
+    position: usize,
+}
+
+impl Upgraded {
+    pub(crate) fn new(stream: TcpStream, read_ahead: Vec<u8>) -> Upgraded {
+        Upgraded {
+            stream,
+            read_ahead,
+            position: 0,
+        }
+    }
+
+    /// The underlying TCP stream. Reading from it directly skips any
+    /// read-ahead bytes not yet returned through [`AsyncRead`].
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.stream
+    }
+}
+
+impl AsyncRead for Upgraded {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        if this.position < this.read_ahead.len() {
+            let remaining = &this.read_ahead[this.position..];
+            let amount = remaining.len().min(buf.remaining());
+            buf.put_slice(&remaining[..amount]);
+            this.position += amount;
+            if this.position == this.read_ahead.len() {
+                this.read_ahead = Vec::new();
+                this.position = 0;
+            }
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Upgraded {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+/// The future an [`OnUpgrade`] callback returns.
+pub type UpgradeFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A callback that takes over the connection once a `101` response is sent.
+///
+/// The callback runs on the connection's task; the connection is closed when
+/// its future completes.
+pub struct OnUpgrade(Box<dyn FnOnce(Upgraded) -> UpgradeFuture + Send>);
+
+impl OnUpgrade {
+    /// Wraps an async callback receiving the upgraded connection.
+    pub fn new<F, Fut>(callback: F) -> OnUpgrade
+    where
+        F: FnOnce(Upgraded) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        OnUpgrade(Box::new(move |upgraded| Box::pin(callback(upgraded))))
+    }
+
+    pub(crate) fn run(self, upgraded: Upgraded) -> UpgradeFuture {
+        (self.0)(upgraded)
+    }
+}
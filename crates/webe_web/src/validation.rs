@@ -12,3 +12,40 @@ pub type Validation = Option<Box<dyn Any + Send>>;
 /// The outcome of [`crate::responders::Responder::validate`]: either the
 /// forwarded [`Validation`] or a [`Status`] to short-circuit with.
 pub type ValidationResult = Result<Validation, Status>;
+
+/// A validation that refuses the request with `status` and extra response
+/// headers, which a plain [`Status`] error cannot carry.
+///
+/// Returned as the top-level [`Validation`] from a routed responder's
+/// `validate`, it is answered like a validation error: the error page for
+/// `status` is sent (with `headers` added) in place of `100 Continue`, the
+/// request body is never read, and the connection is closed.
+pub struct Refusal {
+    pub(crate) status: u16,
+    pub(crate) headers: Vec<(String, String)>,
+}
+
+impl Refusal {
+    /// Refuses the request with `status`.
+    pub fn new(status: u16) -> Refusal {
+        Refusal {
+            status,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Adds a header to the refusal's response.
+    pub fn with_header(mut self, name: &str, value: &str) -> Refusal {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Takes a [`Refusal`] out of `validation`, handing anything else back.
+    pub(crate) fn from_validation(validation: Validation) -> Result<Refusal, Validation> {
+        match validation.map(|any_box| any_box.downcast::<Refusal>()) {
+            Some(Ok(refusal)) => Ok(*refusal),
+            Some(Err(any_box)) => Err(Some(any_box)),
+            None => Err(None),
+        }
+    }
+}
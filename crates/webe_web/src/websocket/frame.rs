@@ -0,0 +1,239 @@
+//! WebSocket frame encoding and incremental decoding (RFC 6455 §5).
+
+/// A frame opcode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OpCode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl OpCode {
+    fn from_bits(bits: u8) -> Option<OpCode> {
+        match bits {
+            0x0 => Some(OpCode::Continuation),
+            0x1 => Some(OpCode::Text),
+            0x2 => Some(OpCode::Binary),
+            0x8 => Some(OpCode::Close),
+            0x9 => Some(OpCode::Ping),
+            0xA => Some(OpCode::Pong),
+            _ => None,
+        }
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            OpCode::Continuation => 0x0,
+            OpCode::Text => 0x1,
+            OpCode::Binary => 0x2,
+            OpCode::Close => 0x8,
+            OpCode::Ping => 0x9,
+            OpCode::Pong => 0xA,
+        }
+    }
+
+    /// Close, ping, and pong frames are control frames.
+    pub(crate) fn is_control(self) -> bool {
+        matches!(self, OpCode::Close | OpCode::Ping | OpCode::Pong)
+    }
+}
+
+/// A decoded (and unmasked) frame.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Frame {
+    pub(crate) fin: bool,
+    pub(crate) opcode: OpCode,
+    pub(crate) payload: Vec<u8>,
+}
+
+/// Why the bytes received so far cannot be a valid frame.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum FrameError {
+    /// Reserved bits or opcodes, an unmasked client frame, or a malformed
+    /// control frame (close code `1002`).
+    Protocol(&'static str),
+    /// The declared payload is larger than allowed (close code `1009`).
+    TooLarge,
+}
+
+/// Decodes one frame from the front of `buf`.
+///
+/// Returns `Ok(None)` until `buf` holds a complete frame, otherwise the frame
+/// and how many bytes it used. `require_mask` enforces that client frames are
+/// masked; `max_payload` caps the declared payload length before it is buffered.
+pub(crate) fn decode(
+    buf: &[u8],
+    require_mask: bool,
+    max_payload: u64,
+) -> Result<Option<(Frame, usize)>, FrameError> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let fin = buf[0] & 0x80 != 0;
+    if buf[0] & 0x70 != 0 {
+        return Err(FrameError::Protocol(
+            "reserved bits set without an extension",
+        ));
+    }
+    let opcode = OpCode::from_bits(buf[0] & 0x0F).ok_or(FrameError::Protocol("reserved opcode"))?;
+    let masked = buf[1] & 0x80 != 0;
+    if require_mask && !masked {
+        return Err(FrameError::Protocol("client frames must be masked"));
+    }
+
+    let (length, mut offset) = match buf[1] & 0x7F {
+        126 => match buf.get(2..4) {
+            Some(bytes) => (u64::from(u16::from_be_bytes([bytes[0], bytes[1]])), 4),
+            None => return Ok(None),
+        },
+        127 => match buf.get(2..10) {
+            Some(bytes) => {
+                let mut length = [0u8; 8];
+                length.copy_from_slice(bytes);
+                let length = u64::from_be_bytes(length);
+                if length >> 63 != 0 {
+                    return Err(FrameError::Protocol("payload length has its high bit set"));
+                }
+                (length, 10)
+            }
+            None => return Ok(None),
+        },
+        length => (u64::from(length), 2),
+    };
+    if opcode.is_control() && (!fin || length > 125) {
+        return Err(FrameError::Protocol(
+            "control frames must be unfragmented and at most 125 bytes",
+        ));
+    }
+    if length > max_payload {
+        return Err(FrameError::TooLarge);
+    }
+
+    let mask = if masked {
+        match buf.get(offset..offset + 4) {
+            Some(key) => {
+                offset += 4;
+                Some([key[0], key[1], key[2], key[3]])
+            }
+            None => return Ok(None),
+        }
+    } else {
+        None
+    };
+    // length <= max_payload, which the caller keeps within usize
+    let end = offset + length as usize;
+    let Some(payload) = buf.get(offset..end) else {
+        return Ok(None);
+    };
+    let mut payload = payload.to_vec();
+    if let Some(mask) = mask {
+        apply_mask(&mut payload, mask);
+    }
+    Ok(Some((
+        Frame {
+            fin,
+            opcode,
+            payload,
+        },
+        end,
+    )))
+}
+
+/// Encodes a frame, masking the payload with `mask` when given (clients must
+/// mask; servers must not).
+pub(crate) fn encode(fin: bool, opcode: OpCode, payload: &[u8], mask: Option<[u8; 4]>) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(if fin { 0x80 } else { 0 } | opcode.bits());
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        length @ 0..=125 => frame.push(mask_bit | length as u8),
+        length @ 126..=0xFFFF => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(mask) => {
+            frame.extend_from_slice(&mask);
+            let start = frame.len();
+            frame.extend_from_slice(payload);
+            apply_mask(&mut frame[start..], mask);
+        }
+        None => frame.extend_from_slice(payload),
+    }
+    frame
+}
+
+/// XORs `data` with the repeating 4-byte masking key (masking and unmasking are
+/// the same operation).
+pub(crate) fn apply_mask(data: &mut [u8], mask: [u8; 4]) {
+    for (index, byte) in data.iter_mut().enumerate() {
+        *byte ^= mask[index % 4];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masked_frames_round_trip_at_every_length_encoding() {
+        for length in [0usize, 5, 125, 126, 300, 70_000] {
+            let payload: Vec<u8> = (0..length).map(|index| index as u8).collect();
+            let encoded = encode(true, OpCode::Binary, &payload, Some([1, 2, 3, 4]));
+            // every prefix is incomplete
+            assert_eq!(
+                decode(&encoded[..encoded.len() - 1], true, u64::MAX),
+                Ok(None)
+            );
+            let (frame, used) = decode(&encoded, true, u64::MAX).unwrap().unwrap();
+            assert_eq!(used, encoded.len());
+            assert_eq!(frame.payload, payload);
+            assert!(frame.fin);
+        }
+    }
+
+    #[test]
+    fn rfc_example_frames_decode() {
+        // RFC 6455 §5.7: a single-frame masked text message "Hello"
+        let masked = [
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+        ];
+        let (frame, _used) = decode(&masked, true, 125).unwrap().unwrap();
+        assert_eq!(frame.opcode, OpCode::Text);
+        assert_eq!(frame.payload, b"Hello");
+        // and the server's unmasked reply
+        assert_eq!(
+            encode(true, OpCode::Text, b"Hello", None),
+            [0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f]
+        );
+    }
+
+    #[test]
+    fn invalid_frames_are_rejected() {
+        let unmasked = encode(true, OpCode::Text, b"hi", None);
+        assert!(matches!(
+            decode(&unmasked, true, 125),
+            Err(FrameError::Protocol(_))
+        ));
+        let fragmented_ping = encode(false, OpCode::Ping, b"", Some([0; 4]));
+        assert!(matches!(
+            decode(&fragmented_ping, true, 125),
+            Err(FrameError::Protocol(_))
+        ));
+        let reserved = [0xC1, 0x80, 0, 0, 0, 0];
+        assert!(matches!(
+            decode(&reserved, true, 125),
+            Err(FrameError::Protocol(_))
+        ));
+        let big = encode(true, OpCode::Binary, &[0; 200], Some([0; 4]));
+        assert_eq!(decode(&big[..4], true, 100), Err(FrameError::TooLarge));
+    }
+}
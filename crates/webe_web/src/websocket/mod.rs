@@ -0,0 +1,578 @@
+//! The WebSocket protocol (RFC 6455) over an upgraded connection.
+//!
+//! [`crate::responders::websocket::WebSocketResponder`] performs the opening
+//! handshake and hands each connection to application code as a [`WebSocket`],
+//! which is both a [`Stream`] of incoming [`Message`]s and a sink via
+//! [`WebSocket::send`]. Fragmented messages are reassembled, pings are answered
+//! automatically, the closing handshake is completed, and frame and message
+//! sizes are capped by [`WebSocketConfig`].
+//!
+//! A single task drives both directions; to send while waiting for messages,
+//! `tokio::select!` between [`WebSocket::recv`] (which is cancel-safe) and the
+//! outgoing source.
+
+mod frame;
+
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use futures_core::Stream;
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::constants::WEBE_BUFFER_SIZE;
+use crate::upgrade::Upgraded;
+use frame::{FrameError, OpCode};
+
+/// The GUID appended to a client's key to derive `Sec-WebSocket-Accept`.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Computes the `Sec-WebSocket-Accept` value for a client's `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.trim().as_bytes());
+    hasher.update(HANDSHAKE_GUID.as_bytes());
+    BASE64.encode(hasher.finalize())
+}
+
+/// Returns `true` when `key` is a valid `Sec-WebSocket-Key`: the base64
+/// encoding of 16 bytes.
+pub fn is_valid_key(key: &str) -> bool {
+    BASE64
+        .decode(key.trim())
+        .is_ok_and(|decoded| decoded.len() == 16)
+}
+
+/// Size limits for a [`WebSocket`].
+#[derive(Clone, Debug)]
+pub struct WebSocketConfig {
+    /// Largest accepted frame payload, in bytes. Larger frames close the
+    /// connection with `1009 Message Too Big`.
+    pub max_frame_size: usize,
+    /// Largest accepted message after reassembling fragments, in bytes.
+    pub max_message_size: usize,
+}
+
+impl Default for WebSocketConfig {
+    /// 16 MiB frames and 64 MiB messages.
+    fn default() -> Self {
+        WebSocketConfig {
+            max_frame_size: 16 << 20,
+            max_message_size: 64 << 20,
+        }
+    }
+}
+
+/// The status code and reason of a close frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CloseFrame {
+    /// The close status code (e.g. `1000` for a normal closure).
+    pub code: u16,
+    /// A human-readable reason, at most 123 bytes when sent.
+    pub reason: String,
+}
+
+/// A complete WebSocket message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// A UTF-8 text message.
+    Text(String),
+    /// A binary message.
+    Binary(Vec<u8>),
+    /// A ping. Received pings are answered with a pong automatically.
+    Ping(Vec<u8>),
+    /// A pong.
+    Pong(Vec<u8>),
+    /// A close frame, optionally with a status code and reason. A received close
+    /// is answered automatically and is the last message of the stream.
+    Close(Option<CloseFrame>),
+}
+
+/// Why a WebSocket connection failed.
+#[derive(Debug)]
+pub enum WebSocketError {
+    /// Reading from or writing to the connection failed.
+    Io(std::io::Error),
+    /// The peer violated the protocol; the connection was closed with `1002`.
+    Protocol(&'static str),
+    /// A frame or message exceeded [`WebSocketConfig`]; closed with `1009`.
+    MessageTooLarge,
+    /// A text message or close reason was not UTF-8; closed with `1007`.
+    InvalidUtf8,
+    /// A message was sent after the connection started closing.
+    Closed,
+}
+
+impl std::fmt::Display for WebSocketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebSocketError::Io(error) => write!(f, "websocket: connection failed: {error}"),
+            WebSocketError::Protocol(reason) => {
+                write!(f, "websocket: protocol violation: {reason} (1002)")
+            }
+            WebSocketError::MessageTooLarge => {
+                write!(
+                    f,
+                    "websocket: frame or message exceeds the size limit (1009)"
+                )
+            }
+            WebSocketError::InvalidUtf8 => write!(f, "websocket: text is not valid UTF-8 (1007)"),
+            WebSocketError::Closed => write!(f, "websocket: the connection is closing"),
+        }
+    }
+}
+
+impl std::error::Error for WebSocketError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Open,
+    /// We sent a close frame and are waiting for the peer's.
+    CloseSent,
+    /// A close frame is queued; the stream ends once it is written.
+    Closing,
+    Closed,
+}
+
+/// The server side of a WebSocket connection.
+///
+/// Incoming messages are read as a [`Stream`] (or with [`WebSocket::recv`]);
+/// outgoing ones are written with [`WebSocket::send`]. The stream ends after the
+/// closing handshake, a protocol error, or a disconnect.
+pub struct WebSocket<S = Upgraded> {
+    stream: S,
+    config: WebSocketConfig,
+    protocol: Option<String>,
+    read_buf: Vec<u8>,
+    /// Control frames (pongs, close replies) waiting to be written.
+    outgoing: Vec<u8>,
+    fragments: Option<(OpCode, Vec<u8>)>,
+    state: State,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> WebSocket<S> {
+    /// Wraps a connection on which the opening handshake has completed.
+    pub fn from_stream(stream: S, config: WebSocketConfig) -> WebSocket<S> {
+        WebSocket {
+            stream,
+            config,
+            protocol: None,
+            read_buf: Vec::new(),
+            outgoing: Vec::new(),
+            fragments: None,
+            state: State::Open,
+        }
+    }
+
+    pub(crate) fn with_protocol(mut self, protocol: Option<String>) -> WebSocket<S> {
+        self.protocol = protocol;
+        self
+    }
+
+    /// The subprotocol agreed during the handshake, if any.
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    /// Waits for the next message. Returns `None` once the connection is closed.
+    ///
+    /// Cancel-safe: dropping the future loses no data.
+    pub async fn recv(&mut self) -> Option<Result<Message, WebSocketError>> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+
+    /// Sends a message as a single frame.
+    ///
+    /// Sending [`Message::Close`] starts the closing handshake; keep reading
+    /// until the stream ends to receive the peer's reply. Returns
+    /// [`WebSocketError::Closed`] once closing has started, and
+    /// [`WebSocketError::Protocol`] for a ping or pong payload over 125 bytes.
+    pub async fn send(&mut self, message: Message) -> Result<(), WebSocketError> {
+        if self.state != State::Open {
+            return Err(WebSocketError::Closed);
+        }
+        let frame = match &message {
+            Message::Text(text) => frame::encode(true, OpCode::Text, text.as_bytes(), None),
+            Message::Binary(data) => frame::encode(true, OpCode::Binary, data, None),
+            Message::Ping(data) | Message::Pong(data) if data.len() > 125 => {
+                return Err(WebSocketError::Protocol(
+                    "control frame payloads are limited to 125 bytes",
+                ));
+            }
+            Message::Ping(data) => frame::encode(true, OpCode::Ping, data, None),
+            Message::Pong(data) => frame::encode(true, OpCode::Pong, data, None),
+            Message::Close(close) => {
+                frame::encode(true, OpCode::Close, &close_payload(close.as_ref()), None)
+            }
+        };
+        if matches!(message, Message::Close(_)) {
+            self.state = State::CloseSent;
+        }
+        let queued = std::mem::take(&mut self.outgoing);
+        self.stream
+            .write_all(&queued)
+            .await
+            .map_err(WebSocketError::Io)?;
+        self.stream
+            .write_all(&frame)
+            .await
+            .map_err(WebSocketError::Io)?;
+        self.stream.flush().await.map_err(WebSocketError::Io)
+    }
+
+    /// Starts the closing handshake with `code` and `reason`.
+    pub async fn close(&mut self, code: u16, reason: &str) -> Result<(), WebSocketError> {
+        self.send(Message::Close(Some(CloseFrame {
+            code,
+            reason: reason.to_owned(),
+        })))
+        .await
+    }
+
+    /// Queues a close frame (if one is still owed), drops any partial message,
+    /// and reports `error` as the stream's last item.
+    fn fail(
+        &mut self,
+        code: u16,
+        error: WebSocketError,
+    ) -> Option<Result<Message, WebSocketError>> {
+        self.fragments = None;
+        self.read_buf.clear();
+        if self.state == State::Open {
+            let close = CloseFrame {
+                code,
+                reason: String::new(),
+            };
+            self.outgoing.extend(frame::encode(
+                true,
+                OpCode::Close,
+                &close_payload(Some(&close)),
+                None,
+            ));
+            self.state = State::Closing;
+        } else {
+            self.state = State::Closed;
+        }
+        Some(Err(error))
+    }
+
+    /// Applies one frame, returning a message when one is complete.
+    fn handle(&mut self, frame: frame::Frame) -> Option<Result<Message, WebSocketError>> {
+        match frame.opcode {
+            OpCode::Continuation => {
+                let Some((opcode, mut data)) = self.fragments.take() else {
+                    return self.fail(
+                        1002,
+                        WebSocketError::Protocol("continuation without a message to continue"),
+                    );
+                };
+                if data.len() + frame.payload.len() > self.config.max_message_size {
+                    return self.fail(1009, WebSocketError::MessageTooLarge);
+                }
+                data.extend(frame.payload);
+                if frame.fin {
+                    self.finish(opcode, data)
+                } else {
+                    self.fragments = Some((opcode, data));
+                    None
+                }
+            }
+            OpCode::Text | OpCode::Binary => {
+                if self.fragments.is_some() {
+                    return self.fail(
+                        1002,
+                        WebSocketError::Protocol("new message before the fragmented one ended"),
+                    );
+                }
+                if frame.payload.len() > self.config.max_message_size {
+                    return self.fail(1009, WebSocketError::MessageTooLarge);
+                }
+                if frame.fin {
+                    self.finish(frame.opcode, frame.payload)
+                } else {
+                    self.fragments = Some((frame.opcode, frame.payload));
+                    None
+                }
+            }
+            OpCode::Ping => {
+                if self.state == State::Open {
+                    self.outgoing
+                        .extend(frame::encode(true, OpCode::Pong, &frame.payload, None));
+                }
+                Some(Ok(Message::Ping(frame.payload)))
+            }
+            OpCode::Pong => Some(Ok(Message::Pong(frame.payload))),
+            OpCode::Close => {
+                let close = match parse_close(&frame.payload) {
+                    Ok(close) => close,
+                    Err(error) => {
+                        let code = match error {
+                            WebSocketError::InvalidUtf8 => 1007,
+                            _ => 1002,
+                        };
+                        return self.fail(code, error);
+                    }
+                };
+                if self.state == State::Open {
+                    // echo the status code back, completing the handshake
+                    let reply = close.as_ref().map(|close| CloseFrame {
+                        code: close.code,
+                        reason: String::new(),
+                    });
+                    self.outgoing.extend(frame::encode(
+                        true,
+                        OpCode::Close,
+                        &close_payload(reply.as_ref()),
+                        None,
+                    ));
+                    self.state = State::Closing;
+                } else {
+                    self.state = State::Closed;
+                }
+                Some(Ok(Message::Close(close)))
+            }
+        }
+    }
+
+    fn finish(&mut self, opcode: OpCode, data: Vec<u8>) -> Option<Result<Message, WebSocketError>> {
+        match opcode {
+            OpCode::Text => match String::from_utf8(data) {
+                Ok(text) => Some(Ok(Message::Text(text))),
+                Err(_error) => self.fail(1007, WebSocketError::InvalidUtf8),
+            },
+            _ => Some(Ok(Message::Binary(data))),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Stream for WebSocket<S> {
+    type Item = Result<Message, WebSocketError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            // write queued control frames first; only a pending close holds up
+            // reading
+            while !this.outgoing.is_empty() {
+                match Pin::new(&mut this.stream).poll_write(cx, &this.outgoing) {
+                    Poll::Ready(Ok(0)) => {
+                        this.outgoing.clear();
+                        this.state = State::Closed;
+                        return Poll::Ready(Some(Err(WebSocketError::Io(
+                            std::io::ErrorKind::WriteZero.into(),
+                        ))));
+                    }
+                    Poll::Ready(Ok(written)) => {
+                        this.outgoing.drain(..written);
+                    }
+                    Poll::Ready(Err(error)) => {
+                        this.outgoing.clear();
+                        this.state = State::Closed;
+                        return Poll::Ready(Some(Err(WebSocketError::Io(error))));
+                    }
+                    Poll::Pending if this.state == State::Closing => return Poll::Pending,
+                    Poll::Pending => break,
+                }
+            }
+            if this.outgoing.is_empty() {
+                let _ = Pin::new(&mut this.stream).poll_flush(cx);
+                if this.state == State::Closing {
+                    this.state = State::Closed;
+                }
+            }
+            if this.state == State::Closed {
+                return Poll::Ready(None);
+            }
+
+            match frame::decode(&this.read_buf, true, this.config.max_frame_size as u64) {
+                Ok(Some((frame, used))) => {
+                    this.read_buf.drain(..used);
+                    if let Some(item) = this.handle(frame) {
+                        return Poll::Ready(Some(item));
+                    }
+                    continue;
+                }
+                Ok(None) => {}
+                Err(FrameError::Protocol(reason)) => {
+                    return Poll::Ready(this.fail(1002, WebSocketError::Protocol(reason)));
+                }
+                Err(FrameError::TooLarge) => {
+                    return Poll::Ready(this.fail(1009, WebSocketError::MessageTooLarge));
+                }
+            }
+
+            let mut chunk = [0u8; WEBE_BUFFER_SIZE];
+            let mut read_buf = ReadBuf::new(&mut chunk);
+            match Pin::new(&mut this.stream).poll_read(cx, &mut read_buf) {
+                Poll::Ready(Ok(())) if read_buf.filled().is_empty() => {
+                    // the peer went away without (finishing) the closing handshake
+                    this.state = State::Closed;
+                    return Poll::Ready(None);
+                }
+                Poll::Ready(Ok(())) => this.read_buf.extend_from_slice(read_buf.filled()),
+                Poll::Ready(Err(error)) => {
+                    this.state = State::Closed;
+                    return Poll::Ready(Some(Err(WebSocketError::Io(error))));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Serializes a close frame's payload; the reason is cut to fit 125 bytes.
+fn close_payload(close: Option<&CloseFrame>) -> Vec<u8> {
+    let Some(close) = close else {
+        return Vec::new();
+    };
+    let mut payload = close.code.to_be_bytes().to_vec();
+    let mut end = close.reason.len().min(123);
+    while !close.reason.is_char_boundary(end) {
+        end -= 1;
+    }
+    payload.extend_from_slice(&close.reason.as_bytes()[..end]);
+    payload
+}
+
+/// Parses and validates a received close frame's payload.
+fn parse_close(payload: &[u8]) -> Result<Option<CloseFrame>, WebSocketError> {
+    match payload {
+        [] => Ok(None),
+        [_] => Err(WebSocketError::Protocol("close payload of one byte")),
+        [high, low, reason @ ..] => {
+            let code = u16::from_be_bytes([*high, *low]);
+            let allowed = matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999);
+            if !allowed {
+                return Err(WebSocketError::Protocol("invalid close code"));
+            }
+            let reason = std::str::from_utf8(reason).map_err(|_| WebSocketError::InvalidUtf8)?;
+            Ok(Some(CloseFrame {
+                code,
+                reason: reason.to_owned(),
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, DuplexStream, duplex};
+
+    const MASK: Option<[u8; 4]> = Some([0x11, 0x22, 0x33, 0x44]);
+
+    fn socket(config: WebSocketConfig) -> (WebSocket<DuplexStream>, DuplexStream) {
+        let (server, client) = duplex(1 << 20);
+        (WebSocket::from_stream(server, config), client)
+    }
+
+    async fn read_frame(client: &mut DuplexStream) -> frame::Frame {
+        let mut buf = Vec::new();
+        loop {
+            if let Some((frame, _used)) = frame::decode(&buf, false, u64::MAX).unwrap() {
+                return frame;
+            }
+            let mut byte = [0u8; 1];
+            client.read_exact(&mut byte).await.unwrap();
+            buf.push(byte[0]);
+        }
+    }
+
+    #[test]
+    fn accept_key_matches_the_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert!(is_valid_key("dGhlIHNhbXBsZSBub25jZQ=="));
+        assert!(!is_valid_key("c2hvcnQ="));
+    }
+
+    #[tokio::test]
+    async fn fragments_are_reassembled_around_control_frames() {
+        let (mut socket, mut client) = socket(WebSocketConfig::default());
+        let mut wire = frame::encode(false, OpCode::Text, b"Hel", MASK);
+        wire.extend(frame::encode(true, OpCode::Ping, b"?", MASK));
+        wire.extend(frame::encode(true, OpCode::Continuation, b"lo", MASK));
+        client.write_all(&wire).await.unwrap();
+
+        assert_eq!(
+            socket.recv().await.unwrap().unwrap(),
+            Message::Ping(b"?".to_vec())
+        );
+        assert_eq!(
+            socket.recv().await.unwrap().unwrap(),
+            Message::Text("Hello".to_owned())
+        );
+        // the ping was answered before the next read completed
+        let pong = read_frame(&mut client).await;
+        assert_eq!((pong.opcode, pong.payload), (OpCode::Pong, b"?".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn client_close_is_echoed_and_ends_the_stream() {
+        let (mut socket, mut client) = socket(WebSocketConfig::default());
+        socket.send(Message::Text("hi".to_owned())).await.unwrap();
+        let text = read_frame(&mut client).await;
+        assert_eq!(text.payload, b"hi");
+
+        let close = close_payload(Some(&CloseFrame {
+            code: 1000,
+            reason: "bye".to_owned(),
+        }));
+        client
+            .write_all(&frame::encode(true, OpCode::Close, &close, MASK))
+            .await
+            .unwrap();
+        assert_eq!(
+            socket.recv().await.unwrap().unwrap(),
+            Message::Close(Some(CloseFrame {
+                code: 1000,
+                reason: "bye".to_owned()
+            }))
+        );
+        assert!(socket.recv().await.is_none());
+        let reply = read_frame(&mut client).await;
+        assert_eq!(
+            (reply.opcode, reply.payload),
+            (OpCode::Close, vec![0x03, 0xE8])
+        );
+        assert!(matches!(
+            socket.send(Message::Text("late".to_owned())).await,
+            Err(WebSocketError::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn violations_close_with_the_matching_code() {
+        let config = WebSocketConfig {
+            max_frame_size: 1024,
+            max_message_size: 8,
+        };
+        let cases: [(Vec<u8>, u16); 4] = [
+            (frame::encode(true, OpCode::Text, b"\xFF", MASK), 1007),
+            (frame::encode(true, OpCode::Text, b"unmasked", None), 1002),
+            (
+                [
+                    frame::encode(false, OpCode::Binary, b"12345", MASK),
+                    frame::encode(true, OpCode::Continuation, b"6789", MASK),
+                ]
+                .concat(),
+                1009,
+            ),
+            (frame::encode(true, OpCode::Continuation, b"x", MASK), 1002),
+        ];
+        for (wire, code) in cases {
+            let (mut socket, mut client) = socket(config.clone());
+            client.write_all(&wire).await.unwrap();
+            assert!(socket.recv().await.unwrap().is_err());
+            assert!(socket.recv().await.is_none());
+            let close = read_frame(&mut client).await;
+            assert_eq!(close.opcode, OpCode::Close);
+            assert_eq!(close.payload[..2], code.to_be_bytes(), "code {code}");
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Integration tests for the access log: one line per answered request, in
+//! each format, including failed requests and the client's address.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::{LabelResponder, TestClient, spawn_server_with_config};
+use webe_log::{LogLevel, Sink};
+use webe_web::access_log::{AccessLog, AccessLogFormat};
+use webe_web::config::ServerConfig;
+use webe_web::server::{Route, RouteMap};
+
+/// Collects every line written to it.
+#[derive(Clone, Default)]
+struct MemorySink(Arc<Mutex<Vec<String>>>);
+
+impl Sink for MemorySink {
+    fn write(&mut self, level: &LogLevel, msg: &str) {
+        self.0.lock().unwrap().push(format!("{level:?} {msg}"));
+    }
+}
+
+impl MemorySink {
+    /// Waits for `count` lines; a line is written just after its response.
+    async fn lines(&self, count: usize) -> Vec<String> {
+        for _ in 0..200 {
+            let lines = self.0.lock().unwrap().clone();
+            if lines.len() >= count {
+                return lines;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("expected {count} lines, got {:?}", self.0.lock().unwrap());
+    }
+}
+
+async fn logged_server(format: AccessLogFormat) -> (std::net::SocketAddr, MemorySink) {
+    let sink = MemorySink::default();
+    let mut routes = RouteMap::new();
+    routes.add_route(Route::new("GET", "/hello"), LabelResponder::new("hello"));
+    let config = ServerConfig::new().with_access_log(AccessLog::new(sink.clone(), format));
+    (spawn_server_with_config(routes, config).await, sink)
+}
+
+#[tokio::test]
+async fn combined_lines_record_each_request_on_a_connection() {
+    let (addr, sink) = logged_server(AccessLogFormat::Combined).await;
+
+    let mut client = TestClient::connect(addr).await;
+    client
+        .send(b"GET /hello HTTP/1.1\r\nUser-Agent: probe/1.0\r\nReferer: http://a/\r\n\r\n")
+        .await;
+    assert_eq!(client.recv().await.status, 200);
+    client.send(b"GET /missing HTTP/1.1\r\n\r\n").await;
+    assert_eq!(client.recv().await.status, 404);
+
+    let lines = sink.lines(2).await;
+    assert!(lines[0].starts_with("INFO 127.0.0.1 - - ["), "{}", lines[0]);
+    assert!(
+        lines[0].ends_with("] \"GET /hello HTTP/1.1\" 200 5 \"http://a/\" \"probe/1.0\""),
+        "{}",
+        lines[0]
+    );
+    assert!(
+        lines[1].contains("\"GET /missing HTTP/1.1\" 404 ") && lines[1].ends_with(" \"-\" \"-\""),
+        "{}",
+        lines[1]
+    );
+}
+
+#[tokio::test]
+async fn json_lines_include_the_peer_and_malformed_requests() {
+    let (addr, sink) = logged_server(AccessLogFormat::Json).await;
+
+    let ok = TestClient::request(addr, b"GET /hello HTTP/1.1\r\nConnection: close\r\n\r\n").await;
+    assert_eq!(ok.status, 200);
+    let malformed = TestClient::request(addr, b"NONSENSE\r\n\r\n").await;
+    assert_eq!(malformed.status, 400);
+
+    let lines = sink.lines(2).await;
+    let entries: Vec<serde_json::Value> = lines
+        .iter()
+        .map(|line| serde_json::from_str(line.strip_prefix("INFO ").unwrap()).unwrap())
+        .collect();
+    assert_eq!(entries[0]["method"], "GET");
+    assert_eq!(entries[0]["path"], "/hello");
+    assert_eq!(entries[0]["status"], 200);
+    assert_eq!(entries[0]["bytes"], 5);
+    assert!(entries[0]["duration_ms"].as_f64().unwrap() >= 0.0);
+    assert!(
+        entries[0]["peer"]
+            .as_str()
+            .unwrap()
+            .starts_with("127.0.0.1:")
+    );
+    assert_eq!(entries[1]["status"], 400);
+    assert!(entries[1]["method"].is_null());
+}
+
+#[cfg(feature = "http2")]
+#[tokio::test]
+async fn http2_streams_are_logged_with_their_body_size() {
+    let (addr, sink) = logged_server(AccessLogFormat::Common).await;
+
+    let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let (client, connection) = h2::client::handshake(tcp).await.unwrap();
+    tokio::spawn(async move {
+        let _ = connection.await;
+    });
+    let request = http::Request::builder()
+        .uri("http://test/hello")
+        .body(())
+        .unwrap();
+    let mut client = client.ready().await.unwrap();
+    let (response, _) = client.send_request(request, true).unwrap();
+    let mut body = response.await.unwrap().into_body();
+    while let Some(data) = body.data().await {
+        data.unwrap();
+    }
+
+    let lines = sink.lines(1).await;
+    assert!(
+        lines[0].ends_with("\"GET /hello HTTP/2.0\" 200 5"),
+        "{}",
+        lines[0]
+    );
+}
@@ -2,15 +2,72 @@
 
 mod common;
 
-use common::{EchoBodyResponder, TestClient, spawn_server};
+use async_compression::tokio::bufread::{BrotliEncoder, GzipEncoder};
+use tokio::io::AsyncReadExt;
+
+use common::{
+    EchoBodyResponder, LabelResponder, TestClient, spawn_server, spawn_server_with_config,
+};
+use webe_web::config::ServerConfig;
+use webe_web::encoding::decompress::DecodingLimits;
 use webe_web::server::{Route, RouteMap};
 
 fn echo_routes() -> RouteMap<'static> {
     let mut map = RouteMap::new();
     map.add_route(Route::new("POST", "/echo"), EchoBodyResponder);
+    // never reads the request body
+    map.add_route(
+        Route::new("POST", "/ignore"),
+        LabelResponder::new("ignored"),
+    );
     map
 }
 
+/// Sends `first` (to `/ignore`) and an `/echo` request in one write, then
+/// checks both responses arrive intact on the same connection.
+async fn assert_pipelined_after(first: &[u8]) {
+    let addr = spawn_server(echo_routes()).await;
+    let mut client = TestClient::connect(addr).await;
+    let mut raw = first.to_vec();
+    raw.extend_from_slice(b"POST /echo HTTP/1.1\r\nContent-Length: 4\r\n\r\nnext");
+    client.send(&raw).await;
+
+    let ignored = client.recv().await;
+    assert_eq!(ignored.status, 200);
+    assert_eq!(ignored.body_string(), "ignored");
+    assert_eq!(
+        ignored.header("Connection"),
+        Some(&"keep-alive".to_string())
+    );
+    let echoed = client.recv().await;
+    assert_eq!(echoed.status, 200);
+    assert_eq!(echoed.body_string(), "next");
+}
+
+fn decoding_config() -> ServerConfig {
+    ServerConfig::new().with_request_decoding(DecodingLimits::default())
+}
+
+async fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    GzipEncoder::new(data)
+        .read_to_end(&mut encoded)
+        .await
+        .expect("gzip encode");
+    encoded
+}
+
+/// Builds a request for `/echo` with `headers` and a `Content-Length` body.
+fn post_echo(headers: &str, body: &[u8]) -> Vec<u8> {
+    let mut raw = format!(
+        "POST /echo HTTP/1.1\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    raw.extend_from_slice(body);
+    raw
+}
+
 #[tokio::test]
 async fn content_length_body_is_read_exactly() {
     let addr = spawn_server(echo_routes()).await;
@@ -35,6 +92,23 @@ async fn chunked_body_is_decoded() {
     assert_eq!(response.body_string(), "hello");
 }
 
+#[tokio::test]
+async fn chunked_extensions_and_trailers_reach_the_request() {
+    let addr = spawn_server(echo_routes()).await;
+    let response = TestClient::request(
+        addr,
+        b"POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+          3;ext=\"a;b\"\r\nhel\r\n2\r\nlo\r\n0\r\nDigest: sha-256=abc\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "hello");
+    assert_eq!(
+        response.header("Trailer-digest"),
+        Some(&"sha-256=abc".to_string())
+    );
+}
+
 #[tokio::test]
 async fn both_framing_headers_present_is_bad_request() {
     let addr = spawn_server(echo_routes()).await;
@@ -70,3 +144,121 @@ async fn unsupported_transfer_coding_is_rejected() {
     assert_eq!(response.status, 400);
     assert_ne!(response.body_string(), "hello");
 }
+
+// ---------- opt-in request decompression ----------
+
+#[tokio::test]
+async fn gzip_content_encoding_is_decoded_when_enabled() {
+    let addr = spawn_server_with_config(echo_routes(), decoding_config()).await;
+    let body = gzip(b"{\"compressed\":true}").await;
+    let response = TestClient::request(addr, &post_echo("Content-Encoding: gzip\r\n", &body)).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "{\"compressed\":true}");
+}
+
+#[tokio::test]
+async fn brotli_content_encoding_is_decoded_when_enabled() {
+    let addr = spawn_server_with_config(echo_routes(), decoding_config()).await;
+    let mut body = Vec::new();
+    BrotliEncoder::new(&b"brotli body"[..])
+        .read_to_end(&mut body)
+        .await
+        .expect("brotli encode");
+    let response = TestClient::request(addr, &post_echo("Content-Encoding: br\r\n", &body)).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "brotli body");
+}
+
+#[tokio::test]
+async fn gzip_content_encoding_is_passed_through_by_default() {
+    let addr = spawn_server(echo_routes()).await;
+    let body = gzip(b"left alone").await;
+    let response = TestClient::request(addr, &post_echo("Content-Encoding: gzip\r\n", &body)).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, body);
+}
+
+#[tokio::test]
+async fn gzip_transfer_coding_under_chunked_is_decoded_when_enabled() {
+    let addr = spawn_server_with_config(echo_routes(), decoding_config()).await;
+    let body = gzip(b"chunked and gzipped").await;
+    let mut raw =
+        b"POST /echo HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\nConnection: close\r\n\r\n"
+            .to_vec();
+    raw.extend_from_slice(format!("{:X}\r\n", body.len()).as_bytes());
+    raw.extend_from_slice(&body);
+    raw.extend_from_slice(b"\r\n0\r\n\r\n");
+    let response = TestClient::request(addr, &raw).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "chunked and gzipped");
+}
+
+#[tokio::test]
+async fn unsupported_content_encoding_is_unsupported_media_type() {
+    let addr = spawn_server_with_config(echo_routes(), decoding_config()).await;
+    let response =
+        TestClient::request(addr, &post_echo("Content-Encoding: zstd\r\n", b"abc")).await;
+    assert_eq!(response.status, 415);
+}
+
+#[tokio::test]
+async fn decompression_bomb_fails_the_body_read() {
+    let config = ServerConfig::new().with_request_decoding(DecodingLimits {
+        max_decoded_size: 1024,
+        ..DecodingLimits::default()
+    });
+    let addr = spawn_server_with_config(echo_routes(), config).await;
+    let body = gzip(&vec![b'a'; 256 * 1024]).await;
+    let response = TestClient::request(addr, &post_echo("Content-Encoding: gzip\r\n", &body)).await;
+    // the echo responder surfaces the failed read as a 500; no decoded bytes leak
+    assert_eq!(response.status, 500);
+}
+
+#[tokio::test]
+async fn unknown_expectation_is_expectation_failed() {
+    let addr = spawn_server(echo_routes()).await;
+    let response = TestClient::request(addr, &post_echo("Expect: 200-ok\r\n", b"hello")).await;
+    assert_eq!(response.status, 417);
+}
+
+#[tokio::test]
+async fn expect_continue_is_not_sent_for_unmatched_routes() {
+    let addr = spawn_server(echo_routes()).await;
+    let response = TestClient::request(
+        addr,
+        b"POST /missing HTTP/1.1\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 404);
+}
+
+#[tokio::test]
+async fn pipelined_request_follows_an_unread_length_body() {
+    assert_pipelined_after(b"POST /ignore HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello").await;
+}
+
+#[tokio::test]
+async fn pipelined_request_follows_an_unread_chunked_body() {
+    assert_pipelined_after(
+        b"POST /ignore HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\nX-Trailer: yes\r\n\r\n",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn pipelined_request_follows_a_bodyless_request() {
+    assert_pipelined_after(b"POST /ignore HTTP/1.1\r\n\r\n").await;
+}
+
+#[tokio::test]
+async fn unread_body_over_the_drain_limit_closes_the_connection() {
+    let config = ServerConfig::new().with_drain_limit(4);
+    let addr = spawn_server_with_config(echo_routes(), config).await;
+    let mut client = TestClient::connect(addr).await;
+    client
+        .send(b"POST /ignore HTTP/1.1\r\nContent-Length: 10\r\n\r\n0123456789POST /echo HTTP/1.1\r\nContent-Length: 0\r\n\r\n")
+        .await;
+    let ignored = client.recv().await;
+    assert_eq!(ignored.status, 200);
+    assert_eq!(ignored.header("Connection"), Some(&"close".to_string()));
+}
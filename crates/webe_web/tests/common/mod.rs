@@ -13,6 +13,7 @@ use std::net::{Ipv4Addr, SocketAddr};
 use async_trait::async_trait;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
+use webe_web::config::ServerConfig;
 use webe_web::request::Request;
 use webe_web::responders::Responder;
 use webe_web::response::Response;
@@ -22,11 +23,20 @@ use webe_web::validation::Validation;
 /// Binds a server on `127.0.0.1:0`, starts it on a background task, and returns
 /// the OS-assigned address to connect to.
 pub async fn spawn_server(routes: RouteMap<'static>) -> SocketAddr {
+    spawn_server_with_config(routes, ServerConfig::default()).await
+}
+
+/// Like [`spawn_server`], but runs the server with `config`.
+pub async fn spawn_server_with_config(
+    routes: RouteMap<'static>,
+    config: ServerConfig,
+) -> SocketAddr {
     let ip = Ipv4Addr::new(127, 0, 0, 1);
     let port: u16 = 0;
     let server = Server::new(&ip, &port)
         .await
-        .expect("server should bind on an ephemeral port");
+        .expect("server should bind on an ephemeral port")
+        .with_config(config);
     let addr = server
         .local_addr()
         .expect("server should report its local address");
@@ -41,7 +51,11 @@ pub async fn spawn_server(routes: RouteMap<'static>) -> SocketAddr {
 pub struct TestResponse {
     pub status: u16,
     pub headers: HashMap<String, String>,
+    /// Every header line in order, including repeated names.
+    pub header_lines: Vec<(String, String)>,
     pub body: Vec<u8>,
+    /// Trailer fields after a chunked body, names lowercased.
+    pub trailers: HashMap<String, String>,
 }
 
 impl TestResponse {
@@ -55,6 +69,14 @@ impl TestResponse {
             .find(|k| k.eq_ignore_ascii_case(name))
             .and_then(|k| self.headers.get(k))
     }
+
+    pub fn header_values(&self, name: &str) -> Vec<&str> {
+        self.header_lines
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
 }
 
 /// A minimal pipelining HTTP client over one TCP connection.
@@ -96,6 +118,7 @@ impl TestClient {
             .expect("response status line should contain a status code");
 
         let mut headers = HashMap::new();
+        let mut header_lines = Vec::new();
         loop {
             let line = self.read_line().await;
             let trimmed = line.trim_end_matches(['\r', '\n']);
@@ -104,18 +127,24 @@ impl TestClient {
             }
             if let Some((name, value)) = trimmed.split_once(':') {
                 headers.insert(name.trim().to_string(), value.trim().to_string());
+                header_lines.push((name.trim().to_string(), value.trim().to_string()));
             }
         }
 
-        let body = self.read_body(&headers).await;
+        let (body, trailers) = self.read_body(&headers).await;
         TestResponse {
             status,
             headers,
+            header_lines,
             body,
+            trailers,
         }
     }
 
-    async fn read_body(&mut self, headers: &HashMap<String, String>) -> Vec<u8> {
+    async fn read_body(
+        &mut self,
+        headers: &HashMap<String, String>,
+    ) -> (Vec<u8>, HashMap<String, String>) {
         let lookup = |name: &str| {
             headers
                 .iter()
@@ -135,19 +164,28 @@ impl TestClient {
                 .read_exact(&mut buf)
                 .await
                 .expect("client should read the full content-length body");
-            return buf;
+            return (buf, HashMap::new());
         }
-        Vec::new()
+        (Vec::new(), HashMap::new())
     }
 
-    async fn read_chunked_body(&mut self) -> Vec<u8> {
+    async fn read_chunked_body(&mut self) -> (Vec<u8>, HashMap<String, String>) {
         let mut body = Vec::new();
+        let mut trailers = HashMap::new();
         loop {
             let size_line = self.read_line().await;
             let size = usize::from_str_radix(size_line.trim_end_matches(['\r', '\n']), 16)
                 .expect("chunk size should be valid hex");
             if size == 0 {
-                let _ = self.read_line().await; // trailing CRLF after the final chunk
+                // trailer fields, up to the CRLF ending the message
+                loop {
+                    let line = self.read_line().await;
+                    let line = line.trim_end_matches(['\r', '\n']);
+                    let Some((name, value)) = line.split_once(':') else {
+                        break;
+                    };
+                    trailers.insert(name.trim().to_lowercase(), value.trim().to_string());
+                }
                 break;
             }
             let mut chunk = vec![0u8; size];
@@ -158,7 +196,7 @@ impl TestClient {
             body.extend_from_slice(&chunk);
             let _ = self.read_line().await; // CRLF after the chunk data
         }
-        body
+        (body, trailers)
     }
 
     async fn read_line(&mut self) -> String {
@@ -240,6 +278,7 @@ impl Responder for StreamResponder {
 
 /// A responder that reads the request body to end and echoes it back with a
 /// `Content-Length`, so tests can verify the body was framed and delivered.
+/// Request trailers come back as `Trailer-<name>` headers.
 pub struct EchoBodyResponder;
 
 #[async_trait]
@@ -260,6 +299,11 @@ impl Responder for EchoBodyResponder {
         response
             .headers
             .insert("Content-Length".to_owned(), buf.len().to_string());
+        for (name, value) in request.trailers.get().into_iter().flatten() {
+            response
+                .headers
+                .insert(format!("Trailer-{name}"), value.clone());
+        }
         response.message_body = Some(Box::pin(Cursor::new(buf)));
         Ok(response)
     }
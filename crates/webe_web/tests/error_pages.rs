@@ -0,0 +1,124 @@
+//! Integration tests for application error handlers: per-status handlers, the
+//! catch-all, declining to the default page, and the request details handed
+//! to them.
+
+mod common;
+
+use std::io::Cursor;
+
+use common::LabelResponder;
+use webe_web::config::ServerConfig;
+use webe_web::error::WebError;
+use webe_web::error_pages::ErrorContext;
+use webe_web::response::Response;
+use webe_web::route::RoutingError;
+use webe_web::server::{Route, RouteMap};
+use webe_web::testing::{TestClient, TestRequest};
+
+fn body_response(status: u16, content_type: &str, body: String) -> Response {
+    let mut response = Response::new(status);
+    response
+        .headers
+        .insert("Content-Type".to_owned(), content_type.to_owned());
+    response
+        .headers
+        .insert("Content-Length".to_owned(), body.len().to_string());
+    response.message_body = Some(Box::pin(Cursor::new(body.into_bytes())));
+    response
+}
+
+/// `application/problem+json` for failures under `/api`; declines elsewhere.
+fn problem_json(context: &ErrorContext) -> Option<Response> {
+    let uri = context.uri.as_deref()?;
+    if !uri.starts_with("/api") {
+        return None;
+    }
+    let body = format!(
+        r#"{{"status":{},"instance":"{}","detail":"{}"}}"#,
+        context.status, uri, context.error
+    );
+    Some(body_response(
+        context.status,
+        "application/problem+json",
+        body,
+    ))
+}
+
+fn client(config: ServerConfig) -> TestClient<'static> {
+    let mut routes = RouteMap::new();
+    routes.add_route(Route::new("GET", "/api/widgets"), LabelResponder::new("w"));
+    routes.add_route(Route::new("GET", "/page"), LabelResponder::new("p"));
+    TestClient::new(routes).with_config(config)
+}
+
+#[tokio::test]
+async fn status_handler_renders_problem_json_and_declines_to_the_default_page() {
+    let client = client(ServerConfig::new().with_error_handler(404, problem_json));
+
+    let api = client.send(TestRequest::get("/api/missing")).await;
+    assert_eq!(api.status, 404);
+    assert_eq!(api.header("content-type"), Some("application/problem+json"));
+    let problem: serde_json::Value = api.json().unwrap();
+    assert_eq!(problem["instance"], "/api/missing");
+    assert_eq!(problem["status"], 404);
+
+    // declined: the built-in reason-phrase page
+    let page = client.send(TestRequest::get("/missing")).await;
+    assert_eq!(page.status, 404);
+    assert_eq!(page.header("content-type"), Some("text/html"));
+
+    // other statuses are untouched
+    let method = client.send(TestRequest::post("/api/widgets")).await;
+    assert_eq!(method.status, 405);
+    assert_eq!(method.header("content-type"), Some("text/html"));
+}
+
+#[tokio::test]
+async fn catch_all_handler_sees_the_error_and_request_headers() {
+    let config = ServerConfig::new()
+        .with_error_handler(404, problem_json)
+        .with_default_error_handler(|context| {
+            let routing = matches!(
+                context.error,
+                WebError::Routing(RoutingError::MethodNotAllowed)
+            );
+            let body = format!(
+                "<h1>{} {}</h1><p>{}</p><p>{}</p>",
+                context.status,
+                context.method.as_deref().unwrap_or("?"),
+                routing,
+                context.header("x-brand").unwrap_or("plain"),
+            );
+            Some(body_response(
+                context.status,
+                "text/html; charset=utf-8",
+                body,
+            ))
+        });
+    let client = client(config);
+
+    let method = client
+        .send(TestRequest::delete("/page").with_header("X-Brand", "acme"))
+        .await;
+    assert_eq!(method.status, 405);
+    assert_eq!(
+        method.body_string(),
+        "<h1>405 DELETE</h1><p>true</p><p>acme</p>"
+    );
+
+    // a 404 outside /api is declined by its handler and reaches the catch-all
+    let missing = client.send(TestRequest::get("/missing")).await;
+    assert_eq!(
+        missing.body_string(),
+        "<h1>404 GET</h1><p>false</p><p>plain</p>"
+    );
+
+    // a malformed request line has no method or headers to report
+    let malformed = client.send_raw(b"NONSENSE\r\n\r\n").await;
+    assert_eq!(malformed[0].status, 400);
+    assert_eq!(
+        malformed[0].body_string(),
+        "<h1>400 ?</h1><p>false</p><p>plain</p>"
+    );
+    assert_eq!(malformed[0].header("connection"), Some("close"));
+}
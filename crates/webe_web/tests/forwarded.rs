@@ -0,0 +1,121 @@
+//! Integration tests for connection addresses and client resolution behind
+//! trusted proxies.
+
+mod common;
+
+use std::io::Cursor;
+use std::net::SocketAddr;
+
+use async_trait::async_trait;
+use webe_web::config::ServerConfig;
+use webe_web::forwarded::TrustedProxies;
+use webe_web::request::Request;
+use webe_web::responders::Responder;
+use webe_web::response::Response;
+use webe_web::server::{Route, RouteMap};
+use webe_web::testing::{TestClient, TestRequest};
+use webe_web::validation::Validation;
+
+/// Answers with the request's addresses and resolved client.
+struct WhoResponder;
+
+#[async_trait]
+impl Responder for WhoResponder {
+    async fn build_response(
+        &self,
+        request: &mut Request,
+        _params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        let show = |value: Option<String>| value.unwrap_or_else(|| "-".to_owned());
+        let body = format!(
+            "peer={} local={} client={} scheme={} host={}",
+            show(request.peer_addr.map(|addr| addr.to_string())),
+            show(request.local_addr.map(|addr| addr.to_string())),
+            show(request.client.ip.map(|ip| ip.to_string())),
+            request.client.scheme,
+            show(request.client.host.clone()),
+        );
+        let mut response = Response::new(200);
+        response
+            .headers
+            .insert("Content-Length".to_owned(), body.len().to_string());
+        response.message_body = Some(Box::pin(Cursor::new(body.into_bytes())));
+        Ok(response)
+    }
+}
+
+fn routes() -> RouteMap<'static> {
+    let mut routes = RouteMap::new();
+    routes.add_route(Route::new("GET", "/who"), WhoResponder);
+    routes
+}
+
+fn client_from(peer: &str) -> TestClient<'static> {
+    let proxies = TrustedProxies::parse(&["10.0.0.0/8"]).unwrap();
+    TestClient::new(routes())
+        .with_config(ServerConfig::new().with_trusted_proxies(proxies))
+        .with_peer_addr(peer.parse::<SocketAddr>().unwrap())
+}
+
+fn forwarded_request() -> TestRequest {
+    TestRequest::get("/who")
+        .with_header("Host", "backend:8080")
+        .with_header("X-Forwarded-For", "192.0.2.60, 10.0.0.7")
+        .with_header("X-Forwarded-Proto", "https")
+        .with_header("X-Forwarded-Host", "www.example")
+}
+
+#[tokio::test]
+async fn trusted_proxies_supply_the_client_ip_scheme_and_host() {
+    let response = client_from("10.0.0.5:51000")
+        .send(forwarded_request())
+        .await;
+    assert_eq!(
+        response.body_string(),
+        "peer=10.0.0.5:51000 local=- client=192.0.2.60 scheme=https host=www.example"
+    );
+
+    let response = client_from("10.0.0.5:51000")
+        .send(
+            TestRequest::get("/who")
+                .with_header("Host", "backend:8080")
+                .with_header(
+                    "Forwarded",
+                    "for=\"[2001:db8::9]:443\";proto=https;host=api.example",
+                ),
+        )
+        .await;
+    assert_eq!(
+        response.body_string(),
+        "peer=10.0.0.5:51000 local=- client=2001:db8::9 scheme=https host=api.example"
+    );
+}
+
+#[tokio::test]
+async fn forwarding_headers_from_untrusted_peers_are_ignored() {
+    let response = client_from("198.51.100.3:40000")
+        .send(forwarded_request())
+        .await;
+    assert_eq!(
+        response.body_string(),
+        "peer=198.51.100.3:40000 local=- client=198.51.100.3 scheme=http host=backend:8080"
+    );
+}
+
+#[tokio::test]
+async fn served_connections_record_both_addresses() {
+    let addr = common::spawn_server(routes()).await;
+    let response = common::TestClient::request(
+        addr,
+        b"GET /who HTTP/1.1\r\nHost: here\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    let body = response.body_string();
+    assert!(body.starts_with("peer=127.0.0.1:"), "{body}");
+    assert!(body.contains(&format!(" local={addr} ")), "{body}");
+    assert!(
+        body.ends_with(" client=127.0.0.1 scheme=http host=here"),
+        "{body}"
+    );
+}
@@ -0,0 +1,144 @@
+//! Integration tests for prior-knowledge HTTP/2 (`h2c`), under the `http2` feature.
+#![cfg(feature = "http2")]
+
+mod common;
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use common::{EchoBodyResponder, LabelResponder, StreamResponder, TestClient, spawn_server};
+use h2::client::SendRequest;
+use tokio::net::TcpStream;
+use webe_web::request::Request;
+use webe_web::responders::Responder;
+use webe_web::response::Response;
+use webe_web::server::{Route, RouteMap};
+use webe_web::streaming::body_channel;
+use webe_web::validation::Validation;
+
+/// Streams a short body from a spawned task and finishes it with a trailer.
+struct TrailerResponder;
+
+#[async_trait]
+impl Responder for TrailerResponder {
+    async fn build_response(
+        &self,
+        _request: &mut Request,
+        _params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        let (sender, body) = body_channel(1);
+        tokio::spawn(async move {
+            let _ = sender.send("payload").await;
+            sender.finish(HashMap::from([("Checksum".to_owned(), "42".to_owned())]));
+        });
+        Ok(Response::new(200).with_streamed_body(body))
+    }
+}
+
+fn routes() -> RouteMap<'static> {
+    let mut map = RouteMap::new();
+    map.add_route(Route::new("GET", "/label"), LabelResponder::new("root"));
+    map.add_route(Route::new("POST", "/echo"), EchoBodyResponder);
+    map.add_route(Route::new("GET", "/trailers"), TrailerResponder);
+    map.add_route(
+        Route::new("GET", "/stream"),
+        StreamResponder {
+            body: vec![b'x'; 100_000],
+        },
+    );
+    map
+}
+
+async fn connect(addr: std::net::SocketAddr) -> SendRequest<Bytes> {
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let (client, connection) = h2::client::handshake(tcp).await.unwrap();
+    tokio::spawn(async move {
+        let _ = connection.await;
+    });
+    client
+}
+
+/// Sends one request and returns the status, headers, and full body.
+async fn exchange(
+    client: &SendRequest<Bytes>,
+    method: &str,
+    path: &str,
+    body: &[u8],
+) -> (u16, http::HeaderMap, Vec<u8>) {
+    let request = http::Request::builder()
+        .method(method)
+        .uri(format!("http://test{path}"))
+        .body(())
+        .unwrap();
+    let mut client = client.clone().ready().await.unwrap();
+    let (response, mut send) = client.send_request(request, body.is_empty()).unwrap();
+    if !body.is_empty() {
+        send.send_data(Bytes::copy_from_slice(body), true).unwrap();
+    }
+    let response = response.await.unwrap();
+    let status = response.status().as_u16();
+    let headers = response.headers().clone();
+    let mut stream = response.into_body();
+    let mut received = Vec::new();
+    while let Some(chunk) = stream.data().await {
+        let chunk = chunk.unwrap();
+        stream.flow_control().release_capacity(chunk.len()).unwrap();
+        received.extend_from_slice(&chunk);
+    }
+    (status, headers, received)
+}
+
+#[tokio::test]
+async fn existing_responders_answer_multiplexed_streams() {
+    let addr = spawn_server(routes()).await;
+    let client = connect(addr).await;
+
+    let (label, echo, stream, missing) = tokio::join!(
+        exchange(&client, "GET", "/label", b""),
+        exchange(&client, "POST", "/echo", b"over h2"),
+        exchange(&client, "GET", "/stream", b""),
+        exchange(&client, "GET", "/missing", b""),
+    );
+    assert_eq!((label.0, label.2.as_slice()), (200, b"root".as_slice()));
+    assert!(label.1.get("connection").is_none());
+    assert_eq!((echo.0, echo.2.as_slice()), (200, b"over h2".as_slice()));
+    // a body of unknown length is sent as DATA frames beyond the initial window
+    assert_eq!(stream.0, 200);
+    assert_eq!(stream.2.len(), 100_000);
+    assert!(stream.1.get("transfer-encoding").is_none());
+    assert_eq!(missing.0, 404);
+}
+
+#[tokio::test]
+async fn http_1_1_still_works_alongside_h2c() {
+    let addr = spawn_server(routes()).await;
+    let response = TestClient::request(
+        addr,
+        b"POST /echo HTTP/1.1\r\nContent-Length: 2\r\nConnection: close\r\n\r\nhi",
+    )
+    .await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "hi");
+}
+
+#[tokio::test]
+async fn response_trailers_are_sent_as_trailing_headers() {
+    let addr = spawn_server(routes()).await;
+    let client = connect(addr).await;
+    let request = http::Request::builder()
+        .uri("http://test/trailers")
+        .body(())
+        .unwrap();
+    let mut client = client.ready().await.unwrap();
+    let (response, _) = client.send_request(request, true).unwrap();
+    let mut stream = response.await.unwrap().into_body();
+    let mut received = Vec::new();
+    while let Some(chunk) = stream.data().await {
+        received.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(received, b"payload");
+    let trailers = stream.trailers().await.unwrap().expect("trailers");
+    assert_eq!(trailers.get("checksum").unwrap(), "42");
+}
@@ -0,0 +1,187 @@
+//! Integration tests for connection limits and load shedding.
+
+mod common;
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::{LabelResponder, TestClient, spawn_server_with_config};
+use webe_web::config::ServerConfig;
+use webe_web::limits::{ConnectionLimits, OverloadAction};
+use webe_web::metrics::Metrics;
+use webe_web::request::Request;
+use webe_web::responders::Responder;
+use webe_web::response::Response;
+use webe_web::server::{Route, RouteMap};
+use webe_web::testing::{self, TestRequest};
+use webe_web::validation::Validation;
+
+const REQUEST: &[u8] = b"GET /a HTTP/1.1\r\n\r\n";
+
+fn routes() -> RouteMap<'static> {
+    let mut routes = RouteMap::new();
+    routes.add_route(Route::new("GET", "/a"), LabelResponder::new("a"));
+    routes
+}
+
+/// Opens a connection and completes one request on it, so the server has
+/// certainly accepted it, then leaves it open.
+async fn open_connection(addr: std::net::SocketAddr) -> TestClient {
+    let mut client = TestClient::connect(addr).await;
+    client.send(REQUEST).await;
+    assert_eq!(client.recv().await.status, 200);
+    client
+}
+
+#[tokio::test]
+async fn connections_over_the_limit_are_refused_with_503() {
+    let metrics = Metrics::new();
+    let limits = ConnectionLimits::new()
+        .with_max_connections(1, OverloadAction::Reject)
+        .with_retry_after(Duration::from_secs(5));
+    let config = ServerConfig::new()
+        .with_connection_limits(limits)
+        .with_metrics(metrics.clone());
+    let addr = spawn_server_with_config(routes(), config).await;
+
+    let open = open_connection(addr).await;
+    let refused = TestClient::request(addr, REQUEST).await;
+    assert_eq!(refused.status, 503);
+    assert_eq!(refused.header("retry-after").map(String::as_str), Some("5"));
+    assert_eq!(
+        refused.header("connection").map(String::as_str),
+        Some("close")
+    );
+
+    // closing the open connection frees its slot
+    drop(open);
+    let mut served = false;
+    for _ in 0..50 {
+        if TestClient::request(addr, REQUEST).await.status == 200 {
+            served = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(served);
+    assert!(
+        metrics
+            .render()
+            .contains("webe_http_shed_total{reason=\"connections\"} 1\n")
+    );
+}
+
+#[tokio::test]
+async fn each_client_ip_gets_its_own_connection_cap() {
+    let metrics = Metrics::new();
+    let limits = ConnectionLimits::new().with_max_connections_per_ip(2);
+    let config = ServerConfig::new()
+        .with_connection_limits(limits)
+        .with_metrics(metrics.clone());
+    let addr = spawn_server_with_config(routes(), config).await;
+
+    let _first = open_connection(addr).await;
+    let _second = open_connection(addr).await;
+    assert_eq!(TestClient::request(addr, REQUEST).await.status, 503);
+    assert!(
+        metrics
+            .render()
+            .contains("webe_http_shed_total{reason=\"connections_per_ip\"} 1\n")
+    );
+}
+
+#[tokio::test]
+async fn a_paused_server_accepts_once_a_connection_closes() {
+    let limits = ConnectionLimits::new().with_max_connections(1, OverloadAction::Pause);
+    let config = ServerConfig::new().with_connection_limits(limits);
+    let addr = spawn_server_with_config(routes(), config).await;
+
+    let open = open_connection(addr).await;
+    let mut waiting = TestClient::connect(addr).await;
+    waiting.send(REQUEST).await;
+    let early = tokio::time::timeout(Duration::from_millis(200), waiting.recv()).await;
+    assert!(early.is_err(), "the second connection should wait");
+
+    drop(open);
+    assert_eq!(waiting.recv().await.status, 200);
+}
+
+/// Holds each request for a while, keeping it in flight.
+struct SlowResponder;
+
+#[async_trait]
+impl Responder for SlowResponder {
+    async fn build_response(
+        &self,
+        _request: &mut Request,
+        _params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        Ok(Response::new(204))
+    }
+}
+
+#[tokio::test]
+async fn requests_over_the_in_flight_limit_are_shed() {
+    let mut routes = RouteMap::new();
+    routes.add_route(Route::new("GET", "/slow"), SlowResponder);
+    let limits = ConnectionLimits::new().with_max_in_flight(1);
+    let config = ServerConfig::new()
+        .with_connection_limits(limits)
+        .with_error_handler(503, |context| {
+            let mut response = Response::new(503);
+            response
+                .headers
+                .insert("X-Shed".to_owned(), context.error.to_string());
+            Some(response)
+        });
+    let client = testing::TestClient::new(routes).with_config(config);
+
+    let slow = client.send(TestRequest::get("/slow"));
+    let shed = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        client.send(TestRequest::get("/slow")).await
+    };
+    let (slow, shed) = tokio::join!(slow, shed);
+    assert_eq!(slow.status, 204);
+    assert_eq!(shed.status, 503);
+    assert_eq!(
+        shed.header("x-shed"),
+        Some("overload: the in-flight request limit was reached (503)")
+    );
+    // the slot is free again
+    assert_eq!(client.send(TestRequest::get("/slow")).await.status, 204);
+}
+
+#[tokio::test]
+async fn a_shed_request_body_is_drained_before_closing() {
+    let mut routes = RouteMap::new();
+    routes.add_route(Route::new("GET", "/slow"), SlowResponder);
+    routes.add_route(Route::new("POST", "/slow"), SlowResponder);
+    let limits = ConnectionLimits::new().with_max_in_flight(1);
+    let config = ServerConfig::new().with_connection_limits(limits);
+    let addr = spawn_server_with_config(routes, config).await;
+
+    let slow = tokio::spawn(TestClient::request(addr, b"GET /slow HTTP/1.1\r\n\r\n"));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // more body than the socket buffers hold: closing with it unread would reset
+    // the connection while the client is still sending
+    let body = vec![b'x'; 4 * 1024 * 1024];
+    let mut raw = format!(
+        "POST /slow HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    raw.extend_from_slice(&body);
+    let mut shed = TestClient::connect(addr).await;
+    shed.send(&raw).await;
+    let response = shed.recv().await;
+    assert_eq!(response.status, 503);
+    assert_eq!(
+        response.header("connection").map(String::as_str),
+        Some("close")
+    );
+    assert_eq!(slow.await.unwrap().status, 204);
+}
@@ -0,0 +1,64 @@
+//! Integration tests for server metrics and their Prometheus exposition.
+
+mod common;
+
+use common::{LabelResponder, TestClient, spawn_server_with_config};
+use webe_web::config::ServerConfig;
+use webe_web::metrics::Metrics;
+use webe_web::responders::metrics::MetricsResponder;
+use webe_web::server::{Route, RouteMap};
+
+#[tokio::test]
+async fn scrapes_count_requests_by_route_pattern_and_errors_by_category() {
+    let metrics = Metrics::new();
+    let mut routes = RouteMap::new();
+    routes.add_route(Route::new("GET", "/users/<id>"), LabelResponder::new("u"));
+    routes.add_route(
+        Route::new("GET", "/metrics"),
+        MetricsResponder::new(metrics.clone()),
+    );
+    let config = ServerConfig::new().with_metrics(metrics.clone());
+    let addr = spawn_server_with_config(routes, config).await;
+
+    let mut client = TestClient::connect(addr).await;
+    for id in ["1", "2"] {
+        client
+            .send(format!("GET /users/{id} HTTP/1.1\r\n\r\n").as_bytes())
+            .await;
+        assert_eq!(client.recv().await.status, 200);
+    }
+    client.send(b"GET /nowhere HTTP/1.1\r\n\r\n").await;
+    assert_eq!(client.recv().await.status, 404);
+    let malformed = TestClient::request(addr, b"NONSENSE\r\n\r\n").await;
+    assert_eq!(malformed.status, 400);
+
+    let scrape =
+        TestClient::request(addr, b"GET /metrics HTTP/1.1\r\nConnection: close\r\n\r\n").await;
+    assert_eq!(scrape.status, 200);
+    assert_eq!(
+        scrape.header("content-type").map(String::as_str),
+        Some("text/plain; version=0.0.4; charset=utf-8")
+    );
+    let text = scrape.body_string();
+    for expected in [
+        "webe_http_requests_total{route=\"/users/<id>\",method=\"GET\",status=\"200\"} 2\n",
+        "webe_http_requests_total{route=\"unmatched\",method=\"GET\",status=\"404\"} 1\n",
+        "webe_http_requests_total{route=\"unmatched\",method=\"OTHER\",status=\"400\"} 1\n",
+        "webe_http_request_duration_seconds_count{route=\"/users/<id>\",method=\"GET\"} 2\n",
+        "webe_http_errors_total{category=\"request\"} 1\n",
+        "webe_http_errors_total{category=\"routing\"} 1\n",
+        // "u;<id>=1" and "u;<id>=2", then "Not Found" and "Bad Request"
+        "webe_http_response_body_bytes_total 36\n",
+        // the scrape itself is in flight on the third connection
+        "webe_http_requests_in_flight 1\n",
+        "webe_http_connections_total 3\n",
+    ] {
+        assert!(text.contains(expected), "missing {expected:?} in\n{text}");
+    }
+    let received = text
+        .lines()
+        .find_map(|line| line.strip_prefix("webe_http_received_bytes_total "))
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap();
+    assert!(received > 60, "{received}");
+}
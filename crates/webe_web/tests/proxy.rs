@@ -0,0 +1,341 @@
+//! Integration tests for `ProxyResponder`, forwarding to a second local server.
+
+mod common;
+
+use std::io::Cursor;
+use std::time::Duration;
+
+use async_compression::tokio::bufread::GzipEncoder;
+use async_trait::async_trait;
+use common::{
+    EchoBodyResponder, LabelResponder, StreamResponder, TestClient, spawn_server,
+    spawn_server_with_config,
+};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+use webe_web::config::ServerConfig;
+use webe_web::encoding::decompress::DecodingLimits;
+use webe_web::forwarded::TrustedProxies;
+use webe_web::request::Request;
+use webe_web::responders::Responder;
+use webe_web::responders::proxy::ProxyResponder;
+use webe_web::response::Response;
+use webe_web::server::{Route, RouteMap};
+use webe_web::validation::Validation;
+
+/// Answers with the request target and every request header, one per line.
+struct DumpResponder;
+
+#[async_trait]
+impl Responder for DumpResponder {
+    async fn build_response(
+        &self,
+        request: &mut Request,
+        _params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        let mut lines = vec![format!("{} {}", request.method, request.uri)];
+        let mut headers: Vec<String> = request
+            .headers
+            .iter()
+            .flatten()
+            .map(|(name, value)| format!("{name}: {value}"))
+            .collect();
+        headers.sort();
+        lines.extend(headers);
+        let body = lines.join("\n").into_bytes();
+        let mut response = Response::new(200);
+        response
+            .headers
+            .insert("Content-Length".to_owned(), body.len().to_string());
+        response
+            .headers
+            .insert("X-Upstream".to_owned(), "yes".to_owned());
+        response.message_body = Some(Box::pin(Cursor::new(body)));
+        Ok(response)
+    }
+}
+
+/// Waits before answering, to trip the proxy's response timeout.
+struct SlowResponder;
+
+#[async_trait]
+impl Responder for SlowResponder {
+    async fn build_response(
+        &self,
+        _request: &mut Request,
+        _params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        Ok(Response::new(204))
+    }
+}
+
+async fn spawn_upstream() -> std::net::SocketAddr {
+    let mut map = RouteMap::new();
+    map.add_route(Route::new("GET", "/dump/<rest>"), DumpResponder);
+    map.add_route(Route::new("POST", "/echo"), EchoBodyResponder);
+    map.add_route(
+        Route::new("GET", "/stream"),
+        StreamResponder {
+            body: b"streamed upstream body".to_vec(),
+        },
+    );
+    map.add_route(Route::new("GET", "/slow"), SlowResponder);
+    map.add_route(Route::new("GET", "/label"), LabelResponder::new("upstream"));
+    spawn_server(map).await
+}
+
+/// Serves `/api/legacy/<rest>` (GET and POST) through proxies built by `make`.
+async fn spawn_front(make: impl Fn() -> ProxyResponder) -> std::net::SocketAddr {
+    let mut map = RouteMap::new();
+    for method in ["GET", "POST"] {
+        map.add_route(Route::new(method, "/api/legacy/<rest>"), make());
+    }
+    spawn_server(map).await
+}
+
+/// A bare upstream answering every request on every connection with `raw`.
+async fn spawn_raw_upstream(raw: &'static [u8]) -> std::net::SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _peer)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut stream = tokio::io::BufReader::new(stream);
+                loop {
+                    let mut line = String::new();
+                    match stream.read_line(&mut line).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(_) if line == "\r\n" => {
+                            if stream.get_mut().write_all(raw).await.is_err() {
+                                return;
+                            }
+                        }
+                        Ok(_) => {}
+                    }
+                }
+            });
+        }
+    });
+    addr
+}
+
+fn legacy_proxy(upstream: std::net::SocketAddr) -> ProxyResponder {
+    ProxyResponder::new(&format!("http://{upstream}"))
+        .expect("proxy responder")
+        .with_path_param("<rest>".to_owned())
+}
+
+#[tokio::test]
+async fn proxy_forwards_path_query_and_end_to_end_headers() {
+    let upstream = spawn_upstream().await;
+    let front = spawn_front(|| legacy_proxy(upstream)).await;
+
+    let response = TestClient::request(
+        front,
+        b"GET /api/legacy/dump/a/b?x=1&y=2 HTTP/1.1\r\nHost: front.example\r\nX-Custom: kept\r\nConnection: close, x-drop-me\r\nX-Drop-Me: gone\r\nKeep-Alive: timeout=5\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.header("X-Upstream"), Some(&"yes".to_string()));
+    let body = response.body_string();
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines[0], "GET /dump/a/b?x=1&y=2");
+    assert!(lines.contains(&"x-custom: kept"), "{body}");
+    assert!(
+        lines.contains(&format!("host: {upstream}").as_str()),
+        "{body}"
+    );
+    assert!(lines.contains(&"x-forwarded-host: front.example"), "{body}");
+    assert!(lines.contains(&"x-forwarded-proto: http"), "{body}");
+    assert!(lines.contains(&"x-forwarded-for: 127.0.0.1"), "{body}");
+    assert!(
+        lines.contains(&"forwarded: for=127.0.0.1;host=\"front.example\";proto=http"),
+        "{body}"
+    );
+    assert!(!body.contains("x-drop-me"), "{body}");
+    assert!(!body.contains("keep-alive: timeout"), "{body}");
+}
+
+#[tokio::test]
+async fn proxy_reports_the_client_resolved_through_trusted_proxies() {
+    let upstream = spawn_upstream().await;
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/api/legacy/<rest>"),
+        legacy_proxy(upstream),
+    );
+    let proxies = TrustedProxies::parse(&["127.0.0.0/8"]).unwrap();
+    let front =
+        spawn_server_with_config(map, ServerConfig::new().with_trusted_proxies(proxies)).await;
+
+    let response = TestClient::request(
+        front,
+        b"GET /api/legacy/dump/a HTTP/1.1\r\nHost: internal:8080\r\nX-Forwarded-For: 198.51.100.7\r\nX-Forwarded-Proto: https\r\nX-Forwarded-Host: www.example.com\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 200);
+    let body = response.body_string();
+    let lines: Vec<&str> = body.lines().collect();
+    assert!(lines.contains(&"x-forwarded-proto: https"), "{body}");
+    assert!(
+        lines.contains(&"x-forwarded-host: www.example.com"),
+        "{body}"
+    );
+    assert!(
+        lines.contains(&"x-forwarded-for: 198.51.100.7, 127.0.0.1"),
+        "{body}"
+    );
+    assert!(
+        lines.contains(&"forwarded: for=127.0.0.1;host=\"www.example.com\";proto=https"),
+        "{body}"
+    );
+}
+
+#[tokio::test]
+async fn proxy_streams_bodies_both_ways() {
+    let upstream = spawn_upstream().await;
+    let front = spawn_front(|| legacy_proxy(upstream)).await;
+
+    // a Content-Length request body
+    let response = TestClient::request(
+        front,
+        b"POST /api/legacy/echo HTTP/1.1\r\nContent-Length: 11\r\nConnection: close\r\n\r\nhello proxy",
+    )
+    .await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "hello proxy");
+
+    // a chunked request body is re-chunked upstream
+    let response = TestClient::request(
+        front,
+        b"POST /api/legacy/echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n5\r\nhello\r\n6\r\n chunk\r\n0\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.body_string(), "hello chunk");
+
+    // a chunked upstream response is relayed
+    let response = TestClient::request(
+        front,
+        b"GET /api/legacy/stream HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "streamed upstream body");
+}
+
+#[tokio::test]
+async fn proxy_reuses_upstream_connections_across_requests() {
+    let upstream = spawn_upstream().await;
+    let front = spawn_front(|| legacy_proxy(upstream)).await;
+
+    let mut client = TestClient::connect(front).await;
+    for _ in 0..3 {
+        client.send(b"GET /api/legacy/label HTTP/1.1\r\n\r\n").await;
+        let response = client.recv().await;
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body_string(), "upstream");
+    }
+    // upstream errors are relayed as-is
+    client
+        .send(b"GET /api/legacy/missing HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .await;
+    assert_eq!(client.recv().await.status, 404);
+}
+
+#[tokio::test]
+async fn proxy_maps_upstream_failures_to_gateway_errors() {
+    // nothing listens on a port that was just released
+    let unused = {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap()
+    };
+    let front = spawn_front(|| legacy_proxy(unused)).await;
+    let response = TestClient::request(
+        front,
+        b"GET /api/legacy/label HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 502);
+
+    let upstream = spawn_upstream().await;
+    let front =
+        spawn_front(|| legacy_proxy(upstream).with_response_timeout(Duration::from_millis(200)))
+            .await;
+    let response = TestClient::request(
+        front,
+        b"GET /api/legacy/slow HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 504);
+}
+
+#[tokio::test]
+async fn proxy_relays_each_set_cookie_header_separately() {
+    let upstream = spawn_raw_upstream(
+        b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nSet-Cookie: a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT\r\nX-Seen: one\r\nSet-Cookie: b=2; Expires=Thu, 22 Oct 2026 07:28:00 GMT; Path=/\r\nX-Seen: two\r\n\r\n",
+    )
+    .await;
+    let front = spawn_front(|| legacy_proxy(upstream)).await;
+    let response = TestClient::request(
+        front,
+        b"GET /api/legacy/cookies HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.header_values("set-cookie"),
+        [
+            "a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT",
+            "b=2; Expires=Thu, 22 Oct 2026 07:28:00 GMT; Path=/",
+        ]
+    );
+    // other repeated headers are still combined
+    assert_eq!(response.header_values("x-seen"), ["one, two"]);
+}
+
+#[tokio::test]
+async fn proxy_answers_an_upstream_protocol_switch_with_502() {
+    let upstream = spawn_raw_upstream(
+        b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n",
+    )
+    .await;
+    let front = spawn_front(|| legacy_proxy(upstream)).await;
+    for _ in 0..2 {
+        let response = TestClient::request(
+            front,
+            b"GET /api/legacy/socket HTTP/1.1\r\nUpgrade: websocket\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert_eq!(response.status, 502);
+        assert_eq!(response.header("upgrade"), None);
+    }
+}
+
+#[tokio::test]
+async fn proxy_forwards_a_decoded_request_body_chunked() {
+    let upstream = spawn_upstream().await;
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("POST", "/api/legacy/<rest>"),
+        legacy_proxy(upstream),
+    );
+    let config = ServerConfig::new().with_request_decoding(DecodingLimits::default());
+    let front = spawn_server_with_config(map, config).await;
+
+    let mut body = Vec::new();
+    GzipEncoder::new(&b"decoded before forwarding"[..])
+        .read_to_end(&mut body)
+        .await
+        .expect("gzip encode");
+    let mut raw = format!(
+        "POST /api/legacy/echo HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    raw.extend_from_slice(&body);
+    let response = TestClient::request(front, &raw).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "decoded before forwarding");
+}
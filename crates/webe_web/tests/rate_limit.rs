@@ -0,0 +1,148 @@
+//! Integration tests for the rate-limiting responder wrapper.
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use webe_web::config::ServerConfig;
+use webe_web::forwarded::TrustedProxies;
+use webe_web::rate_limit::RateLimiter;
+use webe_web::responders::rate_limit::{RateLimitKey, RateLimitResponder};
+use webe_web::responders::static_message::StaticResponder;
+use webe_web::server::{Route, RouteMap};
+use webe_web::status::Status;
+use webe_web::testing::{TestClient, TestRequest};
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+fn limited(key: RateLimitKey) -> RouteMap<'static> {
+    let limiter = RateLimiter::token_bucket(2, MINUTE);
+    let mut routes = RouteMap::new();
+    routes.add_route(
+        Route::new("POST", "/login"),
+        RateLimitResponder::new(limiter.clone(), StaticResponder::from_standard_code(200))
+            .with_key(key.clone()),
+    );
+    routes.add_route(
+        Route::new("POST", "/reset"),
+        RateLimitResponder::new(limiter, StaticResponder::from_standard_code(200)).with_key(key),
+    );
+    routes
+}
+
+fn from(routes: RouteMap<'static>, peer: &str) -> TestClient<'static> {
+    TestClient::new(routes).with_peer_addr(peer.parse::<SocketAddr>().unwrap())
+}
+
+#[tokio::test]
+async fn clients_over_the_limit_get_429_with_retry_after() {
+    let client = from(limited(RateLimitKey::ClientIp), "192.0.2.1:5000");
+    let responses = client
+        .send_all(vec![
+            TestRequest::post("/login").with_body("a"),
+            TestRequest::post("/reset").with_body("b"),
+            TestRequest::post("/login").with_body("c"),
+        ])
+        .await;
+    assert_eq!(responses[0].status, 200);
+    assert_eq!(responses[0].header("ratelimit-limit"), Some("2"));
+    assert_eq!(responses[0].header("ratelimit-remaining"), Some("1"));
+    assert_eq!(responses[0].header("ratelimit-reset"), Some("30"));
+    // the routes share one limiter, and so one budget per client
+    assert_eq!(responses[1].header("ratelimit-remaining"), Some("0"));
+    assert_eq!(responses[2].status, 429);
+    assert_eq!(responses[2].header("retry-after"), Some("30"));
+    assert_eq!(responses[2].header("ratelimit-remaining"), Some("0"));
+    assert_eq!(Status::get_standard_reason(429), "Too Many Requests");
+
+    // another client is unaffected
+    let other = from(limited(RateLimitKey::ClientIp), "192.0.2.2:5000");
+    assert_eq!(other.send(TestRequest::post("/login")).await.status, 200);
+}
+
+#[tokio::test]
+async fn clients_behind_trusted_proxies_are_limited_separately() {
+    let proxies = TrustedProxies::parse(&["10.0.0.0/8"]).unwrap();
+    let client = from(limited(RateLimitKey::ClientIp), "10.0.0.2:5000")
+        .with_config(ServerConfig::new().with_trusted_proxies(proxies));
+    let via_proxy = |ip: &str| TestRequest::post("/login").with_header("X-Forwarded-For", ip);
+    let responses = client
+        .send_all(vec![
+            via_proxy("198.51.100.1"),
+            via_proxy("198.51.100.1"),
+            via_proxy("198.51.100.2"),
+            via_proxy("198.51.100.1"),
+        ])
+        .await;
+    let statuses: Vec<u16> = responses.iter().map(|response| response.status).collect();
+    assert_eq!(statuses, [200, 200, 200, 429]);
+}
+
+#[tokio::test]
+async fn route_and_custom_keys_choose_what_shares_a_budget() {
+    let by_route = limited(RateLimitKey::Route);
+    let responses = from(by_route, "192.0.2.1:5000")
+        .send_all(vec![
+            TestRequest::post("/login"),
+            TestRequest::post("/login"),
+            TestRequest::post("/reset"),
+            TestRequest::post("/login"),
+        ])
+        .await;
+    let statuses: Vec<u16> = responses.iter().map(|response| response.status).collect();
+    assert_eq!(statuses, [200, 200, 200, 429]);
+
+    // a custom key can exempt requests entirely
+    let by_account = limited(RateLimitKey::custom(|request, _params| {
+        request
+            .headers
+            .as_ref()
+            .and_then(|headers| headers.get("x-account"))
+            .cloned()
+    }));
+    let responses = from(by_account, "192.0.2.1:5000")
+        .send_all(vec![
+            TestRequest::post("/login").with_header("X-Account", "ann"),
+            TestRequest::post("/login").with_header("X-Account", "ann"),
+            TestRequest::post("/login"),
+            TestRequest::post("/login").with_header("X-Account", "bob"),
+            TestRequest::post("/login").with_header("X-Account", "ann"),
+        ])
+        .await;
+    let statuses: Vec<u16> = responses.iter().map(|response| response.status).collect();
+    assert_eq!(statuses, [200, 200, 200, 200, 429]);
+    assert_eq!(responses[2].header("ratelimit-limit"), None);
+}
+
+#[tokio::test]
+async fn refused_uploads_are_not_told_to_continue() {
+    let limiter = RateLimiter::token_bucket(1, MINUTE);
+    let mut routes = RouteMap::new();
+    routes.add_route(
+        Route::new("POST", "/upload"),
+        RateLimitResponder::new(limiter, StaticResponder::from_standard_code(200)),
+    );
+    let addr = common::spawn_server(routes).await;
+    let upload = b"POST /upload HTTP/1.1\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n";
+
+    let mut client = common::TestClient::connect(addr).await;
+    client.send(upload).await;
+    assert_eq!(client.recv().await.status, 100);
+    client.send(b"hello").await;
+    assert_eq!(client.recv().await.status, 200);
+
+    // over the limit: the final status comes first, with the limit's headers
+    let mut client = common::TestClient::connect(addr).await;
+    client.send(upload).await;
+    let refused = client.recv().await;
+    assert_eq!(refused.status, 429);
+    assert_eq!(
+        refused.header("retry-after").map(String::as_str),
+        Some("60")
+    );
+    assert_eq!(
+        refused.header("ratelimit-remaining").map(String::as_str),
+        Some("0")
+    );
+}
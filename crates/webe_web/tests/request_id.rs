@@ -0,0 +1,88 @@
+//! Integration tests for request ids: accepted or generated, visible to the
+//! responder and error handlers, and echoed on every response.
+
+use std::io::Cursor;
+
+use async_trait::async_trait;
+use webe_id::{Generator, NodeId};
+use webe_web::config::ServerConfig;
+use webe_web::request::Request;
+use webe_web::request_id::RequestIds;
+use webe_web::responders::Responder;
+use webe_web::response::Response;
+use webe_web::server::{Route, RouteMap};
+use webe_web::testing::{TestClient, TestRequest};
+use webe_web::validation::Validation;
+
+/// Answers with the id the request was given.
+struct IdResponder;
+
+#[async_trait]
+impl Responder for IdResponder {
+    async fn build_response(
+        &self,
+        request: &mut Request,
+        _params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        Ok(text_response(200, request.id.clone().unwrap_or_default()))
+    }
+}
+
+fn text_response(status: u16, body: String) -> Response {
+    let mut response = Response::new(status);
+    response
+        .headers
+        .insert("Content-Length".to_owned(), body.len().to_string());
+    response.message_body = Some(Box::pin(Cursor::new(body.into_bytes())));
+    response
+}
+
+fn client(request_ids: RequestIds) -> TestClient<'static> {
+    let mut routes = RouteMap::new();
+    routes.add_route(Route::new("GET", "/id"), IdResponder);
+    let config = ServerConfig::new()
+        .with_request_ids(request_ids)
+        .with_default_error_handler(|context| {
+            let id = context.request_id.clone().unwrap_or_default();
+            Some(text_response(context.status, format!("failed {id}")))
+        });
+    TestClient::new(routes).with_config(config)
+}
+
+fn request_ids() -> RequestIds {
+    RequestIds::new(Generator::new(NodeId::from_u8(3)).unwrap())
+}
+
+#[tokio::test]
+async fn incoming_ids_are_kept_and_missing_ones_generated() {
+    let client = client(request_ids());
+
+    let kept = client
+        .send(TestRequest::get("/id").with_header("X-Request-Id", "upstream-42"))
+        .await;
+    assert_eq!(kept.body_string(), "upstream-42");
+    assert_eq!(kept.header("x-request-id"), Some("upstream-42"));
+
+    let generated = client.send(TestRequest::get("/id")).await;
+    let id = generated.body_string();
+    assert_eq!(id.len(), 16);
+    assert!(id.bytes().all(|byte| byte.is_ascii_hexdigit()));
+    assert_eq!(generated.header("x-request-id"), Some(id.as_str()));
+}
+
+#[tokio::test]
+async fn failed_requests_carry_an_id_to_the_error_handler_and_response() {
+    let client = client(request_ids().with_header("X-Correlation-Id"));
+
+    let missing = client
+        .send(TestRequest::get("/missing").with_header("X-Correlation-Id", "corr-7"))
+        .await;
+    assert_eq!(missing.status, 404);
+    assert_eq!(missing.body_string(), "failed corr-7");
+    assert_eq!(missing.header("x-correlation-id"), Some("corr-7"));
+
+    let malformed = client.send_raw(b"NONSENSE\r\n\r\n").await;
+    let id = malformed[0].header("x-correlation-id").unwrap().to_owned();
+    assert_eq!(malformed[0].body_string(), format!("failed {id}"));
+}
@@ -4,16 +4,40 @@ mod common;
 
 use std::net::Ipv4Addr;
 
-use common::{LabelResponder, TestClient, spawn_server};
+use common::{LabelResponder, StreamResponder, TestClient, spawn_server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
 use webe_web::error::WebError;
+use webe_web::processor::process_connection;
 use webe_web::server::{Route, RouteMap, Server};
 
 fn routes() -> RouteMap<'static> {
     let mut map = RouteMap::new();
     map.add_route(Route::new("GET", "/"), LabelResponder::new("root"));
+    map.add_route(
+        Route::new("GET", "/stream"),
+        StreamResponder {
+            body: b"streamed".to_vec(),
+        },
+    );
     map
 }
 
+/// Sends `raw` and reads until the server closes the connection.
+async fn exchange_until_close(addr: std::net::SocketAddr, raw: &[u8]) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(raw).await.unwrap();
+    let mut received = Vec::new();
+    tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        stream.read_to_end(&mut received),
+    )
+    .await
+    .expect("the server should close the connection")
+    .unwrap();
+    String::from_utf8(received).unwrap()
+}
+
 #[tokio::test]
 async fn valid_http_1_1_request_is_accepted() {
     let addr = spawn_server(routes()).await;
@@ -59,12 +83,53 @@ async fn request_line_that_never_terminates_is_bad_request() {
 }
 
 #[tokio::test]
-async fn non_http_1_1_version_is_unsupported() {
+async fn unsupported_version_is_rejected() {
     let addr = spawn_server(routes()).await;
-    let response = TestClient::request(addr, b"GET / HTTP/1.0\r\nConnection: close\r\n\r\n").await;
+    let response = TestClient::request(addr, b"GET / HTTP/2.0\r\nConnection: close\r\n\r\n").await;
     assert_eq!(response.status, 505);
 }
 
+#[tokio::test]
+async fn http_1_0_requests_close_by_default_and_echo_the_version() {
+    let addr = spawn_server(routes()).await;
+    // two requests, but the connection closes after the first
+    let received =
+        exchange_until_close(addr, b"GET / HTTP/1.0\r\n\r\nGET / HTTP/1.0\r\n\r\n").await;
+    assert!(received.starts_with("HTTP/1.0 200 OK\r\n"), "{received}");
+    assert!(received.contains("Connection: close\r\n"));
+    assert_eq!(received.matches("HTTP/1.0 200").count(), 1);
+}
+
+#[tokio::test]
+async fn http_1_0_keep_alive_is_honored() {
+    let addr = spawn_server(routes()).await;
+    let mut client = TestClient::connect(addr).await;
+    client
+        .send(b"GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n")
+        .await;
+    let first = client.recv().await;
+    assert_eq!(first.header("Connection"), Some(&"keep-alive".to_string()));
+    client.send(b"GET / HTTP/1.0\r\n\r\n").await;
+    let second = client.recv().await;
+    assert_eq!(second.body_string(), "root");
+    assert_eq!(second.header("Connection"), Some(&"close".to_string()));
+}
+
+#[tokio::test]
+async fn http_1_0_streamed_bodies_are_close_delimited() {
+    let addr = spawn_server(routes()).await;
+    let received = exchange_until_close(
+        addr,
+        b"GET /stream HTTP/1.0\r\nConnection: keep-alive\r\n\r\n",
+    )
+    .await;
+    let (head, body) = received.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.0 200 OK"));
+    assert!(!head.to_lowercase().contains("transfer-encoding"));
+    assert!(head.contains("Connection: close"));
+    assert_eq!(body, "streamed");
+}
+
 #[tokio::test]
 async fn binding_an_already_bound_port_is_a_typed_bind_error() {
     let ip = Ipv4Addr::new(127, 0, 0, 1);
@@ -80,3 +145,17 @@ async fn binding_an_already_bound_port_is_a_typed_bind_error() {
         result.err()
     );
 }
+
+#[tokio::test]
+async fn process_connection_serves_an_accepted_stream_with_the_default_config() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (stream, _peer) = listener.accept().await.unwrap();
+        let _ = process_connection(stream, std::sync::Arc::new(routes())).await;
+    });
+
+    let response = TestClient::request(addr, b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "root");
+}
@@ -4,9 +4,17 @@ mod common;
 
 use std::path::PathBuf;
 
-use common::{TestClient, spawn_server};
-use webe_web::responders::file::FileResponder;
+use common::{TestClient, spawn_server, spawn_server_with_config};
+use webe_web::config::ServerConfig;
+use webe_web::forwarded::TrustedProxies;
+use webe_web::responders::embedded::{EmbeddedAsset, EmbeddedResponder};
+use webe_web::responders::file::{FileResponder, MimeTypeList};
+use webe_web::responders::listing::{DirectoryListing, ListingSort};
 use webe_web::responders::options::OptionsResponder;
+use webe_web::responders::redirect::{
+    CanonicalHostResponder, RedirectResponder, RedirectStatus, TrailingSlash,
+    TrailingSlashResponder,
+};
 use webe_web::responders::spa::SpaResponder;
 use webe_web::responders::static_message::StaticResponder;
 use webe_web::server::{Route, RouteMap};
@@ -72,6 +80,160 @@ async fn options_responder_echoes_preflight_headers() {
     );
 }
 
+// ---------- Redirects ----------
+
+#[tokio::test]
+async fn redirect_responder_expands_route_params() {
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/old/<id>/<rest>"),
+        RedirectResponder::new(
+            RedirectStatus::PermanentRedirect,
+            "/items/<id>/<rest>".to_owned(),
+        )
+        .with_query_string(true),
+    );
+    map.add_route(
+        Route::new("POST", "/form"),
+        RedirectResponder::new(RedirectStatus::SeeOther, "/thanks".to_owned()),
+    );
+    let addr = spawn_server(map).await;
+
+    let response = TestClient::request(
+        addr,
+        b"GET /old/42/a/b?x=1 HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 308);
+    assert_eq!(
+        response.header("Location"),
+        Some(&"/items/42/a/b?x=1".to_string())
+    );
+
+    let response = TestClient::request(
+        addr,
+        b"POST /form HTTP/1.1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 303);
+    assert_eq!(response.header("Location"), Some(&"/thanks".to_string()));
+}
+
+#[tokio::test]
+async fn redirect_responder_keeps_captured_values_on_site() {
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/old/<rest>"),
+        RedirectResponder::new(RedirectStatus::Found, "/<rest>".to_owned()),
+    );
+    let addr = spawn_server(map).await;
+
+    for (target, location) in [
+        ("/old//evil.example", "/evil.example"),
+        ("/old///evil.example/a", "/evil.example/a"),
+        ("/old/%5C%5Cevil.example", "/%5C%5Cevil.example"),
+        ("/old/\\\\evil.example", "/%5C%5Cevil.example"),
+    ] {
+        let raw = format!("GET {target} HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let response = TestClient::request(addr, raw.as_bytes()).await;
+        assert_eq!(response.status, 302, "{target}");
+        assert_eq!(
+            response.header("Location").map(String::as_str),
+            Some(location),
+            "{target}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn trailing_slash_responder_redirects_to_the_canonical_path() {
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/docs"),
+        TrailingSlashResponder::new(
+            TrailingSlash::Remove,
+            StaticResponder::new(200, "docs".to_owned()),
+        ),
+    );
+    let addr = spawn_server(map).await;
+
+    let response = TestClient::request(
+        addr,
+        b"GET /docs/?page=2 HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 308);
+    assert_eq!(
+        response.header("Location"),
+        Some(&"/docs?page=2".to_string())
+    );
+
+    let response =
+        TestClient::request(addr, b"GET /docs HTTP/1.1\r\nConnection: close\r\n\r\n").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "docs");
+}
+
+#[tokio::test]
+async fn canonical_host_responder_redirects_other_hosts_and_plain_http() {
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/<path>"),
+        CanonicalHostResponder::new(
+            "www.example.com".to_owned(),
+            StaticResponder::new(200, "home".to_owned()),
+        )
+        .with_https(true),
+    );
+    let proxies = TrustedProxies::parse(&["127.0.0.0/8"]).unwrap();
+    let addr =
+        spawn_server_with_config(map, ServerConfig::new().with_trusted_proxies(proxies)).await;
+    let get = |host: &str, proto: &str| {
+        format!(
+            "GET /a/b?c=d HTTP/1.1\r\nHost: {host}\r\nX-Forwarded-For: 203.0.113.9\r\nX-Forwarded-Proto: {proto}\r\nConnection: close\r\n\r\n"
+        )
+    };
+
+    let response = TestClient::request(addr, get("example.com", "https").as_bytes()).await;
+    assert_eq!(response.status, 301);
+    assert_eq!(
+        response.header("Location"),
+        Some(&"https://www.example.com/a/b?c=d".to_string())
+    );
+
+    let response = TestClient::request(addr, get("www.example.com", "http").as_bytes()).await;
+    assert_eq!(response.status, 301);
+
+    let response = TestClient::request(addr, get("WWW.example.com", "https").as_bytes()).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "home");
+}
+
+#[tokio::test]
+async fn canonical_host_responder_ignores_an_untrusted_forwarded_proto() {
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/<path>"),
+        CanonicalHostResponder::new(
+            "www.example.com".to_owned(),
+            StaticResponder::new(200, "home".to_owned()),
+        )
+        .with_https(true),
+    );
+    let addr = spawn_server(map).await;
+
+    let response = TestClient::request(
+        addr,
+        b"GET /a HTTP/1.1\r\nHost: www.example.com\r\nX-Forwarded-For: 203.0.113.9\r\nX-Forwarded-Proto: https\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 301);
+    assert_eq!(
+        response.header("Location"),
+        Some(&"https://www.example.com/a".to_string())
+    );
+}
+
 // ---------- FileResponder success ----------
 
 #[tokio::test]
@@ -98,6 +260,307 @@ async fn file_responder_serves_an_existing_file() {
     let _ = std::fs::remove_dir_all(&mount);
 }
 
+#[tokio::test]
+async fn large_files_are_sent_whole_on_keep_alive_connections() {
+    let mount = temp_mount("file_large");
+    let contents: Vec<u8> = (0..3_000_000u32).map(|n| (n % 251) as u8).collect();
+    std::fs::write(mount.join("large.bin"), &contents).unwrap();
+    let addr = spawn_server(file_routes(file_responder(&mount))).await;
+
+    // the body goes out kernel-side on Linux; framing must stay exact either way
+    let mut client = TestClient::connect(addr).await;
+    for _ in 0..2 {
+        client.send(b"GET /large.bin HTTP/1.1\r\n\r\n").await;
+        let response = client.recv().await;
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            response.header("Content-Length"),
+            Some(&"3000000".to_string())
+        );
+        assert!(response.body == contents);
+    }
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+// ---------- FileResponder MIME resolution ----------
+
+/// Serves `mount` on `GET /<path>` with a configured file responder.
+fn file_routes(responder: FileResponder) -> RouteMap<'static> {
+    let mut map = RouteMap::new();
+    map.add_route(Route::new("GET", "/<path>"), responder);
+    map
+}
+
+fn file_responder(mount: &std::path::Path) -> FileResponder {
+    FileResponder::new(mount.to_string_lossy().into_owned(), "<path>".to_owned())
+        .expect("file responder")
+}
+
+async fn content_type_of(addr: std::net::SocketAddr, path: &str) -> (u16, Option<String>) {
+    let raw = format!("GET /{path} HTTP/1.1\r\nConnection: close\r\n\r\n");
+    let response = TestClient::request(addr, raw.as_bytes()).await;
+    (response.status, response.header("Content-Type").cloned())
+}
+
+#[tokio::test]
+async fn file_responder_resolves_common_types_case_insensitively() {
+    let mount = temp_mount("mime_default");
+    std::fs::write(mount.join("app.wasm"), b"\0asm").unwrap();
+    std::fs::write(mount.join("LOGO.PNG"), b"png").unwrap();
+    std::fs::write(mount.join("notes.txt"), b"text").unwrap();
+    let addr = spawn_server(file_routes(file_responder(&mount))).await;
+
+    let response =
+        TestClient::request(addr, b"GET /app.wasm HTTP/1.1\r\nConnection: close\r\n\r\n").await;
+    assert_eq!(
+        response.header("Content-Type"),
+        Some(&"application/wasm".to_string())
+    );
+    assert_eq!(
+        response.header("X-Content-Type-Options"),
+        Some(&"nosniff".to_string())
+    );
+    assert_eq!(
+        content_type_of(addr, "LOGO.PNG").await.1.as_deref(),
+        Some("image/png")
+    );
+    assert_eq!(
+        content_type_of(addr, "notes.txt").await.1.as_deref(),
+        Some("text/plain; charset=utf-8")
+    );
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+#[tokio::test]
+async fn custom_mime_types_merge_over_the_default_table() {
+    let mount = temp_mount("mime_custom");
+    std::fs::write(mount.join("data.webe"), b"custom").unwrap();
+    std::fs::write(mount.join("site.css"), b"body{}").unwrap();
+    std::fs::write(mount.join("data.json"), b"{}").unwrap();
+    let custom = MimeTypeList::Custom(vec![
+        ("webe".to_owned(), "application/x-webe".to_owned()),
+        ("JSON".to_owned(), "application/vnd.webe+json".to_owned()),
+    ]);
+    let addr = spawn_server(file_routes(file_responder(&mount).with_mime_types(custom))).await;
+
+    assert_eq!(
+        content_type_of(addr, "data.webe").await.1.as_deref(),
+        Some("application/x-webe")
+    );
+    assert_eq!(
+        content_type_of(addr, "data.json").await.1.as_deref(),
+        Some("application/vnd.webe+json")
+    );
+    // unlisted extensions still resolve through the default table
+    assert_eq!(
+        content_type_of(addr, "site.css").await.1.as_deref(),
+        Some("text/css; charset=utf-8")
+    );
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+#[tokio::test]
+async fn exclusive_mime_types_replace_the_default_table() {
+    let mount = temp_mount("mime_exclusive");
+    std::fs::write(mount.join("site.css"), b"body{}").unwrap();
+    let exclusive = MimeTypeList::Exclusive(vec![("webe".to_owned(), "x/webe".to_owned())]);
+    let addr = spawn_server(file_routes(
+        file_responder(&mount).with_mime_types(exclusive),
+    ))
+    .await;
+
+    assert_eq!(
+        content_type_of(addr, "site.css").await.1.as_deref(),
+        Some("application/octet-stream")
+    );
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+#[tokio::test]
+async fn extensionless_files_are_sniffed_only_when_enabled() {
+    let mount = temp_mount("mime_sniff");
+    std::fs::write(mount.join("image"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+    std::fs::write(mount.join("readme"), b"plain words").unwrap();
+    let plain = spawn_server(file_routes(file_responder(&mount))).await;
+    let sniffing = spawn_server(file_routes(
+        file_responder(&mount).with_content_sniffing(true),
+    ))
+    .await;
+
+    assert_eq!(
+        content_type_of(plain, "image").await.1.as_deref(),
+        Some("application/octet-stream")
+    );
+    assert_eq!(
+        content_type_of(sniffing, "image").await.1.as_deref(),
+        Some("image/png")
+    );
+    assert_eq!(
+        content_type_of(sniffing, "readme").await.1.as_deref(),
+        Some("text/plain; charset=utf-8")
+    );
+    // sniffing rewinds the file, so the full body is still served
+    let response = TestClient::request(
+        sniffing,
+        b"GET /readme HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.body_string(), "plain words");
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+// ---------- FileResponder directory listing ----------
+
+/// Serves `mount` on `GET /<path>` with directory listings enabled.
+fn listing_routes(mount: &std::path::Path, listing: DirectoryListing) -> RouteMap<'static> {
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/<path>"),
+        FileResponder::new(mount.to_string_lossy().into_owned(), "<path>".to_owned())
+            .expect("file responder")
+            .with_directory_listing(listing),
+    );
+    map
+}
+
+#[tokio::test]
+async fn directory_listing_renders_html_without_dotfiles() {
+    let mount = temp_mount("listing_html");
+    std::fs::create_dir_all(mount.join("docs/nested")).unwrap();
+    std::fs::write(mount.join("docs/a <b>.txt"), b"12345").unwrap();
+    std::fs::write(mount.join("docs/.secret"), b"hidden").unwrap();
+    let addr = spawn_server(listing_routes(&mount, DirectoryListing::default())).await;
+
+    let response =
+        TestClient::request(addr, b"GET /docs HTTP/1.1\r\nConnection: close\r\n\r\n").await;
+    assert_eq!(response.status, 200);
+    assert!(
+        response
+            .header("Content-Type")
+            .unwrap()
+            .starts_with("text/html")
+    );
+    let body = response.body_string();
+    assert!(body.contains("href=\"/docs/nested/\""), "{body}");
+    assert!(body.contains("href=\"/docs/a%20%3Cb%3E.txt\""), "{body}");
+    assert!(body.contains("a &lt;b&gt;.txt"), "{body}");
+    assert!(body.contains("href=\"/\">../"), "{body}");
+    assert!(!body.contains(".secret"), "{body}");
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+#[tokio::test]
+async fn directory_listing_renders_sorted_json_when_accepted() {
+    let mount = temp_mount("listing_json");
+    std::fs::create_dir_all(mount.join("sub")).unwrap();
+    std::fs::write(mount.join("small.txt"), b"1").unwrap();
+    std::fs::write(mount.join("large.txt"), b"1234567890").unwrap();
+    std::fs::write(mount.join(".hidden"), b"shown").unwrap();
+    let listing = DirectoryListing {
+        show_hidden: true,
+        ..DirectoryListing::default()
+    };
+    let addr = spawn_server(listing_routes(&mount, listing)).await;
+
+    let response = TestClient::request(
+        addr,
+        b"GET /?sort=size&order=desc HTTP/1.1\r\nAccept: application/json\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 200);
+    let json: serde_json::Value = serde_json::from_slice(&response.body).expect("json listing");
+    let names: Vec<&str> = json["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["name"].as_str().unwrap())
+        .collect();
+    // directories first, then files by descending size
+    assert_eq!(names, vec!["sub", "large.txt", ".hidden", "small.txt"]);
+    assert_eq!(json["entries"][1]["size"], 10);
+    assert!(json["entries"][1]["modified"].is_u64());
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+#[tokio::test]
+async fn only_directory_listings_ignore_the_query_string() {
+    let mount = temp_mount("listing_query");
+    std::fs::create_dir_all(mount.join("docs")).unwrap();
+    std::fs::write(mount.join("docs/a.txt"), b"a").unwrap();
+    let addr = spawn_server(listing_routes(&mount, DirectoryListing::default())).await;
+
+    let response = TestClient::request(
+        addr,
+        b"GET /docs?sort=name HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 200);
+    assert!(response.body_string().contains("a.txt"));
+
+    // a file path keeps the query as part of its name, as without listings
+    let response = TestClient::request(
+        addr,
+        b"GET /docs/a.txt?v=1 HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 404);
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+#[tokio::test]
+async fn directory_listing_prefers_the_index_file() {
+    let mount = temp_mount("listing_index");
+    std::fs::write(mount.join("index.html"), b"<html>index</html>").unwrap();
+    let listing = DirectoryListing {
+        sort: ListingSort::Modified,
+        ..DirectoryListing::default()
+    };
+    let addr = spawn_server(listing_routes(&mount, listing)).await;
+
+    let response = TestClient::request(addr, b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "<html>index</html>");
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn directory_listing_never_lists_outside_the_mount() {
+    let mount = temp_mount("listing_escape");
+    let outside = temp_mount("listing_escape_target");
+    std::fs::write(outside.join("private.txt"), b"private").unwrap();
+    std::fs::create_dir_all(mount.join("pub")).unwrap();
+    std::os::unix::fs::symlink(&outside, mount.join("pub/escape")).unwrap();
+    std::os::unix::fs::symlink(&outside, mount.join("escape_dir")).unwrap();
+    let addr = spawn_server(listing_routes(&mount, DirectoryListing::default())).await;
+
+    // the symlink entry itself is not listed
+    let response =
+        TestClient::request(addr, b"GET /pub HTTP/1.1\r\nConnection: close\r\n\r\n").await;
+    assert_eq!(response.status, 200);
+    assert!(!response.body_string().contains("escape"));
+
+    // and a directory symlink pointing outside cannot be listed directly
+    let response = TestClient::request(
+        addr,
+        b"GET /escape_dir HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 404);
+
+    let _ = std::fs::remove_dir_all(&mount);
+    let _ = std::fs::remove_dir_all(&outside);
+}
+
 // ---------- SpaResponder ----------
 
 #[tokio::test]
@@ -150,6 +613,208 @@ async fn spa_responder_missing_index_is_not_found() {
     let _ = std::fs::remove_dir_all(&mount);
 }
 
+#[tokio::test]
+async fn spa_responder_assets_first_only_falls_back_for_navigation() {
+    let mount = temp_mount("spa_assets");
+    std::fs::create_dir_all(mount.join("js")).unwrap();
+    std::fs::write(mount.join("index.html"), b"<html>spa</html>").unwrap();
+    std::fs::write(mount.join("js/app.js"), b"run()").unwrap();
+
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/<path>"),
+        SpaResponder::new(
+            mount.to_string_lossy().into_owned(),
+            "index.html".to_owned(),
+        )
+        .expect("spa responder")
+        .with_assets_first("<path>".to_owned()),
+    );
+    let addr = spawn_server(map).await;
+    let get = |path: &str, accept: &str| {
+        format!("GET {path} HTTP/1.1\r\nAccept: {accept}\r\nConnection: close\r\n\r\n")
+    };
+
+    // real assets are served as themselves
+    let response = TestClient::request(addr, get("/js/app.js", "*/*").as_bytes()).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "run()");
+
+    // deep links navigated to by a browser get the app file
+    let response = TestClient::request(
+        addr,
+        get("/flash/23434455", "text/html,*/*;q=0.8").as_bytes(),
+    )
+    .await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "<html>spa</html>");
+
+    // missing assets, and non-navigation requests, are real 404s
+    for (path, accept) in [
+        ("/js/missing.js", "text/html"),
+        ("/flash/23434455", "application/json"),
+    ] {
+        let response = TestClient::request(addr, get(path, accept).as_bytes()).await;
+        assert_eq!(response.status, 404, "{path} ({accept})");
+    }
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+// ---------- EmbeddedResponder ----------
+
+static ASSETS: &[EmbeddedAsset] = &[
+    EmbeddedAsset {
+        path: "index.html",
+        bytes: b"<html>embedded</html>",
+        etag: "\"index\"",
+        gzip: None,
+        brotli: None,
+    },
+    EmbeddedAsset {
+        path: "js/app.js",
+        bytes: b"run()",
+        etag: "\"app\"",
+        gzip: Some(b"gzip bytes"),
+        brotli: Some(b"br bytes"),
+    },
+];
+
+fn embedded_routes() -> RouteMap<'static> {
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/<path>"),
+        EmbeddedResponder::new(ASSETS, "<path>".to_owned()),
+    );
+    map
+}
+
+#[tokio::test]
+async fn embedded_responder_serves_assets_and_index() {
+    let addr = spawn_server(embedded_routes()).await;
+
+    let response = TestClient::request(
+        addr,
+        b"GET /js/app.js HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "run()");
+    assert_eq!(
+        response.header("Content-Type"),
+        Some(&"application/javascript; charset=utf-8".to_string())
+    );
+    assert_eq!(response.header("ETag"), Some(&"\"app\"".to_string()));
+    assert_eq!(
+        response.header("Vary"),
+        Some(&"Accept-Encoding".to_string())
+    );
+
+    let response =
+        TestClient::request(addr, b"GET /?x=1 HTTP/1.1\r\nConnection: close\r\n\r\n").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "<html>embedded</html>");
+
+    for path in ["/missing.css", "/js/../index.html"] {
+        let raw = format!("GET {path} HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let response = TestClient::request(addr, raw.as_bytes()).await;
+        assert_eq!(response.status, 404, "{path}");
+    }
+}
+
+#[tokio::test]
+async fn embedded_responder_prefers_precompressed_variants() {
+    let addr = spawn_server(embedded_routes()).await;
+
+    let response = TestClient::request(
+        addr,
+        b"GET /js/app.js HTTP/1.1\r\nAccept-Encoding: gzip, br\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.body_string(), "br bytes");
+    assert_eq!(response.header("Content-Encoding"), Some(&"br".to_string()));
+    assert_eq!(response.header("ETag"), Some(&"\"app-br\"".to_string()));
+
+    let response = TestClient::request(
+        addr,
+        b"GET /js/app.js HTTP/1.1\r\nAccept-Encoding: gzip, br;q=0\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.body_string(), "gzip bytes");
+    assert_eq!(
+        response.header("Content-Encoding"),
+        Some(&"gzip".to_string())
+    );
+}
+
+#[tokio::test]
+async fn embedded_responder_revalidates_with_etags() {
+    let addr = spawn_server(embedded_routes()).await;
+
+    let response = TestClient::request(
+        addr,
+        b"GET /index.html HTTP/1.1\r\nIf-None-Match: \"index\"\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 304);
+    assert!(response.body.is_empty());
+
+    let response = TestClient::request(
+        addr,
+        b"GET /index.html HTTP/1.1\r\nIf-None-Match: \"stale\"\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 200);
+}
+
+#[tokio::test]
+async fn spa_responder_serves_an_embedded_app() {
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/<path>"),
+        SpaResponder::from_embedded(ASSETS, "index.html".to_owned()),
+    );
+    let addr = spawn_server(map).await;
+
+    let response = TestClient::request(
+        addr,
+        b"GET /flash/23434455 HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "<html>embedded</html>");
+}
+
+#[tokio::test]
+async fn spa_responder_serves_embedded_assets_first() {
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/<path>"),
+        SpaResponder::from_embedded(ASSETS, "index.html".to_owned())
+            .with_assets_first("<path>".to_owned()),
+    );
+    let addr = spawn_server(map).await;
+
+    let response = TestClient::request(
+        addr,
+        b"GET /js/app.js HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.body_string(), "run()");
+    let response = TestClient::request(
+        addr,
+        b"GET /settings HTTP/1.1\r\nAccept: text/html\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.body_string(), "<html>embedded</html>");
+    let response = TestClient::request(
+        addr,
+        b"GET /js/gone.js HTTP/1.1\r\nAccept: text/html\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 404);
+}
+
 // ---------- FileResponder safety (FR-013) ----------
 
 #[tokio::test]
@@ -237,6 +902,239 @@ async fn file_responder_denies_symlink_outside_mount() {
     let _ = std::fs::remove_dir_all(&mount);
 }
 
+// ---------- FileResponder writes ----------
+
+/// Routes `PUT`, `MKCOL`, and `DELETE` on `/<path>` to `responder`'s mount.
+fn write_routes(make: impl Fn() -> FileResponder) -> RouteMap<'static> {
+    let mut map = RouteMap::new();
+    for method in ["PUT", "MKCOL", "DELETE", "GET"] {
+        map.add_route(Route::new(method, "/<path>"), make());
+    }
+    map
+}
+
+fn put_request(path: &str, body: &[u8]) -> Vec<u8> {
+    let mut raw = format!(
+        "PUT /{path} HTTP/1.1\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    raw.extend_from_slice(body);
+    raw
+}
+
+/// Lists the names in `dir`, to check that no temporary upload files remain.
+fn entries(dir: &std::path::Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn file_responder_put_creates_then_replaces() {
+    let mount = temp_mount("put_create");
+    let addr = spawn_server(write_routes(|| file_responder(&mount))).await;
+
+    let created = TestClient::request(addr, &put_request("new.txt", b"first")).await;
+    assert_eq!(created.status, 201);
+    assert_eq!(std::fs::read(mount.join("new.txt")).unwrap(), b"first");
+
+    let replaced = TestClient::request(addr, &put_request("new.txt", b"second")).await;
+    assert_eq!(replaced.status, 204);
+    assert_eq!(std::fs::read(mount.join("new.txt")).unwrap(), b"second");
+    assert_eq!(entries(&mount), vec!["new.txt"]);
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+#[tokio::test]
+async fn file_responder_interrupted_put_keeps_the_original() {
+    let mount = temp_mount("put_interrupted");
+    std::fs::write(mount.join("keep.txt"), b"original").unwrap();
+    let addr = spawn_server(write_routes(|| file_responder(&mount))).await;
+
+    // declare ten bytes, send three, then hang up the write side
+    let mut client = TestClient::connect(addr).await;
+    client
+        .send(b"PUT /keep.txt HTTP/1.1\r\nContent-Length: 10\r\nConnection: close\r\n\r\nabc")
+        .await;
+    client.shutdown_write().await;
+    let response = client.recv().await;
+    assert_eq!(response.status, 400);
+    assert_eq!(std::fs::read(mount.join("keep.txt")).unwrap(), b"original");
+    assert_eq!(entries(&mount), vec!["keep.txt"]);
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+#[tokio::test]
+async fn file_responder_put_parent_directories_are_opt_in() {
+    let mount = temp_mount("put_parents");
+    let strict = spawn_server(write_routes(|| file_responder(&mount))).await;
+    let creating = spawn_server(write_routes(|| {
+        file_responder(&mount).with_create_dirs(true)
+    }))
+    .await;
+
+    let response = TestClient::request(strict, &put_request("a/b/c.txt", b"deep")).await;
+    assert_eq!(response.status, 409);
+    assert!(!mount.join("a").exists());
+
+    let response = TestClient::request(creating, &put_request("a/b/c.txt", b"deep")).await;
+    assert_eq!(response.status, 201);
+    assert_eq!(std::fs::read(mount.join("a/b/c.txt")).unwrap(), b"deep");
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+#[tokio::test]
+async fn file_responder_put_enforces_the_upload_limit() {
+    let mount = temp_mount("put_limit");
+    let addr = spawn_server(write_routes(|| file_responder(&mount).with_upload_limit(4))).await;
+
+    // a declared oversize body is refused up front
+    let response = TestClient::request(addr, &put_request("big.txt", b"too large")).await;
+    assert_eq!(response.status, 413);
+
+    // a chunked body is cut off once it passes the limit
+    let response = TestClient::request(
+        addr,
+        b"PUT /big.txt HTTP/1.1\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n9\r\ntoo large\r\n0\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 413);
+    assert!(entries(&mount).is_empty());
+
+    let response = TestClient::request(addr, &put_request("ok.txt", b"fits")).await;
+    assert_eq!(response.status, 201);
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+#[tokio::test]
+async fn file_responder_put_answers_expect_continue_after_validation() {
+    let mount = temp_mount("put_expect");
+    let addr = spawn_server(write_routes(|| file_responder(&mount).with_upload_limit(8))).await;
+
+    // an upload that passes validation is invited to send its body
+    let mut client = TestClient::connect(addr).await;
+    client
+        .send(b"PUT /new.txt HTTP/1.1\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n")
+        .await;
+    assert_eq!(client.recv().await.status, 100);
+    client.send(b"hello").await;
+    assert_eq!(client.recv().await.status, 201);
+    assert_eq!(std::fs::read(mount.join("new.txt")).unwrap(), b"hello");
+
+    // an oversize one gets its final status instead, before sending any body
+    let mut client = TestClient::connect(addr).await;
+    client
+        .send(b"PUT /big.txt HTTP/1.1\r\nContent-Length: 100\r\nExpect: 100-continue\r\n\r\n")
+        .await;
+    assert_eq!(client.recv().await.status, 413);
+    assert!(!mount.join("big.txt").exists());
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
+#[tokio::test]
+async fn file_responder_put_cannot_escape_the_mount() {
+    let mount = temp_mount("put_escape");
+    let outside = temp_mount("put_escape_target");
+    let addr = spawn_server(write_routes(|| {
+        file_responder(&mount).with_create_dirs(true)
+    }))
+    .await;
+
+    let escape = format!(
+        "../webe_web_test_{}_put_escape_target/owned.txt",
+        std::process::id()
+    );
+    let response = TestClient::request(addr, &put_request(&escape, b"owned")).await;
+    assert_eq!(response.status, 404);
+    assert!(!outside.join("owned.txt").exists());
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(&outside, mount.join("link")).unwrap();
+        let response = TestClient::request(addr, &put_request("link/owned.txt", b"owned")).await;
+        assert_eq!(response.status, 404);
+        assert!(!outside.join("owned.txt").exists());
+    }
+
+    let _ = std::fs::remove_dir_all(&mount);
+    let _ = std::fs::remove_dir_all(&outside);
+}
+
+#[tokio::test]
+async fn file_responder_mkcol_and_delete() {
+    let mount = temp_mount("mkcol_delete");
+    std::fs::write(mount.join("gone.txt"), b"bye").unwrap();
+    let addr = spawn_server(write_routes(|| file_responder(&mount))).await;
+
+    let request = |raw: &'static str| TestClient::request(addr, raw.as_bytes());
+
+    assert_eq!(
+        request("MKCOL /dir HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .status,
+        201
+    );
+    assert!(mount.join("dir").is_dir());
+    assert_eq!(
+        request("MKCOL /dir HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .status,
+        405
+    );
+    assert_eq!(
+        request("MKCOL /missing/dir HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .status,
+        409
+    );
+
+    assert_eq!(
+        request("DELETE /gone.txt HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .status,
+        204
+    );
+    assert!(!mount.join("gone.txt").exists());
+    assert_eq!(
+        request("DELETE /gone.txt HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .status,
+        404
+    );
+
+    std::fs::write(mount.join("dir/child.txt"), b"child").unwrap();
+    assert_eq!(
+        request("DELETE /dir HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .status,
+        409
+    );
+    std::fs::remove_file(mount.join("dir/child.txt")).unwrap();
+    assert_eq!(
+        request("DELETE /dir HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .status,
+        204
+    );
+    assert_eq!(
+        request("DELETE /../../etc/passwd HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .status,
+        404
+    );
+
+    let _ = std::fs::remove_dir_all(&mount);
+}
+
 #[tokio::test]
 async fn file_responder_put_to_directory_is_rejected() {
     let mount = temp_mount("put_dir");
@@ -2,6 +2,8 @@
 
 mod common;
 
+use std::collections::HashMap;
+
 use async_trait::async_trait;
 
 use common::{LabelResponder, StreamResponder, TestClient, spawn_server};
@@ -9,6 +11,7 @@ use webe_web::request::Request;
 use webe_web::responders::Responder;
 use webe_web::response::Response;
 use webe_web::server::{Route, RouteMap};
+use webe_web::streaming::body_channel;
 use webe_web::validation::Validation;
 
 /// A responder that returns a bodyless `204` response.
@@ -26,6 +29,37 @@ impl Responder for EmptyResponder {
     }
 }
 
+/// A responder that streams its body from a spawned task through a body
+/// channel, finishing with a trailer that totals the bytes sent.
+struct ProducerResponder;
+
+#[async_trait]
+impl Responder for ProducerResponder {
+    async fn build_response(
+        &self,
+        _request: &mut Request,
+        _params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        let (sender, body) = body_channel(1);
+        tokio::spawn(async move {
+            let mut sent = 0;
+            for row in ["id,name\n", "1,a\n", "2,b\n"] {
+                if sender.send(row).await.is_err() {
+                    return;
+                }
+                sent += row.len();
+            }
+            sender.finish(HashMap::from([("X-Bytes".to_owned(), sent.to_string())]));
+        });
+        let mut response = Response::new(200).with_streamed_body(body);
+        response
+            .headers
+            .insert("Trailer".to_owned(), "X-Bytes".to_owned());
+        Ok(response)
+    }
+}
+
 fn routes() -> RouteMap<'static> {
     let mut map = RouteMap::new();
     map.add_route(
@@ -39,6 +73,7 @@ fn routes() -> RouteMap<'static> {
         },
     );
     map.add_route(Route::new("GET", "/empty"), EmptyResponder);
+    map.add_route(Route::new("GET", "/export"), ProducerResponder);
     map
 }
 
@@ -69,6 +104,20 @@ async fn streamed_unknown_length_body_sends_chunked() {
     assert_eq!(response.body_string(), "streamed payload");
 }
 
+#[tokio::test]
+async fn channel_body_streams_chunks_then_trailers() {
+    let addr = spawn_server(routes()).await;
+    let mut client = TestClient::connect(addr).await;
+    client.send(b"GET /export HTTP/1.1\r\n\r\n").await;
+    let response = client.recv().await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body_string(), "id,name\n1,a\n2,b\n");
+    assert_eq!(response.trailers.get("x-bytes"), Some(&"16".to_string()));
+    // the trailer section ends the message, so the connection is reusable
+    client.send(b"GET /known HTTP/1.1\r\n\r\n").await;
+    assert_eq!(client.recv().await.body_string(), "known-body");
+}
+
 #[tokio::test]
 async fn bodyless_response_sends_neither_framing_header() {
     let addr = spawn_server(routes()).await;
@@ -111,3 +160,97 @@ async fn connection_close_is_honored() {
         Some("close".to_string())
     );
 }
+
+/// Sets a file body, then replaces it with a same-length body of its own.
+struct ReplacedFileResponder {
+    path: std::path::PathBuf,
+}
+
+#[async_trait]
+impl Responder for ReplacedFileResponder {
+    async fn build_response(
+        &self,
+        _request: &mut Request,
+        _params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        let file = std::fs::File::open(&self.path).map_err(|_error| 500u16)?;
+        let mut response = Response::new(200).with_file_body(file, 8);
+        response.message_body = Some(Box::pin(std::io::Cursor::new(b"replaced".to_vec())));
+        Ok(response)
+    }
+}
+
+#[tokio::test]
+async fn a_replaced_file_body_is_not_sent_from_the_file() {
+    let path = std::env::temp_dir().join(format!("webe_replaced_body_{}", std::process::id()));
+    std::fs::write(&path, b"original").unwrap();
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/file"),
+        ReplacedFileResponder { path: path.clone() },
+    );
+    let addr = spawn_server(map).await;
+
+    let mut client = TestClient::connect(addr).await;
+    for _ in 0..3 {
+        client.send(b"GET /file HTTP/1.1\r\n\r\n").await;
+        let response = client.recv().await;
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body_string(), "replaced");
+    }
+    let _ = std::fs::remove_file(path);
+}
+
+/// Sends the first four bytes of a file.
+struct FilePrefixResponder {
+    path: std::path::PathBuf,
+}
+
+#[async_trait]
+impl Responder for FilePrefixResponder {
+    async fn build_response(
+        &self,
+        _request: &mut Request,
+        _params: &Vec<(String, String)>,
+        _validation: Validation,
+    ) -> Result<Response, u16> {
+        let file = std::fs::File::open(&self.path).map_err(|_error| 500u16)?;
+        Ok(Response::new(200).with_file_body(file, 4))
+    }
+}
+
+#[tokio::test]
+async fn a_file_body_sends_only_its_length() {
+    let path = std::env::temp_dir().join(format!("webe_file_prefix_{}", std::process::id()));
+    std::fs::write(&path, b"original").unwrap();
+    let routes = || {
+        let mut map = RouteMap::new();
+        map.add_route(
+            Route::new("GET", "/file"),
+            FilePrefixResponder { path: path.clone() },
+        );
+        map
+    };
+
+    // over TCP the file is sent with sendfile, twice on one connection
+    let addr = spawn_server(routes()).await;
+    let mut client = TestClient::connect(addr).await;
+    for _ in 0..2 {
+        client.send(b"GET /file HTTP/1.1\r\n\r\n").await;
+        let response = client.recv().await;
+        assert_eq!(
+            response.header("content-length").map(String::as_str),
+            Some("4")
+        );
+        assert_eq!(response.body_string(), "orig");
+    }
+
+    // the in-memory test client has no socket, so the file is copied
+    let client = webe_web::testing::TestClient::new(routes());
+    let response = client
+        .send(webe_web::testing::TestRequest::get("/file"))
+        .await;
+    assert_eq!(response.body_string(), "orig");
+    let _ = std::fs::remove_file(path);
+}
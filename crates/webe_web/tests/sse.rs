@@ -0,0 +1,153 @@
+//! Integration tests for server-sent event responses.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::{TestClient, spawn_server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use webe_web::responders::sse::{Event, EventStream, SseResponder};
+use webe_web::server::{Route, RouteMap};
+
+#[tokio::test]
+async fn events_are_streamed_until_the_source_ends() {
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/events"),
+        SseResponder::new(|_request, _params, last_event_id| {
+            let (sender, receiver) = mpsc::channel(4);
+            let resume = last_event_id.unwrap_or("none").to_owned();
+            tokio::spawn(async move {
+                let _ = sender
+                    .send(Event::new(format!("resumed after {resume}")).with_id("1"))
+                    .await;
+                let _ = sender
+                    .send(
+                        Event::new("50%")
+                            .with_id("2")
+                            .with_event("progress")
+                            .with_retry(Duration::from_secs(3)),
+                    )
+                    .await;
+                // dropping the sender ends the stream
+            });
+            EventStream::from(receiver)
+        }),
+    );
+    let addr = spawn_server(map).await;
+
+    let response = TestClient::request(
+        addr,
+        b"GET /events HTTP/1.1\r\nLast-Event-ID: 41\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.header("Content-Type"),
+        Some(&"text/event-stream".to_string())
+    );
+    assert_eq!(
+        response.header("Transfer-Encoding"),
+        Some(&"chunked".to_string())
+    );
+    assert_eq!(
+        response.body_string(),
+        "id: 1\ndata: resumed after 41\n\nid: 2\nevent: progress\nretry: 3000\ndata: 50%\n\n"
+    );
+}
+
+#[tokio::test]
+async fn idle_streams_send_keep_alives_and_stop_when_the_client_leaves() {
+    let senders: Arc<Mutex<Vec<mpsc::Sender<Event>>>> = Arc::default();
+    let handoff = senders.clone();
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/events"),
+        SseResponder::new(move |_request, _params, _last_event_id| {
+            let (sender, receiver) = mpsc::channel(4);
+            handoff.lock().unwrap().push(sender);
+            EventStream::from(receiver)
+        })
+        .with_keep_alive(Duration::from_millis(50)),
+    );
+    let addr = spawn_server(map).await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /events HTTP/1.1\r\n\r\n")
+        .await
+        .unwrap();
+    let mut received = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !String::from_utf8_lossy(&received).contains(": keep-alive\n\n") {
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+            .await
+            .expect("a keep-alive comment should arrive")
+            .unwrap();
+        assert!(read > 0, "the stream should stay open");
+        received.extend_from_slice(&buf[..read]);
+    }
+
+    // an event sent now is flushed right away
+    let sender = senders.lock().unwrap().pop().expect("stream was opened");
+    sender.send(Event::new("live")).await.unwrap();
+    while !String::from_utf8_lossy(&received).contains("data: live\n\n") {
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+            .await
+            .expect("the event should arrive")
+            .unwrap();
+        received.extend_from_slice(&buf[..read]);
+    }
+
+    // once the client disconnects, the next write fails and the source is dropped
+    drop(stream);
+    tokio::time::timeout(Duration::from_secs(5), sender.closed())
+        .await
+        .expect("the server should drop the event source after a disconnect");
+}
+
+#[tokio::test]
+async fn events_reach_http_1_0_clients_as_they_are_produced() {
+    let senders: Arc<Mutex<Vec<mpsc::Sender<Event>>>> = Arc::default();
+    let handoff = senders.clone();
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/events"),
+        SseResponder::new(move |_request, _params, _last_event_id| {
+            let (sender, receiver) = mpsc::channel(4);
+            handoff.lock().unwrap().push(sender);
+            EventStream::from(receiver)
+        }),
+    );
+    let addr = spawn_server(map).await;
+
+    // HTTP/1.0 has no chunked coding, so the body runs until the connection closes
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /events HTTP/1.0\r\n\r\n")
+        .await
+        .unwrap();
+    let sender = loop {
+        if let Some(sender) = senders.lock().unwrap().pop() {
+            break sender;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    };
+    sender.send(Event::new("live")).await.unwrap();
+    let mut received = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !String::from_utf8_lossy(&received).contains("data: live\n\n") {
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+            .await
+            .expect("the event should arrive while the stream is open")
+            .unwrap();
+        assert!(read > 0, "the stream should stay open");
+        received.extend_from_slice(&buf[..read]);
+    }
+    let received = String::from_utf8_lossy(&received);
+    assert!(received.starts_with("HTTP/1.0 200"));
+    assert!(!received.to_lowercase().contains("transfer-encoding"));
+}
@@ -0,0 +1,160 @@
+//! Integration tests for the WebSocket upgrade handshake and message exchange.
+
+mod common;
+
+use std::time::Duration;
+
+use common::{TestClient, spawn_server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use webe_web::responders::websocket::WebSocketResponder;
+use webe_web::server::{Route, RouteMap};
+use webe_web::websocket::{Message, WebSocket};
+
+const KEY: &str = "dGhlIHNhbXBsZSBub25jZQ==";
+
+fn echo_routes() -> RouteMap<'static> {
+    let mut map = RouteMap::new();
+    map.add_route(
+        Route::new("GET", "/ws/<room>"),
+        WebSocketResponder::new(|mut socket: WebSocket, params| async move {
+            let room = params[0].1.clone();
+            while let Some(Ok(message)) = socket.recv().await {
+                if let Message::Text(text) = message {
+                    let reply = format!("{room}: {text}");
+                    if socket.send(Message::Text(reply)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        })
+        .with_protocols(vec!["chat".to_owned()]),
+    );
+    map
+}
+
+/// Encodes a masked client frame with a short payload.
+fn client_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    assert!(payload.len() <= 125);
+    let mask = [0xA1, 0xB2, 0xC3, 0xD4];
+    let mut frame = vec![0x80 | opcode, 0x80 | payload.len() as u8];
+    frame.extend_from_slice(&mask);
+    frame.extend(
+        payload
+            .iter()
+            .enumerate()
+            .map(|(index, byte)| byte ^ mask[index % 4]),
+    );
+    frame
+}
+
+/// Reads one short unmasked server frame, returning its opcode and payload.
+async fn server_frame(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let mut header = [0u8; 2];
+    tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut header))
+        .await
+        .expect("a frame should arrive")
+        .unwrap();
+    assert_eq!(header[1] & 0x80, 0, "server frames are never masked");
+    let mut payload = vec![0u8; usize::from(header[1] & 0x7F)];
+    stream.read_exact(&mut payload).await.unwrap();
+    (header[0] & 0x0F, payload)
+}
+
+async fn read_head(stream: &mut TcpStream) -> String {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0u8; 1];
+        stream.read_exact(&mut byte).await.unwrap();
+        head.push(byte[0]);
+    }
+    String::from_utf8(head).unwrap()
+}
+
+#[tokio::test]
+async fn upgraded_connections_exchange_messages_and_close_cleanly() {
+    let addr = spawn_server(echo_routes()).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut request = format!(
+        "GET /ws/lobby HTTP/1.1\r\nHost: test\r\nUpgrade: websocket\r\n\
+         Connection: keep-alive, Upgrade\r\nSec-WebSocket-Key: {KEY}\r\n\
+         Sec-WebSocket-Version: 13\r\nSec-WebSocket-Protocol: superchat, chat\r\n\r\n"
+    )
+    .into_bytes();
+    // a frame sent right behind the handshake is not lost
+    request.extend(client_frame(0x1, b"early"));
+    stream.write_all(&request).await.unwrap();
+
+    let head = read_head(&mut stream).await;
+    assert!(head.starts_with("HTTP/1.1 101 "), "{head}");
+    assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+    assert!(head.contains("Sec-WebSocket-Protocol: chat\r\n"));
+    assert!(head.contains("Upgrade: websocket\r\n"));
+    assert!(head.contains("Connection: Upgrade\r\n"));
+
+    assert_eq!(
+        server_frame(&mut stream).await,
+        (0x1, b"lobby: early".to_vec())
+    );
+    stream
+        .write_all(&client_frame(0x9, b"ping?"))
+        .await
+        .unwrap();
+    assert_eq!(server_frame(&mut stream).await, (0xA, b"ping?".to_vec()));
+    stream.write_all(&client_frame(0x1, b"hi")).await.unwrap();
+    assert_eq!(
+        server_frame(&mut stream).await,
+        (0x1, b"lobby: hi".to_vec())
+    );
+
+    stream
+        .write_all(&client_frame(0x8, &1000u16.to_be_bytes()))
+        .await
+        .unwrap();
+    assert_eq!(server_frame(&mut stream).await, (0x8, vec![0x03, 0xE8]));
+    let mut rest = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut rest))
+        .await
+        .expect("the server should close the connection")
+        .unwrap();
+    assert!(rest.is_empty());
+}
+
+#[tokio::test]
+async fn invalid_handshakes_are_rejected() {
+    let addr = spawn_server(echo_routes()).await;
+
+    let missing_upgrade = TestClient::request(
+        addr,
+        format!(
+            "GET /ws/a HTTP/1.1\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {KEY}\r\n\
+             Sec-WebSocket-Version: 13\r\nContent-Length: 0\r\n\r\n"
+        )
+        .as_bytes(),
+    )
+    .await;
+    assert_eq!(missing_upgrade.status, 400);
+
+    let short_key = TestClient::request(
+        addr,
+        b"GET /ws/a HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+          Sec-WebSocket-Key: c2hvcnQ=\r\nSec-WebSocket-Version: 13\r\n\r\n",
+    )
+    .await;
+    assert_eq!(short_key.status, 400);
+
+    let old_version = TestClient::request(
+        addr,
+        format!(
+            "GET /ws/a HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: {KEY}\r\nSec-WebSocket-Version: 8\r\n\r\n"
+        )
+        .as_bytes(),
+    )
+    .await;
+    assert_eq!(old_version.status, 426);
+    assert_eq!(
+        old_version.header("Sec-WebSocket-Version"),
+        Some(&"13".to_string())
+    );
+}